@@ -0,0 +1,325 @@
+// This module contains the loading of `WindowConf` from TOML files. Watching of
+// these files for live reload is done by `SpellWin`.
+use crate::configure::{Dimension, MonitorSelector, WindowConf};
+use serde::Deserialize;
+use smithay_client_toolkit::shell::wlr_layer::{Anchor, KeyboardInteractivity, Layer};
+use std::{collections::HashMap, error::Error, fs, path::Path};
+
+/// Dimension as written in the file, either pixels, a string like `"full"`,
+/// `"auto"` or `"50%"`, or a table of bounds for auto dimension.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum DimensionValue {
+    Pixel(u32),
+    Text(String),
+    Auto { min: Option<u32>, max: Option<u32> },
+}
+
+impl TryFrom<DimensionValue> for Dimension {
+    type Error = Box<dyn Error>;
+
+    fn try_from(value: DimensionValue) -> Result<Self, Self::Error> {
+        match value {
+            DimensionValue::Pixel(pixels) => Ok(Dimension::Pixel(pixels)),
+            DimensionValue::Auto { min, max } => Ok(Dimension::Auto { min, max }),
+            DimensionValue::Text(text) => match text.trim() {
+                "full" => Ok(Dimension::Full),
+                "auto" => Ok(Dimension::auto()),
+                text => {
+                    if let Some(percentage) = text.strip_suffix('%') {
+                        Ok(Dimension::Percentage(percentage.trim().parse()?))
+                    } else {
+                        Ok(Dimension::Pixel(text.parse()?))
+                    }
+                }
+            },
+        }
+    }
+}
+
+/// Monitor as written in the file, either its name (or `"primary"`, `"focused"`)
+/// or a table with one of the ways of selecting it.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum MonitorValue {
+    Name(String),
+    Index {
+        index: usize,
+    },
+    Description {
+        description: String,
+    },
+    MakeModel {
+        make: String,
+        model: String,
+        serial: Option<String>,
+    },
+}
+
+impl From<MonitorValue> for MonitorSelector {
+    fn from(value: MonitorValue) -> Self {
+        match value {
+            MonitorValue::Name(name) => match name.as_str() {
+                "primary" => MonitorSelector::Primary,
+                "focused" => MonitorSelector::Focused,
+                _ => MonitorSelector::Name(name),
+            },
+            MonitorValue::Index { index } => MonitorSelector::Index(index),
+            MonitorValue::Description { description } => MonitorSelector::Description(description),
+            MonitorValue::MakeModel {
+                make,
+                model,
+                serial,
+            } => MonitorSelector::MakeModel {
+                make,
+                model,
+                serial,
+            },
+        }
+    }
+}
+
+/// Settings of a single widget in the file.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct WidgetSection {
+    width: Option<DimensionValue>,
+    height: Option<DimensionValue>,
+    anchors: Vec<String>,
+    margins: Option<(i32, i32, i32, i32)>,
+    layer: Option<String>,
+    keyboard: Option<String>,
+    monitor: Option<MonitorValue>,
+    exclusive_zone: Option<i32>,
+    natural_scroll: Option<bool>,
+}
+
+fn parse_anchor(anchor: &str) -> Result<Anchor, Box<dyn Error>> {
+    match anchor {
+        "top" => Ok(Anchor::TOP),
+        "bottom" => Ok(Anchor::BOTTOM),
+        "left" => Ok(Anchor::LEFT),
+        "right" => Ok(Anchor::RIGHT),
+        other => Err(format!("unknown anchor: {}", other).into()),
+    }
+}
+
+fn parse_layer(layer: &str) -> Result<Layer, Box<dyn Error>> {
+    match layer {
+        "background" => Ok(Layer::Background),
+        "bottom" => Ok(Layer::Bottom),
+        "top" => Ok(Layer::Top),
+        "overlay" => Ok(Layer::Overlay),
+        other => Err(format!("unknown layer: {}", other).into()),
+    }
+}
+
+fn parse_keyboard(keyboard: &str) -> Result<KeyboardInteractivity, Box<dyn Error>> {
+    match keyboard {
+        "none" => Ok(KeyboardInteractivity::None),
+        "exclusive" => Ok(KeyboardInteractivity::Exclusive),
+        "on_demand" | "on-demand" => Ok(KeyboardInteractivity::OnDemand),
+        other => Err(format!("unknown keyboard interactivity: {}", other).into()),
+    }
+}
+
+impl WindowConf {
+    /// Loads the [`WindowConf`] of a widget from a TOML file, in which every widget
+    /// has its own table named after `section`. Values which are not given take the
+    /// defaults of [`WindowConfBuilder`](crate::layer_properties::WindowConfBuilder).
+    /// The file is watched while the widget runs and changes in it are applied to
+    /// the widget without a restart (see [`SpellWin::apply_window_conf`](crate::wayland_adapter::SpellWin::apply_window_conf)).
+    ///
+    /// ```toml
+    /// [top-bar]
+    /// width = "full"              # pixels, "full", "auto", "50%" or { min = 10, max = 100 }
+    /// height = 30
+    /// anchors = ["top", "left", "right"]
+    /// margins = [5, 5, 0, 5]      # top, right, bottom, left
+    /// layer = "top"               # background, bottom, top or overlay
+    /// keyboard = "none"           # none, exclusive or on_demand
+    /// monitor = "DP-3"            # name, "primary", "focused", { index = 0 },
+    ///                             # { description = "Dell" } or { make = "..", model = ".." }
+    /// exclusive_zone = 30
+    /// natural_scroll = false
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the file can't be read or parsed, has no table for the
+    /// section or if the resulting configuration is invalid.
+    pub fn from_file<P: AsRef<Path>>(path: P, section: &str) -> Result<Self, Box<dyn Error>> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path)?;
+        let mut sections: HashMap<String, WidgetSection> = toml::from_str(&contents)?;
+        let widget = sections
+            .remove(section)
+            .ok_or_else(|| format!("no table for {} in {}", section, path.display()))?;
+
+        let mut builder = WindowConf::builder();
+        if let Some(width) = widget.width {
+            builder.width(Dimension::try_from(width)?);
+        }
+        if let Some(height) = widget.height {
+            builder.height(Dimension::try_from(height)?);
+        }
+        let mut anchor = Anchor::empty();
+        for value in widget.anchors.iter() {
+            anchor |= parse_anchor(value)?;
+        }
+        if !anchor.is_empty() {
+            builder.anchor_1(anchor);
+        }
+        if let Some((top, right, bottom, left)) = widget.margins {
+            builder.margins(top, right, bottom, left);
+        }
+        if let Some(layer) = widget.layer {
+            builder.layer_type(parse_layer(&layer)?);
+        }
+        if let Some(keyboard) = widget.keyboard {
+            builder.board_interactivity(parse_keyboard(&keyboard)?);
+        }
+        if let Some(monitor) = widget.monitor {
+            builder.monitor(MonitorSelector::from(monitor));
+        }
+        if let Some(zone) = widget.exclusive_zone {
+            builder.exclusive_zone(zone);
+        }
+        if let Some(scroll) = widget.natural_scroll {
+            builder.natural_scroll(scroll);
+        }
+        let mut window_conf = builder.build()?;
+        window_conf.config_file = Some((path.to_path_buf(), section.to_string()));
+        Ok(window_conf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, path::PathBuf};
+
+    fn parse<T: for<'de> Deserialize<'de>>(value: &str) -> T {
+        let mut table: HashMap<String, T> = toml::from_str(&format!("value = {}", value)).unwrap();
+        table.remove("value").unwrap()
+    }
+
+    fn write_file(name: &str, contents: &str) -> PathBuf {
+        let path = env::temp_dir().join(format!("spell-{}-{}.toml", name, std::process::id()));
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn dimension_values() {
+        let cases = [
+            ("30", "Pixel(30)"),
+            ("\"30\"", "Pixel(30)"),
+            ("\"full\"", "Full"),
+            ("\"auto\"", "Auto { min: None, max: None }"),
+            ("\"50%\"", "Percentage(50)"),
+            (
+                "{ min = 10, max = 100 }",
+                "Auto { min: Some(10), max: Some(100) }",
+            ),
+            ("{ max = 100 }", "Auto { min: None, max: Some(100) }"),
+        ];
+        for (value, expected) in cases {
+            let dimension = Dimension::try_from(parse::<DimensionValue>(value)).unwrap();
+            assert_eq!(format!("{:?}", dimension), expected, "value: {}", value);
+        }
+        for value in ["\"wide\"", "\"half%\"", "\"-5\""] {
+            assert!(
+                Dimension::try_from(parse::<DimensionValue>(value)).is_err(),
+                "value: {}",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn monitor_values() {
+        let cases = [
+            ("\"primary\"", MonitorSelector::Primary),
+            ("\"focused\"", MonitorSelector::Focused),
+            ("\"DP-3\"", MonitorSelector::Name("DP-3".to_string())),
+            ("{ index = 1 }", MonitorSelector::Index(1)),
+            (
+                "{ description = \"Dell\" }",
+                MonitorSelector::Description("Dell".to_string()),
+            ),
+            (
+                "{ make = \"Dell\", model = \"U2720Q\" }",
+                MonitorSelector::MakeModel {
+                    make: "Dell".to_string(),
+                    model: "U2720Q".to_string(),
+                    serial: None,
+                },
+            ),
+        ];
+        for (value, expected) in cases {
+            let monitor = MonitorSelector::from(parse::<MonitorValue>(value));
+            assert_eq!(monitor, expected, "value: {}", value);
+        }
+    }
+
+    #[test]
+    fn window_conf_from_file() {
+        let path = write_file(
+            "from-file",
+            r#"
+            [top-bar]
+            width = "full"
+            height = 30
+            anchors = ["top", "left"]
+            margins = [5, 5, 0, 5]
+            layer = "overlay"
+            keyboard = "on_demand"
+            monitor = { index = 0 }
+            exclusive_zone = 30
+            natural_scroll = true
+
+            [other]
+            height = 10
+            "#,
+        );
+        let conf = WindowConf::from_file(&path, "top-bar").unwrap();
+        assert_eq!(format!("{:?}", conf.width), "Full");
+        assert_eq!(format!("{:?}", conf.height), "Pixel(30)");
+        assert_eq!(conf.anchor[0], Some(Anchor::TOP | Anchor::LEFT));
+        assert_eq!(conf.margin, (5, 5, 0, 5));
+        assert_eq!(conf.layer_type, Layer::Overlay);
+        assert_eq!(
+            conf.board_interactivity.get(),
+            KeyboardInteractivity::OnDemand
+        );
+        assert_eq!(conf.monitor, Some(MonitorSelector::Index(0)));
+        assert_eq!(conf.exclusive_zone, Some(30));
+        assert!(conf.natural_scroll);
+        assert_eq!(
+            conf.config_file,
+            Some((path.clone(), "top-bar".to_string()))
+        );
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn window_conf_from_file_errors() {
+        let cases = [
+            ("missing-section", "[other]\nheight = 10\n"),
+            ("unknown-field", "[top-bar]\ncolour = \"red\"\n"),
+            ("unknown-anchor", "[top-bar]\nanchors = [\"middle\"]\n"),
+            ("zero-width", "[top-bar]\nwidth = 0\nheight = 10\n"),
+        ];
+        for (name, contents) in cases {
+            let path = write_file(name, contents);
+            assert!(
+                WindowConf::from_file(&path, "top-bar").is_err(),
+                "case: {}",
+                name
+            );
+            fs::remove_file(path).unwrap();
+        }
+        assert!(WindowConf::from_file("/nonexistent/spell.toml", "top-bar").is_err());
+    }
+}
@@ -1,17 +1,19 @@
 use crate::layer_properties::popup::{PopupAnchor, PopupGravity};
 use smithay_client_toolkit::{
+    output::OutputInfo,
+    reexports::client::protocol::wl_output::WlOutput,
     shell::{
         wlr_layer::{Anchor, KeyboardInteractivity, Layer},
         xdg::popup::Popup,
     },
-    shm::slot::{Buffer, SlotPool},
+    shm::slot::SlotPool,
 };
 use std::{
     cell::{Cell, RefCell},
     fs,
     io::Write,
     os::unix::net::UnixDatagram,
-    path::Path,
+    path::{Path, PathBuf},
     rc::Rc,
     sync::Mutex,
 };
@@ -31,7 +33,6 @@ pub struct PopupCore {
     pub(crate) pool: Rc<RefCell<SlotPool>>,
     pub(crate) popup: Popup,
     pub(crate) popup_conf: PopupConf,
-    pub(crate) buffer: Buffer,
     // pub(crate) viewport: Viewport,
 }
 
@@ -64,13 +65,135 @@ impl From<u32> for Dimension {
 /// case it simply returns an instant of [`Dimension::Pixel`].
 #[derive(Debug, Clone, Default)]
 pub enum Dimension {
-    /// Full screen Dimension of the selected monitor.
+    /// Full screen Dimension of the selected monitor, less the margins. If the widget
+    /// is anchored to both edges of the axis, the compositor sizes it to the area left
+    /// by exclusive zones of other layers.
     #[default]
     Full,
-    /// Whole number percentage Dimension of width/height, relative to the selected monitor.
+    /// Whole number percentage Dimension of width/height, relative to the same axis of
+    /// the selected monitor less the margins. Like `Full`, it is re-evaluated when the
+    /// monitor's mode or scale changes.
     Percentage(u32),
     /// Definition of widgets in static pixels.
     Pixel(u32),
+    /// Size of the widget follows the preferred size of the layout of the root slint
+    /// component, bounded by optional minimum and maximum pixels. The widget is
+    /// resized whenever the preferred size changes, so it hugs its content. Until the
+    /// slint component is created, the widget takes the minimum size (or 1 pixel).
+    Auto {
+        /// Minimum size in pixels.
+        min: Option<u32>,
+        /// Maximum size in pixels.
+        max: Option<u32>,
+    },
+}
+
+impl Dimension {
+    /// Returns [`Dimension::Auto`] without any bounds.
+    pub fn auto() -> Self {
+        Dimension::Auto {
+            min: None,
+            max: None,
+        }
+    }
+
+    /// Evaluates the size of an [`Dimension::Auto`] axis from the preferred size
+    /// of slint's layout.
+    pub(crate) fn auto_size(preferred: Option<f32>, min: Option<u32>, max: Option<u32>) -> u32 {
+        let size = preferred.map_or(min.unwrap_or(1), |preferred| preferred.ceil() as u32);
+        let size = size.max(min.unwrap_or(1));
+        match max {
+            Some(max) => size.min(max),
+            None => size,
+        }
+    }
+}
+
+/// Selects the monitor on which a widget is created. Connector names (like `DP-3`)
+/// can change between docks and machines, so other variants select the monitor by
+/// its own properties instead. `String` and `&str` convert into [`MonitorSelector::Name`].
+#[derive(Debug, Clone, PartialEq)]
+pub enum MonitorSelector {
+    /// Connector name of the monitor, like `DP-3` or `eDP-1`.
+    Name(String),
+    /// Make and model of the monitor as given by its EDID. Wayland doesn't provide
+    /// the serial number of a monitor directly, so it is searched for in the
+    /// description of the monitor, which contains it on most compositors.
+    MakeModel {
+        /// Manufacturer of the monitor.
+        make: String,
+        /// Model name of the monitor.
+        model: String,
+        /// Serial number of the monitor.
+        serial: Option<String>,
+    },
+    /// A part of the human readable description of the monitor.
+    Description(String),
+    /// Index of the monitor when monitors are ordered from left to right (and top to
+    /// bottom) by their position in the global space.
+    Index(usize),
+    /// The primary monitor. Wayland has no notion of it, so the monitor at the
+    /// origin of the global space is taken, or the leftmost if none is.
+    Primary,
+    /// The monitor which currently has focus. It is decided by the compositor when
+    /// the widget is created, size of the widget is evaluated once it is shown on it.
+    Focused,
+}
+
+impl From<String> for MonitorSelector {
+    fn from(value: String) -> Self {
+        MonitorSelector::Name(value)
+    }
+}
+
+impl From<&str> for MonitorSelector {
+    fn from(value: &str) -> Self {
+        MonitorSelector::Name(value.to_string())
+    }
+}
+
+impl MonitorSelector {
+    /// Returns the output matching the selector among the given outputs. `None` is
+    /// returned for [`MonitorSelector::Focused`], as the compositor chooses it.
+    pub(crate) fn resolve<'a>(
+        &self,
+        outputs: &'a [(WlOutput, OutputInfo)],
+    ) -> Option<&'a (WlOutput, OutputInfo)> {
+        let by_position = || {
+            let mut sorted: Vec<&(WlOutput, OutputInfo)> = outputs.iter().collect();
+            sorted.sort_by_key(|(_, info)| info.logical_position.unwrap_or_default());
+            sorted
+        };
+        match self {
+            MonitorSelector::Name(name) => outputs
+                .iter()
+                .find(|(_, info)| info.name.as_ref() == Some(name)),
+            MonitorSelector::MakeModel {
+                make,
+                model,
+                serial,
+            } => outputs.iter().find(|(_, info)| {
+                info.make == *make
+                    && info.model == *model
+                    && serial.as_ref().is_none_or(|serial| {
+                        info.description
+                            .as_ref()
+                            .is_some_and(|description| description.contains(serial))
+                    })
+            }),
+            MonitorSelector::Description(part) => outputs.iter().find(|(_, info)| {
+                info.description
+                    .as_ref()
+                    .is_some_and(|description| description.contains(part))
+            }),
+            MonitorSelector::Index(index) => by_position().get(*index).copied(),
+            MonitorSelector::Primary => outputs
+                .iter()
+                .find(|(_, info)| info.logical_position == Some((0, 0)))
+                .or_else(|| by_position().first().copied()),
+            MonitorSelector::Focused => None,
+        }
+    }
 }
 
 /// WindowConf is an essential struct passed on to widget constructor functions (like invoke_spell
@@ -79,7 +202,8 @@ pub enum Dimension {
 /// ## Panics
 ///
 /// 1. Event loops ([cast_spell](crate::cast_spell)) will panic if 0 is provided as width or height.
-/// 2. Builder will also panic if percentage or full is used without specifying the monitor explicitly.
+/// 2. Builder will also panic if percentage or full is used without specifying the monitor explicitly,
+///    unless the widget is created on all outputs.
 #[derive(Debug, Clone)]
 pub struct WindowConf {
     pub(super) width: Dimension,
@@ -93,8 +217,11 @@ pub struct WindowConf {
     pub(super) layer_type: Layer,
     pub(super) board_interactivity: Cell<KeyboardInteractivity>,
     pub(super) exclusive_zone: Option<i32>,
-    pub(super) monitor_name: Option<String>,
+    pub(super) monitor: Option<MonitorSelector>,
+    pub(super) all_outputs: bool,
     pub(super) natural_scroll: bool,
+    /// File (along with the section in it) from which the configuration is loaded.
+    pub(super) config_file: Option<(PathBuf, String)>,
 }
 
 impl WindowConf {
@@ -115,16 +242,16 @@ pub struct WindowConfBuilder {
     layer_type: Option<Layer>,
     board_interactivity: KeyboardInteractivity,
     exclusive_zone: Option<i32>,
-    monitor_name: Option<String>,
+    monitor: Option<MonitorSelector>,
+    all_outputs: bool,
     natural_scroll: bool,
 }
 
 impl WindowConfBuilder {
-    /// Defines the widget width in pixels, fullscreen width or pecentage width
-    /// of full screen. On setting values greater than the provided pixels of
+    /// Defines the widget width in pixels, fullscreen width, pecentage width
+    /// of full screen or width of the content (see [`Dimension::Auto`]). On setting values greater than the provided pixels of
     /// monitor, the widget offsets from monitor's rectangular monitor space.
-    /// It is important to note that the value should be the maximum width the
-    /// widget will ever attain, not the current width in case of resizeable widgets.
+    /// The width can later be changed with [`SpellWin::set_size`](crate::wayland_adapter::SpellWin::set_size).
     /// This value has full screen width as its default.
     pub fn width<I: Into<Dimension>>(&mut self, width: I) -> &mut Self {
         let new = self;
@@ -132,11 +259,10 @@ impl WindowConfBuilder {
         new
     }
 
-    /// Defines the widget height in pixels, fullscreen width or pecentage height
-    /// of full screen. On setting values greater than the provided pixels of
-    /// monitor, the widget offsets from monitor's rectangular monitor space. It
-    /// is important to note that the value should be the maximum height the widget
-    /// will ever attain, not the current height in case of resizeable widgets.
+    /// Defines the widget height in pixels, fullscreen width, pecentage height
+    /// of full screen or height of the content (see [`Dimension::Auto`]). On setting values greater than the provided pixels of
+    /// monitor, the widget offsets from monitor's rectangular monitor space.
+    /// The height can later be changed with [`SpellWin::set_size`](crate::wayland_adapter::SpellWin::set_size).
     /// This value has full screen height as its default.
     pub fn height<I: Into<Dimension>>(&mut self, height: I) -> &mut Self {
         let x = self;
@@ -215,13 +341,24 @@ impl WindowConfBuilder {
         x
     }
 
-    /// Defines the monitor name on which to spawn the window. It is necessary
-    /// to set this value if a percentage dimention is given to either width or
-    /// height; When no monitor is provided, the window is spawned on the
-    /// default monitor.
-    pub fn monitor(&mut self, name: String) -> &mut Self {
+    /// Defines the monitor on which to spawn the window, either by its name or
+    /// by a [`MonitorSelector`]. It is necessary to set this value if a percentage
+    /// dimention is given to either width or height; When no monitor is provided
+    /// (or the selected one isn't found), the window is spawned on the default monitor.
+    pub fn monitor<M: Into<MonitorSelector>>(&mut self, monitor: M) -> &mut Self {
         let x = self;
-        x.monitor_name = Some(name);
+        x.monitor = Some(monitor.into());
+        x
+    }
+
+    /// Makes the widget to be created once on every connected monitor, along with
+    /// monitors plugged in later, when it is invoked for outputs (see
+    /// [`SpellOutputs`](crate::wayland_adapter::SpellOutputs)). Instances are destroyed
+    /// when their monitor is removed. Full and percentage dimensions are evaluated
+    /// for each monitor separately, so monitor name need not be set.
+    pub fn on_all_outputs(&mut self) -> &mut Self {
+        let x = self;
+        x.all_outputs = true;
         x
     }
 
@@ -247,6 +384,13 @@ impl WindowConfBuilder {
                 && y == 0
             {
                 return Err("width is zero in pixel".into());
+            } else if let Dimension::Auto {
+                min: Some(min),
+                max: Some(max),
+            } = self.max_width
+                && min > max
+            {
+                return Err("minimum width is greater than maximum width".into());
             } else {
                 self.max_width.clone()
             },
@@ -258,6 +402,13 @@ impl WindowConfBuilder {
                 && y == 0
             {
                 return Err("height is zero in pixel".into());
+            } else if let Dimension::Auto {
+                min: Some(min),
+                max: Some(max),
+            } = self.max_height
+                && min > max
+            {
+                return Err("minimum height is greater than maximum height".into());
             } else {
                 self.max_height.clone()
             },
@@ -271,21 +422,23 @@ impl WindowConfBuilder {
             },
             board_interactivity: Cell::new(self.board_interactivity),
             exclusive_zone: self.exclusive_zone,
-            monitor_name: {
+            monitor: {
                 let needs_monitor =
                     matches!(self.max_width, Dimension::Full | Dimension::Percentage(_))
                         || matches!(self.max_height, Dimension::Full | Dimension::Percentage(_));
 
-                if needs_monitor && self.monitor_name.is_none() {
+                if needs_monitor && self.monitor.is_none() && !self.all_outputs {
                     return Err(
                         "Provide explicit monitor name if using Full or Percentage dimensions"
                             .into(),
                     );
                 } else {
-                    self.monitor_name.clone()
+                    self.monitor.clone()
                 }
             },
+            all_outputs: self.all_outputs,
             natural_scroll: self.natural_scroll,
+            config_file: None,
         })
     }
 }
@@ -10,6 +10,7 @@ macro_rules! generate_widgets {
                 struct [<$slint_win Spell>] {
                     ui: $slint_win ,
                     way: SpellWin,
+                    board: Option<$crate::wayland_adapter::BoardHandle>,
                 }
 
                 impl std::fmt::Debug for [<$slint_win Spell>] {
@@ -25,9 +26,47 @@ macro_rules! generate_widgets {
                         let way_win = SpellWin::invoke_spell(name, window_conf);
                         [<$slint_win Spell>] {
                             ui: $slint_win::new().unwrap(),
-                            way: way_win
+                            way: way_win,
+                            board: None,
                         }
                     }
+                    /// Creates the widget as an on-screen keyboard, details are documented
+                    /// in [`crate::wayland_adapter::SpellBoard`].
+                    pub fn invoke_board(name: &str, window_conf: WindowConf) -> Self {
+                        let way_win = $crate::wayland_adapter::SpellBoard::invoke_spell(name, window_conf);
+                        [<$slint_win Spell>] {
+                            ui: $slint_win::new().unwrap(),
+                            board: Some(way_win.get_handler()),
+                            way: way_win.into_inner(),
+                        }
+                    }
+
+                    /// Creates instances of the widget on monitors, details are documented
+                    /// in [`crate::wayland_adapter::SpellOutputs`]. `setup` is called with the
+                    /// UI of every instance after its creation.
+                    pub fn invoke_spell_per_output<F: FnMut(&$slint_win) + 'static>(
+                        name: &str,
+                        window_conf: WindowConf,
+                        setup: F,
+                    ) -> $crate::wayland_adapter::SpellOutputs {
+                        Self::try_invoke_spell_per_output(name, window_conf, setup)
+                            .unwrap_or_else(|err| panic!("{}", err))
+                    }
+
+                    /// Same as `invoke_spell_per_output`, but returns an error instead of
+                    /// panicking when the outputs of the compositor can't be watched.
+                    pub fn try_invoke_spell_per_output<F: FnMut(&$slint_win) + 'static>(
+                        name: &str,
+                        window_conf: WindowConf,
+                        mut setup: F,
+                    ) -> Result<$crate::wayland_adapter::SpellOutputs, $crate::wayland_adapter::SpellError> {
+                        $crate::wayland_adapter::SpellOutputs::new(name, window_conf, move |instance_name, conf| {
+                            let widget = Self::invoke_spell(instance_name, conf);
+                            setup(&widget.ui);
+                            Box::new(widget) as Box<dyn $crate::SpellAssociatedNew>
+                        })
+                    }
+
                     /// Internally calls [`crate::wayland_adapter::SpellWin::hide`]
                     pub fn hide(&self) {
                         self.way.hide();
@@ -77,11 +116,50 @@ macro_rules! generate_widgets {
                     pub fn set_exclusive_zone(&mut self, val: i32) {
                         self.way.set_exclusive_zone(val);
                     }
+
+                    /// Internally calls [`crate::wayland_adapter::SpellWin::set_size`]
+                    pub fn set_size(
+                        &mut self,
+                        width: $crate::layer_properties::Dimension,
+                        height: $crate::layer_properties::Dimension,
+                    ) {
+                        self.way.set_size(width, height);
+                    }
+
+                    /// Internally calls [`crate::wayland_adapter::SpellWin::set_anchor`]
+                    pub fn set_anchor(&mut self, anchor: $crate::layer_properties::LayerAnchor) {
+                        self.way.set_anchor(anchor);
+                    }
+
+                    /// Internally calls [`crate::wayland_adapter::SpellWin::set_margins`]
+                    pub fn set_margins(&mut self, top: i32, right: i32, bottom: i32, left: i32) {
+                        self.way.set_margins(top, right, bottom, left);
+                    }
+
+                    /// Internally calls [`crate::wayland_adapter::SpellWin::set_layer`]
+                    pub fn set_layer(&mut self, layer: $crate::layer_properties::LayerType) {
+                        self.way.set_layer(layer);
+                    }
+
+                    /// Internally calls [`crate::wayland_adapter::SpellWin::move_to_output`]
+                    pub fn move_to_output<M: Into<$crate::layer_properties::MonitorSelector>>(
+                        &mut self,
+                        monitor: M,
+                    ) {
+                        self.way.move_to_output(monitor);
+                    }
+
                     /// Returns a handle of [`crate::wayland_adapter::WinHandle`] to invoke wayland specific features.
                     pub fn get_handler(&self) -> WinHandle {
                       self.way.get_handler().clone()
                     }
 
+                    /// Returns a handle of [`crate::wayland_adapter::BoardHandle`] to send keys,
+                    /// `None` unless the widget is created with `invoke_board`.
+                    pub fn get_board_handler(&self) -> Option<$crate::wayland_adapter::BoardHandle> {
+                        self.board.clone()
+                    }
+
                     pub fn open_popup<T: $crate::PopupSlint + 'static>(
                         &mut self,
                         popup_conf: $crate::layer_properties::popup::PopupConf,
@@ -90,21 +168,34 @@ macro_rules! generate_widgets {
                     }
 
                     pub fn parts(self) -> ($slint_win, SpellWin) {
-                        let [<$slint_win Spell>] { ui, way } = self;
+                        let [<$slint_win Spell>] { ui, way, .. } = self;
                         (ui, way)
                     }
                 }
 
-                // impl $crate::SpellAssociatedNew for [<$slint_win Spell>] {
-                    // fn on_call(
-                        // &mut self,
-                    // ) -> Result<(), Box<dyn std::error::Error>> {
-                      // self.way.on_call()
-                    // }
-                    // fn get_span(&self) -> $crate::macro_internal::Span {
-                        // self.way.get_span()
-                    // }
-                // }
+                impl $crate::SpellAssociatedNew for [<$slint_win Spell>] {
+                    fn on_call(
+                        &mut self,
+                    ) -> Result<(), Box<dyn std::error::Error>> {
+                        $crate::SpellAssociatedNew::on_call(&mut self.way)
+                    }
+
+                    fn event_fd(&self) -> std::os::fd::BorrowedFd<'_> {
+                        $crate::SpellAssociatedNew::event_fd(&self.way)
+                    }
+
+                    fn get_span(&self) -> $crate::macro_internal::Span {
+                        $crate::SpellAssociatedNew::get_span(&self.way)
+                    }
+
+                    fn layer_name(&self) -> Option<&str> {
+                        $crate::SpellAssociatedNew::layer_name(&self.way)
+                    }
+
+                    fn next_timeout(&self) -> Option<std::time::Duration> {
+                        $crate::SpellAssociatedNew::next_timeout(&self.way)
+                    }
+                }
 
                 impl std::ops::Deref for [<$slint_win Spell>] {
                     type Target = [<$slint_win>];
@@ -193,6 +284,35 @@ macro_rules! cast_spell {
     //     $crate::cast_spells_new(windows)
     // }};
 
+    // Widgets instanced on monitors (viz. SpellOutputs)
+    (
+        outputs: $outputs:expr
+        $(, notification: $noti:expr)?
+        $(,)?
+    ) => {{
+        let mut windows = Vec::new();
+        $(
+            let (ui_noti, mut way_noti) = $noti.parts();
+            $crate::cast_spell!(@notification &way_noti, ui_noti);
+            windows.push(Box::new(way_noti) as Box<dyn $crate::SpellAssociatedNew>);
+        )?
+        windows.push(Box::new($outputs) as Box<dyn $crate::SpellAssociatedNew>);
+        $crate::cast_spells_new(windows)
+    }};
+
+    // Adding a window (IPC / non-IPC) to an already running event loop. Evaluates
+    // to a Result as the IPC socket can fail to be created.
+    (
+        spawn: $entry:tt
+        $(,)?
+    ) => {
+        (|| -> Result<(), Box<dyn std::error::Error>> {
+            let (ui, way) = $crate::cast_spell!(@handle_entry $entry);
+            $crate::SpellRuntime::new().add_widget_with(way, Box::new(ui));
+            Ok(())
+        })()
+    };
+
     (
         notification: $noti:expr
         $(,)?
@@ -7,6 +7,7 @@
 #![doc = include_str!("../docs/entry.md")]
 #![warn(missing_docs)]
 
+mod config_file;
 mod configure;
 #[cfg(docsrs)]
 mod dummy_skia_docs;
@@ -25,7 +26,7 @@ pub mod wayland_adapter;
 /// types from this module to implement relevant features. See docs of related objects for
 /// their overview.
 pub mod layer_properties {
-    pub use crate::configure::{Dimension, WindowConf, WindowConfBuilder};
+    pub use crate::configure::{Dimension, MonitorSelector, WindowConf, WindowConfBuilder};
     pub mod internal {
         //! It is an internal trait required for the implementation of [`PopupSlint`](crate::PopupSlint)
         //!  .It is not to be used directly and contains internal types which are
@@ -60,14 +61,29 @@ pub mod macro_internal {
     };
     pub use tracing::{info, span::Span, warn};
 }
+use slint::platform::WindowAdapter;
 use smithay_client_toolkit::{
-    reexports::client::{QueueHandle, protocol::wl_surface::WlSurface},
+    reexports::{
+        calloop::{
+            EventLoop, Interest, LoopHandle, Mode, PostAction, RegistrationToken,
+            channel::Event,
+            generic::Generic,
+            ping::{Ping, make_ping},
+        },
+        client::{QueueHandle, protocol::wl_surface::WlSurface},
+    },
     shell::xdg::popup::Popup,
 };
-use std::error::Error;
-use tracing::{Level, span, trace};
+use std::{
+    any::Any, cell::RefCell, error::Error, marker::PhantomData, os::fd::BorrowedFd, time::Duration,
+};
+use tracing::{Level, info, span, trace, warn};
 
-use crate::{configure::PopupCore, slint_adapter::SpellSkiaWinAdapter, wayland_adapter::SpellWin};
+use crate::{
+    configure::PopupCore,
+    slint_adapter::{ADAPTERS, SLINT_EVENTS, SpellSkiaWinAdapter},
+    wayland_adapter::SpellWin,
+};
 
 /// This trait is implemented upon slint generated windows to enable IPC handling
 pub trait IpcController {
@@ -87,9 +103,15 @@ pub trait IpcController {
 /// It helps in running every SpellWidget (like [SpellWin](`wayland_adapter::SpellWin`),
 /// [SpellLock](`wayland_adapter::SpellLock`)) through the same event_loop function.
 pub trait SpellAssociatedNew: std::fmt::Debug {
-    /// Internal method used to call to update UI in a loop.
+    /// Internal method used to dispatch pending events of the widget and redraw
+    /// its UI if needed. It never blocks, waiting is done by the shared event loop.
     fn on_call(&mut self) -> Result<(), Box<dyn Error>>;
 
+    /// Internal method returning the file descriptor of the widget's own event loop.
+    /// It becomes readable whenever the widget has wayland events, IPC requests or
+    /// slint callbacks waiting to be dispatched.
+    fn event_fd(&self) -> BorrowedFd<'_>;
+
     /// Internal method used to retrive logging span of a window.
     fn get_span(&self) -> span::Span {
         span!(Level::INFO, "unnamed-widget")
@@ -99,6 +121,19 @@ pub trait SpellAssociatedNew: std::fmt::Debug {
     fn is_locked(&self) -> bool {
         true
     }
+
+    /// Internal method returning the layer name of the widget, if it has one. It
+    /// is used by [`SpellRuntime::remove_widget`] to find the widget to drop.
+    fn layer_name(&self) -> Option<&str> {
+        None
+    }
+
+    /// Internal method returning the time left till the next timer (like key repeat)
+    /// of the widget's own event loop expires. Timers don't make the event fd readable,
+    /// so the shared event loop wakes up for them on its own.
+    fn next_timeout(&self) -> Option<Duration> {
+        None
+    }
 }
 
 /// Trait necessary to be implemented for an UI object to become a popup. It is
@@ -124,30 +159,258 @@ pub trait PopupSlint {
     fn adapter(&self) -> &std::rc::Rc<SpellSkiaWinAdapter>;
 }
 
+thread_local! {
+    /// Ping of the event loop currently run by [`cast_spells_new`]. It is used to
+    /// wake the loop when slint requests a redraw or when a handle queues work for
+    /// a widget.
+    static LOOP_WAKER: RefCell<Option<Ping>> = const { RefCell::new(None) };
+    /// Requests made through [`SpellRuntime`] which are yet to be applied by the
+    /// running event loop.
+    static RUNTIME_REQUESTS: RefCell<Vec<RuntimeRequest>> = const { RefCell::new(Vec::new()) };
+}
+
+/// Wakes the running event loop so that every widget gets dispatched again. It
+/// does nothing if the loop is not running yet.
+pub(crate) fn wake_event_loop() {
+    LOOP_WAKER.with_borrow(|waker| {
+        if let Some(ping) = waker {
+            ping.ping();
+        }
+    });
+}
+
+enum RuntimeRequest {
+    Add(Box<dyn SpellAssociatedNew>),
+    Remove(String),
+}
+
+/// A handle to add and remove widgets from the event loop run by [`cast_spell`]
+/// while it is running. It can be created anywhere in the main thread (including
+/// slint callbacks and IPC commands) and the requests are applied as soon as the
+/// current dispatch of the loop finishes. Requests made before the loop starts are
+/// applied when it starts.
+///
+/// Removing a widget drops it, which closes its layer surface, removes its IPC
+/// socket and frees its slint window adapter. Hence, widgets like launchers and
+/// OSDs can exist only while they are in use.
+///
+/// ## Example
+///
+/// ```rust
+/// use spell_framework::{SpellRuntime, generate_widgets, layer_properties::WindowConf};
+/// slint::include_modules!();
+/// generate_widgets![Launcher];
+///
+/// fn open_launcher(window_conf: WindowConf) -> Result<(), Box<dyn std::error::Error>> {
+///     // A non IPC widget can be added directly
+///     SpellRuntime::new().add_widget(LauncherSpell::invoke_spell("launcher", window_conf));
+///     Ok(())
+/// }
+///
+/// fn close_launcher() {
+///     SpellRuntime::new().remove_widget("launcher");
+/// }
+/// ```
+///
+/// To add a widget along with its IPC socket, use the `spawn` arm of [`cast_spell`].
+#[derive(Debug, Clone, Default)]
+pub struct SpellRuntime {
+    // Requests are stored in a thread local, so the handle must stay in the main thread.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl SpellRuntime {
+    /// Creates a new handle to the runtime.
+    pub fn new() -> Self {
+        SpellRuntime::default()
+    }
+
+    /// Adds the given widget to the running event loop. Any widget created by
+    /// [`generate_widgets`] can be passed here.
+    pub fn add_widget<W: SpellAssociatedNew + 'static>(&self, widget: W) {
+        self.request(RuntimeRequest::Add(Box::new(widget)));
+    }
+
+    pub(crate) fn add_boxed_widget(&self, widget: Box<dyn SpellAssociatedNew>) {
+        self.request(RuntimeRequest::Add(widget));
+    }
+
+    /// Adds the widget to the running event loop along with a value which is
+    /// kept alive as long as the widget runs. Used internally by [`cast_spell`],
+    /// not to be used by the end user.
+    #[doc(hidden)]
+    pub fn add_widget_with<W: SpellAssociatedNew + 'static>(&self, widget: W, ui: Box<dyn Any>) {
+        self.request(RuntimeRequest::Add(Box::new(WidgetWithUi {
+            widget: Box::new(widget),
+            _ui: ui,
+        })));
+    }
+
+    /// Removes the widget with the given layer name from the running event loop
+    /// and drops it. Nothing happens if there is no such widget.
+    pub fn remove_widget(&self, layer_name: &str) {
+        self.request(RuntimeRequest::Remove(layer_name.to_string()));
+    }
+
+    fn request(&self, request: RuntimeRequest) {
+        RUNTIME_REQUESTS.with_borrow_mut(|requests| requests.push(request));
+        wake_event_loop();
+    }
+}
+
+/// A widget along with the UI handles it needs to keep alive.
+struct WidgetWithUi {
+    widget: Box<dyn SpellAssociatedNew>,
+    _ui: Box<dyn Any>,
+}
+
+impl std::fmt::Debug for WidgetWithUi {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        self.widget.fmt(f)
+    }
+}
+
+impl SpellAssociatedNew for WidgetWithUi {
+    fn on_call(&mut self) -> Result<(), Box<dyn Error>> {
+        self.widget.on_call()
+    }
+
+    fn event_fd(&self) -> BorrowedFd<'_> {
+        self.widget.event_fd()
+    }
+
+    fn get_span(&self) -> span::Span {
+        self.widget.get_span()
+    }
+
+    fn is_locked(&self) -> bool {
+        self.widget.is_locked()
+    }
+
+    fn layer_name(&self) -> Option<&str> {
+        self.widget.layer_name()
+    }
+
+    fn next_timeout(&self) -> Option<Duration> {
+        self.widget.next_timeout()
+    }
+}
+
 /// event loop function internally used by [`cast_spell`] for single widget setups.
 /// Not to be used by end user,
-pub fn cast_spell_inner<S: SpellAssociatedNew>(mut waywindow: S) -> Result<(), Box<dyn Error>> {
-    let span = waywindow.get_span();
-    let _gaurd = span.enter();
+pub fn cast_spell_inner<S: SpellAssociatedNew + 'static>(
+    waywindow: S,
+) -> Result<(), Box<dyn Error>> {
     trace!("{:?}", &waywindow);
-    while waywindow.is_locked() {
-        waywindow.on_call()?
-    }
-    Ok(())
+    cast_spells_new(vec![Box::new(waywindow)])
 }
 
 /// event loop function internally used by [`cast_spell`] for multiple widget setups.
 /// Not to be used by end user.
-pub fn cast_spells_new(
-    mut windows: Vec<Box<dyn SpellAssociatedNew>>,
-) -> Result<(), Box<dyn Error>> {
-    loop {
-        for win in windows.iter_mut() {
+///
+/// A single event loop waits on the event loops of every widget along with slint's
+/// timers. Hence, the thread sleeps until there is wayland traffic, an IPC request,
+/// a slint callback, a timer or a redraw request to process. Widgets can be added
+/// and removed from it with [`SpellRuntime`].
+pub fn cast_spells_new(windows: Vec<Box<dyn SpellAssociatedNew>>) -> Result<(), Box<dyn Error>> {
+    let mut event_loop: EventLoop<'static, ()> = EventLoop::try_new()?;
+    let loop_handle = event_loop.handle();
+    let mut widgets: Vec<(Box<dyn SpellAssociatedNew>, RegistrationToken)> = Vec::new();
+    for win in windows {
+        let token = register_widget(&loop_handle, win.as_ref())?;
+        widgets.push((win, token));
+    }
+    let (ping, ping_source) = make_ping()?;
+    loop_handle
+        .insert_source(ping_source, |_, _, _| {})
+        .map_err(|err| err.error)?;
+    LOOP_WAKER.with_borrow_mut(|waker| *waker = Some(ping));
+
+    while widgets.iter().all(|(win, _)| win.is_locked()) {
+        apply_runtime_requests(&loop_handle, &mut widgets)?;
+        // Checked on every iteration as the slint platform can also be set by a
+        // widget created after the start of the loop.
+        register_slint_events(&loop_handle)?;
+        for (win, _) in widgets.iter_mut() {
             let span = win.get_span().clone();
             let _gaurd = span.enter();
             win.on_call()?;
         }
+        // Slint timers and animations along with the timers of widgets are the only
+        // events which are not backed by a file descriptor, so they decide the
+        // maximum time to sleep.
+        let timeout = widgets
+            .iter()
+            .filter_map(|(win, _)| win.next_timeout())
+            .chain(slint::platform::duration_until_next_timer_update())
+            .min();
+        if let Err(err) = event_loop.dispatch(timeout, &mut ()) {
+            warn!("Error dispatching the event loop: {}", err);
+        }
     }
+    LOOP_WAKER.with_borrow_mut(|waker| *waker = None);
+    Ok(())
+}
+
+fn register_widget(
+    loop_handle: &LoopHandle<'static, ()>,
+    win: &dyn SpellAssociatedNew,
+) -> Result<RegistrationToken, Box<dyn Error>> {
+    // Readiness is only used to wake up, widgets are dispatched afterwards.
+    let widget_fd = win.event_fd().try_clone_to_owned()?;
+    let token = loop_handle
+        .insert_source(
+            Generic::new(widget_fd, Interest::READ, Mode::Level),
+            |_, _, _| Ok(PostAction::Continue),
+        )
+        .map_err(|err| err.error)?;
+    Ok(token)
+}
+
+fn register_slint_events(loop_handle: &LoopHandle<'static, ()>) -> Result<(), Box<dyn Error>> {
+    if let Some(slint_events) = SLINT_EVENTS.take() {
+        loop_handle
+            .insert_source(slint_events, |event, _, _| {
+                if let Event::Msg(callback) = event {
+                    callback();
+                    ADAPTERS.with_borrow(|adapters| {
+                        adapters.iter().for_each(|adapter| adapter.request_redraw())
+                    });
+                }
+            })
+            .map_err(|err| err.error)?;
+    }
+    Ok(())
+}
+
+fn apply_runtime_requests(
+    loop_handle: &LoopHandle<'static, ()>,
+    widgets: &mut Vec<(Box<dyn SpellAssociatedNew>, RegistrationToken)>,
+) -> Result<(), Box<dyn Error>> {
+    let requests = RUNTIME_REQUESTS.take();
+    for request in requests {
+        match request {
+            RuntimeRequest::Add(win) => {
+                info!("Runtime: adding widget {:?}", win.layer_name());
+                let token = register_widget(loop_handle, win.as_ref())?;
+                widgets.push((win, token));
+            }
+            RuntimeRequest::Remove(layer_name) => {
+                if let Some(index) = widgets
+                    .iter()
+                    .position(|(win, _)| win.layer_name() == Some(layer_name.as_str()))
+                {
+                    info!("Runtime: removing widget {}", layer_name);
+                    let (win, token) = widgets.remove(index);
+                    loop_handle.remove(token);
+                    drop(win);
+                } else {
+                    warn!("Runtime: no widget with layer name {} found", layer_name);
+                }
+            }
+        }
+    }
+    Ok(())
 }
 
 // TODO: Various functions can be sufficed with pub(super) and not pub(crate), reevaluate every
@@ -163,8 +426,6 @@ pub fn cast_spells_new(
 // that they are closed when main thread closes.
 // TODO: linux's DNF Buffers needs to be used to improve rendering and avoid conversions
 // from CPU to GPU and vice versa.
-// TO REMEMBER I removed dirty region from spellskiawinadapter but it can be added
-// if I want to make use of the dirty region information to strengthen my rendering.
 // TODO: lock screen behaviour in a multi-monitor setup needs to be tested.
 // Provide a method in the macro to disable tracing_subsriber completely for some project
 // which want's to do it themselves.
@@ -1,14 +1,13 @@
-// use i_slint_core::item_rendering::DirtyRegion;
-use i_slint_core::window::WindowAdapterInternal;
+use i_slint_core::window::{InputMethodProperties, InputMethodRequest, WindowAdapterInternal};
 use i_slint_core::{items::MouseCursor, partial_renderer::DirtyRegion, platform::WindowAdapter};
 
 #[cfg(not(docsrs))]
-use slint::{PhysicalSize, Window};
+use slint::{LogicalSize, PhysicalSize, Window, platform::WindowProperties};
 #[cfg(not(docsrs))]
 #[cfg(feature = "i-slint-renderer-skia")]
 use smithay_client_toolkit::{
-    reexports::client::protocol::wl_shm,
-    shm::slot::{Buffer, Slot, SlotPool},
+    reexports::client::protocol::{wl_shm, wl_surface::WlSurface},
+    shm::slot::{Buffer, SlotPool},
 };
 use std::{
     cell::Cell,
@@ -16,7 +15,7 @@ use std::{
     fmt::Debug,
     rc::{Rc, Weak},
 };
-use tracing::{info, warn};
+use tracing::{info, trace, warn};
 
 #[cfg(feature = "i-slint-renderer-skia")]
 use i_slint_renderer_skia::{
@@ -24,24 +23,95 @@ use i_slint_renderer_skia::{
     software_surface::RenderBuffer,
 };
 
+#[cfg(not(docsrs))]
+#[cfg(feature = "i-slint-renderer-skia")]
+/// Number of buffers a swapchain can grow to. Two buffers are enough when the
+/// compositor releases them quickly, a third one is created when it holds on to both.
+const MAX_SWAPCHAIN_BUFFERS: usize = 3;
+
+#[cfg(not(docsrs))]
+#[cfg(feature = "i-slint-renderer-skia")]
+/// A buffer of the swapchain along with the frame last rendered in it.
+pub struct SwapchainBuffer {
+    buffer: Buffer,
+    rendered_frame: Option<u64>,
+}
+
 #[cfg(feature = "i-slint-renderer-skia")]
 #[cfg(not(docsrs))]
 pub struct SkiaSoftwareBufferReal {
-    pub primary_slot: RefCell<Slot>,
     pub pool: Rc<RefCell<SlotPool>>,
+    /// Buffers rendered into in turns, a buffer is only rendered into when the
+    /// compositor has released it (i.e. is done reading it).
+    pub swapchain: RefCell<Vec<SwapchainBuffer>>,
+    /// Index of the buffer in swapchain holding the latest frame.
+    pub current: Cell<Option<usize>>,
     pub last_dirty_region: RefCell<Option<DirtyRegion>>,
+    frame_count: Cell<u64>,
+    /// Set when the last render was skipped as every buffer was in use by the
+    /// compositor.
+    skipped: Cell<bool>,
 }
 
 impl SkiaSoftwareBufferReal {
-    fn refresh_buffer(&self, width: i32, height: i32) -> Buffer {
-        let stride = width * 4;
-        let (buffer, _raw_canvas) = self
-            .pool
-            .borrow_mut()
-            .create_buffer(width, height, stride, wl_shm::Format::Argb8888)
-            .unwrap();
-        *self.primary_slot.borrow_mut() = buffer.slot();
-        buffer
+    /// Drops the buffers of the swapchain, new ones are created with the size
+    /// of the next render.
+    fn refresh_buffers(&self) {
+        self.swapchain.borrow_mut().clear();
+        self.current.set(None);
+    }
+
+    /// Returns the index of the buffer to render the next frame into, creating
+    /// a new buffer if all of them are in use by the compositor. `None` is returned
+    /// if the swapchain can't grow any more, as a buffer being read by the
+    /// compositor must never be written into.
+    fn next_buffer(&self, pool: &mut SlotPool, width: i32, height: i32) -> Option<usize> {
+        let mut swapchain = self.swapchain.borrow_mut();
+        // Buffers of an older size are of no use.
+        if swapchain.first().is_some_and(|swap_buffer| {
+            swap_buffer.buffer.height() != height || swap_buffer.buffer.stride() != width * 4
+        }) {
+            swapchain.clear();
+            self.current.set(None);
+        }
+
+        // The free buffer with the most recent frame needs least re-rendering.
+        let free_buffer = swapchain
+            .iter()
+            .enumerate()
+            .filter(|(_, swap_buffer)| swap_buffer.buffer.canvas(pool).is_some())
+            .max_by_key(|(_, swap_buffer)| swap_buffer.rendered_frame)
+            .map(|(index, _)| index);
+        if free_buffer.is_some() {
+            return free_buffer;
+        }
+        if swapchain.len() < MAX_SWAPCHAIN_BUFFERS {
+            // The pool grows if there is no space left for the buffer.
+            let (buffer, _) = pool
+                .create_buffer(width, height, width * 4, wl_shm::Format::Argb8888)
+                .expect("Creating Buffer");
+            swapchain.push(SwapchainBuffer {
+                buffer,
+                rendered_frame: None,
+            });
+            return Some(swapchain.len() - 1);
+        }
+        info!("All buffers are in use by the compositor, skipping the frame");
+        None
+    }
+
+    /// Attaches the buffer with the latest frame to the surface.
+    fn attach_to(&self, surface: &WlSurface) {
+        let swapchain = self.swapchain.borrow();
+        let Some(swap_buffer) = self.current.get().and_then(|index| swapchain.get(index)) else {
+            warn!("No frame has been rendered to attach");
+            return;
+        };
+        // Attaching marks the buffer as in use till the compositor releases it. The
+        // buffer can already be in use if it is attached again without a redraw.
+        if swap_buffer.buffer.attach_to(surface).is_err() {
+            surface.attach(Some(swap_buffer.buffer.wl_buffer()), 0, 0);
+        }
     }
 }
 
@@ -85,21 +155,30 @@ impl RenderBuffer for SkiaSoftwareBufferReal {
         //     SharedPixelBuffer::new(width.get(), height.get())
         // });
         let pool = &mut self.pool.borrow_mut();
-        // let mut native_buffer = {
-        //     let x = self.secondary_slot.borrow().canvas(pool).unwrap();
-        //     // creates a copy
-        //     x.to_vec()
-        // };
-
-        // let bytes = bytemuck::cast_slice_mut(&mut native_buffer);
-        *self.last_dirty_region.borrow_mut() = render_callback(
-            width,
-            height,
-            skia_safe::ColorType::BGRA8888,
-            1,
-            self.primary_slot.borrow_mut().canvas(pool).unwrap(),
-        )
-        .unwrap();
+        // A buffer is never written into while the compositor reads it, the frame
+        // is rendered once the compositor releases one instead.
+        let Some(index) = self.next_buffer(pool, width.get() as i32, height.get() as i32) else {
+            self.skipped.set(true);
+            return Ok(());
+        };
+        let frame = self.frame_count.get() + 1;
+        let mut swapchain = self.swapchain.borrow_mut();
+        let swap_buffer = &mut swapchain[index];
+        // Age is the number of frames since the buffer was last rendered into, skia
+        // only re-renders the parts changed since then. 0 makes it render everything.
+        let age = swap_buffer
+            .rendered_frame
+            .map_or(0, |rendered| (frame - rendered).min(u8::MAX as u64) as u8);
+        let Some(canvas) = swap_buffer.buffer.canvas(pool) else {
+            self.skipped.set(true);
+            return Ok(());
+        };
+        self.skipped.set(false);
+        *self.last_dirty_region.borrow_mut() =
+            render_callback(width, height, skia_safe::ColorType::BGRA8888, age, canvas).unwrap();
+        self.frame_count.set(frame);
+        swap_buffer.rendered_frame = Some(frame);
+        self.current.set(Some(index));
         Ok(())
     }
 }
@@ -120,8 +199,18 @@ pub struct SpellSkiaWinAdapterReal {
     pub(crate) renderer: SkiaRenderer,
     pub(crate) buffer_slint: Rc<SkiaSoftwareBufferReal>,
     pub(crate) needs_redraw: Cell<bool>,
+    /// Set when a frame is committed and unset when the compositor's frame callback
+    /// for it is received. Used to pace drawing with the compositor.
+    pub(crate) frame_pending: Cell<bool>,
     pub(crate) scale_factor: Cell<f32>,
     pub(crate) current_cursor: Cell<MouseCursor>,
+    /// Preferred size of the layout of the root slint component.
+    pub(crate) preferred_size: Cell<Option<LogicalSize>>,
+    pub(crate) preferred_size_changed: Cell<bool>,
+    /// Properties of the focused text input of slint, set when it requires an
+    /// input method.
+    pub(crate) input_method: RefCell<Option<InputMethodProperties>>,
+    pub(crate) input_method_changed: Cell<bool>,
 }
 
 impl Debug for SpellSkiaWinAdapterReal {
@@ -141,6 +230,19 @@ impl WindowAdapterInternal for SpellSkiaWinAdapterReal {
     fn set_mouse_cursor(&self, cursor: MouseCursor) {
         self.current_cursor.set(cursor);
     }
+
+    fn input_method_request(&self, request: InputMethodRequest) {
+        let properties = match request {
+            InputMethodRequest::Enable(properties) | InputMethodRequest::Update(properties) => {
+                Some(properties)
+            }
+            InputMethodRequest::Disable => None,
+            _ => return,
+        };
+        *self.input_method.borrow_mut() = properties;
+        self.input_method_changed.set(true);
+        crate::wake_event_loop();
+    }
 }
 
 impl WindowAdapter for SpellSkiaWinAdapterReal {
@@ -167,6 +269,20 @@ impl WindowAdapter for SpellSkiaWinAdapterReal {
 
     fn request_redraw(&self) {
         self.needs_redraw.set(true);
+        // Wakes the event loop so that the redraw isn't delayed till the next event.
+        crate::wake_event_loop();
+    }
+
+    fn update_window_properties(&self, properties: WindowProperties<'_>) {
+        let preferred = properties.layout_constraints().preferred;
+        if self.preferred_size.replace(Some(preferred)) != Some(preferred) {
+            trace!(
+                "Preferred size changed to width: {}, height: {}",
+                preferred.width, preferred.height
+            );
+            self.preferred_size_changed.set(true);
+            crate::wake_event_loop();
+        }
     }
 
     fn internal(&self, _: i_slint_core::InternalToken) -> Option<&dyn WindowAdapterInternal> {
@@ -176,16 +292,14 @@ impl WindowAdapter for SpellSkiaWinAdapterReal {
 
 impl SpellSkiaWinAdapterReal {
     #[allow(clippy::type_complexity)]
-    pub fn new(
-        pool: Rc<RefCell<SlotPool>>,
-        primary_slot: RefCell<Slot>,
-        width: u32,
-        height: u32,
-    ) -> Rc<Self> {
+    pub fn new(pool: Rc<RefCell<SlotPool>>, width: u32, height: u32) -> Rc<Self> {
         let buffer = Rc::new(SkiaSoftwareBufferReal {
-            primary_slot,
             pool,
+            swapchain: RefCell::new(Vec::new()),
+            current: Cell::new(None),
             last_dirty_region: Default::default(),
+            frame_count: Cell::new(0),
+            skipped: Cell::new(false),
         });
         let renderer = SkiaRenderer::new_with_surface(
             &SkiaSharedContext::default(),
@@ -199,7 +313,12 @@ impl SpellSkiaWinAdapterReal {
             buffer_slint: buffer,
             scale_factor: Cell::new(1.),
             needs_redraw: Cell::new(true),
+            frame_pending: Cell::new(false),
             current_cursor: Cell::new(MouseCursor::Default),
+            preferred_size: Cell::new(None),
+            preferred_size_changed: Cell::new(false),
+            input_method: RefCell::new(None),
+            input_method_changed: Cell::new(false),
         })
     }
 
@@ -210,6 +329,12 @@ impl SpellSkiaWinAdapterReal {
                 warn!("Panicking because of error: {}", err);
                 panic!("Seems like you have initialised slint before SpellWin");
             });
+            // Drawing is retried on the next call, which happens after the
+            // compositor sends an event like a buffer release or a frame callback.
+            if self.buffer_slint.skipped.get() {
+                self.needs_redraw.set(true);
+                return false;
+            }
             true
         } else {
             false
@@ -220,6 +345,28 @@ impl SpellSkiaWinAdapterReal {
         self.draw()
     }
 
+    /// Returns the rectangles (x, y, width, height) of the buffer changed by the last
+    /// draw. `None` is returned when the whole buffer has changed.
+    pub(crate) fn damaged_rects(&self) -> Option<Vec<(i32, i32, i32, i32)>> {
+        let scale = self.scale_factor.get();
+        let size = self.size.get();
+        let dirty_region = self.buffer_slint.last_dirty_region.borrow();
+        let rects = dirty_region
+            .as_ref()?
+            .iter()
+            .filter_map(|rect| {
+                // Dirty region is in logical pixels, rounding outwards to cover
+                // partially changed pixels.
+                let x = ((rect.min.x * scale).floor() as i32).max(0);
+                let y = ((rect.min.y * scale).floor() as i32).max(0);
+                let max_x = ((rect.max.x * scale).ceil() as i32).min(size.width as i32);
+                let max_y = ((rect.max.y * scale).ceil() as i32).min(size.height as i32);
+                (max_x > x && max_y > y).then_some((x, y, max_x - x, max_y - y))
+            })
+            .collect();
+        Some(rects)
+    }
+
     pub(crate) fn try_dispatch_event(
         &self,
         event: slint::platform::WindowEvent,
@@ -227,33 +374,42 @@ impl SpellSkiaWinAdapterReal {
         self.window.try_dispatch_event(event)
     }
 
-    pub(crate) fn changed_scale_factor(&self, scale: u32) -> (Buffer, u32, u32, f32) {
-        let width: u32 = (self.size.get().width * scale + 60) / 120;
-        let height: u32 = (self.size.get().height * scale + 60) / 120;
+    /// Attaches the buffer holding the latest drawn frame to the surface.
+    pub(crate) fn attach_buffer(&self, surface: &WlSurface) {
+        self.buffer_slint.attach_to(surface);
+    }
+
+    /// Resizes the window to the given logical size, buffers of the new size are
+    /// created on the next draw. Returns the new physical size.
+    pub(crate) fn resize(&self, width: u32, height: u32) -> (u32, u32) {
+        let scale_factor = self.scale_factor.get();
+        let physical_width = (width as f32 * scale_factor).round() as u32;
+        let physical_height = (height as f32 * scale_factor).round() as u32;
+        self.size_original.set(PhysicalSize { width, height });
+        self.size.set(PhysicalSize {
+            width: physical_width,
+            height: physical_height,
+        });
+        self.buffer_slint.refresh_buffers();
+        self.window
+            .dispatch_event(slint::platform::WindowEvent::Resized {
+                size: LogicalSize::new(width as f32, height as f32),
+            });
+        self.needs_redraw.set(true);
+        (physical_width, physical_height)
+    }
+
+    pub(crate) fn changed_scale_factor(&self, scale: u32) -> (u32, u32, f32) {
+        // Physical size is always derived from the logical one, so that repeated
+        // scale changes don't compound.
+        let width: u32 = (self.size_original.get().width * scale + 60) / 120;
+        let height: u32 = (self.size_original.get().height * scale + 60) / 120;
         let scale_factor: f32 = scale as f32 / 120.0;
         self.scale_factor.set(scale_factor);
         self.size.set(PhysicalSize { width, height });
         info!("Physical Size: width: {}, height: {}", width, height);
         // self.needs_redraw.set(true);
-        (
-            self.buffer_slint
-                .refresh_buffer(width as i32, height as i32),
-            width,
-            height,
-            scale_factor,
-        )
+        self.buffer_slint.refresh_buffers();
+        (width, height, scale_factor)
     }
-
-    // fn last_dirty_region_bounding_box_size(&self) -> Option<slint::LogicalSize> {
-    //     self.buffer.last_dirty_region.borrow().as_ref().map(|r| {
-    //         let size = r.bounding_rect().size;
-    //         slint::LogicalSize::new(size.width as _, size.height as _)
-    //     })
-    // }
-    // fn last_dirty_region_bounding_box_origin(&self) -> Option<slint::LogicalPosition> {
-    //     self.buffer.last_dirty_region.borrow().as_ref().map(|r| {
-    //         let origin = r.bounding_rect().origin;
-    //         slint::LogicalPosition::new(origin.x as _, origin.y as _)
-    //     })
-    // }
 }
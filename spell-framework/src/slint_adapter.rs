@@ -2,18 +2,18 @@
 //! All structs mentioned are either internal or not used anymore. Still their
 //! implementation is public because they had to be set by the user of library
 //! in intial iterations of spell_framework.
-use crate::configure::LayerConf;
+use crate::wayland_adapter::SpellClipboard;
 use slint::platform::{EventLoopProxy, Platform, WindowAdapter};
 use smithay_client_toolkit::reexports::calloop;
-use std::{cell::RefCell, io::Read, rc::Rc};
-use tracing::{Level, info, span, warn};
-use wl_clipboard_rs::{
-    copy::{MimeType as CopyMimeType, Options, Source},
-    paste::{ClipboardType, Error, MimeType as PasteMimeType, Seat, get_contents},
-};
+use std::{cell::RefCell, rc::Rc};
+use tracing::{Level, info, span};
 
 thread_local! {
     pub(crate) static ADAPTERS: RefCell<Vec<Rc<SpellSkiaWinAdapter>>> = const { RefCell::new(Vec::new()) };
+    /// Receiving end of the callbacks sent by slint through [`SpellLayerShell`]. It is
+    /// taken by [`cast_spells_new`](crate::cast_spells_new) so that the callbacks don't
+    /// depend on any single widget being alive.
+    pub(crate) static SLINT_EVENTS: RefCell<Option<calloop::channel::Channel<Box<dyn FnOnce() + Send>>>> = const { RefCell::new(None) };
 }
 
 #[cfg(not(docsrs))]
@@ -87,51 +87,15 @@ impl Platform for SpellLayerShell {
         Some(Box::new(SlintEventProxy(self.slint_event_sender.clone())))
     }
 
-    // FIXME: this implementation should be by smithay-clipboard.
-    // fix after state management moved from window to platform.
-    fn set_clipboard_text(&self, text: &str, _clipboard: slint::platform::Clipboard) {
-        // if let DefaultClipboard = clipboard {
-        let opts = Options::new();
-        if let Err(err) = opts.copy(
-            Source::Bytes(text.to_string().into_bytes().into()),
-            CopyMimeType::Autodetect,
-        ) {
-            warn!("[Clipboard]: Error in setting clipboard value: {}", err);
-        } else {
-            info!("[Clipboard]: Successfully copied text");
-        }
-        //}
-    }
-
-    // FIXME: this implementation should be by smithay-clipboard.
-    // fix after state management moved from window to platform.
-    fn clipboard_text(&self, _clipboard: slint::platform::Clipboard) -> Option<String> {
-        let result = get_contents(
-            ClipboardType::Regular,
-            Seat::Unspecified,
-            PasteMimeType::Text,
-        );
-        match result {
-            Ok((mut pipe, _)) => {
-                let mut contents = vec![];
-                // TODO: handle the below unwrap properly.
-                pipe.read_to_end(&mut contents).unwrap();
-                let text = String::from_utf8_lossy(&contents).to_string();
-                info!("[Clipboard]: Successfully pasted text: {}", text);
-                Some(text)
-            }
-
-            // In this cases, an empty string is returned.
-            Err(Error::NoSeats) | Err(Error::ClipboardEmpty) | Err(Error::NoMimeType) => {
-                warn!("[Clipboard]: Clipboard was either empty or didn't have text type data");
-                Some("".to_string())
-            }
+    // Clipboard is handled by the widgets in their own event loops, see
+    // [`SpellClipboard`] for its limitations.
+    fn set_clipboard_text(&self, text: &str, clipboard: slint::platform::Clipboard) {
+        info!("[Clipboard]: Copying text");
+        SpellClipboard.set_text(clipboard, text);
+    }
 
-            Err(err) => {
-                warn!("[Clipboard]: error getting clipboard text: {}", err);
-                None
-            }
-        }
+    fn clipboard_text(&self, clipboard: slint::platform::Clipboard) -> Option<String> {
+        SpellClipboard.text(clipboard)
     }
 }
 
@@ -141,29 +105,42 @@ impl Platform for SpellLayerShell {
 /// for documentation purposes (and was previously used by end user of library) but
 /// it is now not to be used directly.
 pub struct SpellMultiWinHandler {
-    pub(crate) windows: Vec<(String, LayerConf)>,
-    pub(crate) adapter: Vec<Rc<SpellSkiaWinAdapter>>,
-    pub(crate) value_given: u32,
+    /// Adapters of the lock surfaces which are yet to be given to a slint component,
+    /// in the order their outputs were added.
+    pub(crate) pending: Vec<Rc<SpellSkiaWinAdapter>>,
 }
 
 impl SpellMultiWinHandler {
-    pub(crate) fn new_lock(lock_outputs: Vec<(String, (u32, u32))>) -> Rc<RefCell<Self>> {
-        let new_locks: Vec<(String, LayerConf)> = lock_outputs
-            .iter()
-            .map(|(output_name, conf)| (output_name.clone(), LayerConf::Lock(conf.0, conf.1)))
-            .collect();
-
+    pub(crate) fn new_lock() -> Rc<RefCell<Self>> {
         Rc::new(RefCell::new(SpellMultiWinHandler {
-            windows: new_locks,
-            adapter: Vec::new(),
-            value_given: 0,
+            pending: Vec::new(),
         }))
     }
 
-    fn request_new_lock(&mut self) -> Rc<dyn WindowAdapter> {
-        self.value_given += 1;
-        let index = self.value_given - 1;
-        self.adapter[index as usize].clone()
+    /// Queues the adapter of a new lock surface to be given to a slint component.
+    pub(crate) fn add_lock(&mut self, adapter: Rc<SpellSkiaWinAdapter>) {
+        self.pending.push(adapter);
+    }
+
+    /// Removes the adapter of a destroyed lock surface, if it is not given yet.
+    pub(crate) fn remove_lock(&mut self, adapter: &Rc<SpellSkiaWinAdapter>) {
+        self.pending.retain(|pending| !Rc::ptr_eq(pending, adapter));
+    }
+
+    /// Returns if the adapter is yet to be given to a slint component.
+    pub(crate) fn is_pending(&self, adapter: &Rc<SpellSkiaWinAdapter>) -> bool {
+        self.pending
+            .iter()
+            .any(|pending| Rc::ptr_eq(pending, adapter))
+    }
+
+    fn request_new_lock(&mut self) -> Result<Rc<dyn WindowAdapter>, slint::PlatformError> {
+        if self.pending.is_empty() {
+            return Err(slint::PlatformError::Other(
+                "No lock surface is left for the component".to_string(),
+            ));
+        }
+        Ok(self.pending.remove(0))
     }
 }
 
@@ -196,8 +173,7 @@ impl SpellLockShell {
 
 impl Platform for SpellLockShell {
     fn create_window_adapter(&self) -> Result<Rc<dyn WindowAdapter>, slint::PlatformError> {
-        let value = self.window_manager.borrow_mut().request_new_lock();
-        Ok(value)
+        self.window_manager.borrow_mut().request_new_lock()
     }
 
     fn new_event_loop_proxy(&self) -> Option<Box<dyn EventLoopProxy>> {
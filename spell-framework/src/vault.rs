@@ -8,7 +8,8 @@
 //! </div>
 //!
 //! Current it provides three main functionalities, namely notification management
-//! interface via [`NotificationManager`]
+//! interface via [`NotificationManager`] and clipboard history via
+//! [`ClipboardHistory`](clipboard::ClipboardHistory).
 use crate::vault::application::desktop_entry_extracter;
 pub use mpris;
 pub use notification_manager::set_notification;
@@ -21,6 +22,7 @@ use std::{
 };
 
 mod application;
+pub mod clipboard;
 mod notification_manager;
 
 
@@ -0,0 +1,929 @@
+//! This module provides a clipboard history, which watches the clipboard of the
+//! session through the `ext-data-control-v1` protocol (or `wlr-data-control-unstable-v1`
+//! on compositors not supporting it yet). Unlike the clipboard of the widgets, it
+//! doesn't need the keyboard focus, so every copy made in the session is recorded.
+//!
+//! The history is exposed as a slint model through [`ClipboardHistory::model`],
+//! which can be mapped to a struct of your slint file with
+//! [`ModelExt::map`](slint::ModelExt::map):
+//!
+//! ```rust,ignore
+//! let history = ClipboardHistory::new(&win.get_handler(), HistoryConf::default())?;
+//! ui.set_entries(ModelRc::new(history.model().map(|entry| ClipEntry {
+//!     id: entry.id as i32,
+//!     text: entry.preview(),
+//!     image: entry.image().unwrap_or_default(),
+//!     pinned: entry.pinned,
+//! })));
+//! let history_clone = history.clone();
+//! ui.on_selected(move |id| history_clone.select(id as u64));
+//! ```
+use std::{cell::Cell, env, error::Error, fs, os::fd::AsFd, path::PathBuf, rc::Rc, sync::Mutex};
+
+use serde::{Deserialize, Serialize};
+use slint::{FilterModel, Image, Model, ModelRc, SharedString, VecModel};
+use smithay_client_toolkit::reexports::{
+    calloop::{
+        EventLoop, LoopHandle, RegistrationToken,
+        channel::{self, Channel, Sender},
+    },
+    calloop_wayland_source::WaylandSource,
+    client::{
+        Connection, Dispatch, Proxy, QueueHandle, event_created_child,
+        globals::{GlobalListContents, registry_queue_init},
+        protocol::{wl_registry::WlRegistry, wl_seat::WlSeat},
+    },
+    protocols::ext::data_control::v1::client::{
+        ext_data_control_device_v1::{self, ExtDataControlDeviceV1},
+        ext_data_control_manager_v1::ExtDataControlManagerV1,
+        ext_data_control_offer_v1::{self, ExtDataControlOfferV1},
+        ext_data_control_source_v1::{self, ExtDataControlSourceV1},
+    },
+    protocols_wlr::data_control::v1::client::{
+        zwlr_data_control_device_v1::{self, ZwlrDataControlDeviceV1},
+        zwlr_data_control_manager_v1::ZwlrDataControlManagerV1,
+        zwlr_data_control_offer_v1::{self, ZwlrDataControlOfferV1},
+        zwlr_data_control_source_v1::{self, ZwlrDataControlSourceV1},
+    },
+};
+use tracing::{info, warn};
+
+use crate::wayland_adapter::{SpellWin, TEXT_MIME_TYPES, WinHandle, read_pipe, write_pipe};
+
+/// Image types recorded in the history in the order of preference.
+const IMAGE_MIME_TYPES: [&str; 5] = [
+    "image/png",
+    "image/jpeg",
+    "image/webp",
+    "image/gif",
+    "image/bmp",
+];
+
+/// Set by password managers on secrets, which are not recorded.
+const SECRET_MIME_TYPE: &str = "x-kde-passwordManagerHint";
+
+/// Configuration of [`ClipboardHistory`].
+#[derive(Debug, Clone)]
+pub struct HistoryConf {
+    /// Maximum number of entries kept, pinned entries are never removed to make
+    /// space for new ones.
+    pub max_entries: usize,
+    /// Saves the history on every change so that it is restored on the next start.
+    /// Otherwise, the copied images are deleted once the history is dropped.
+    pub persist: bool,
+    /// Directory storing the history and the copied images. Defaults to
+    /// `$XDG_CACHE_HOME/spell/clipboard`.
+    pub directory: Option<PathBuf>,
+}
+
+impl Default for HistoryConf {
+    fn default() -> Self {
+        HistoryConf {
+            max_entries: 100,
+            persist: false,
+            directory: None,
+        }
+    }
+}
+
+/// An entry of the clipboard history, either text or an image.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ClipboardEntry {
+    /// Unique ID of the entry, used to pin, delete or select it.
+    pub id: u64,
+    /// MIME type in which the entry was copied.
+    pub mime_type: String,
+    /// Copied text, `None` for images.
+    pub text: Option<SharedString>,
+    /// File in which the copied image is stored, `None` for text.
+    pub image_path: Option<PathBuf>,
+    /// Pinned entries are kept at the top and never removed for new ones.
+    pub pinned: bool,
+    /// Hash of the copied data, used to find copies of the same data.
+    hash: u64,
+}
+
+impl ClipboardEntry {
+    /// Returns the first line of the text (or `[image]` for images) to be shown in
+    /// the list of entries.
+    pub fn preview(&self) -> SharedString {
+        match &self.text {
+            Some(text) => text.trim().lines().next().unwrap_or_default().into(),
+            None => "[image]".into(),
+        }
+    }
+
+    /// Loads the image of the entry, if it is one.
+    pub fn image(&self) -> Option<Image> {
+        self.image_path
+            .as_ref()
+            .and_then(|path| Image::load_from_path(path).ok())
+    }
+
+    fn matches(&self, query: &str) -> bool {
+        match &self.text {
+            Some(text) => text.to_lowercase().contains(query),
+            None => self.mime_type.contains(query),
+        }
+    }
+}
+
+/// Entry as saved in the history file.
+#[derive(Debug, Serialize, Deserialize)]
+struct StoredEntry {
+    mime_type: String,
+    text: Option<String>,
+    image_path: Option<PathBuf>,
+    pinned: bool,
+    /// TOML only has signed integers, the hash is stored as one.
+    hash: i64,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct StoredHistory {
+    entries: Vec<StoredEntry>,
+}
+
+/// Data copied in the session, sent from the watching thread.
+struct CopiedData {
+    mime_type: String,
+    data: Vec<u8>,
+}
+
+/// Data to be set as the selection by the watching thread, offered in all the
+/// given MIME types.
+struct Selection {
+    mime_types: Vec<String>,
+    data: Vec<u8>,
+}
+
+struct HistoryInner {
+    model: Rc<VecModel<ClipboardEntry>>,
+    conf: HistoryConf,
+    directory: PathBuf,
+    next_id: Cell<u64>,
+    /// Dropping it stops the thread watching the clipboard.
+    selection_sender: Sender<Selection>,
+    /// Event loop of the widget receiving the copied data, along with the token of
+    /// its source.
+    copied_source: Option<(
+        LoopHandle<'static, SpellWin>,
+        Cell<Option<RegistrationToken>>,
+    )>,
+}
+
+impl Drop for HistoryInner {
+    fn drop(&mut self) {
+        if let Some((loop_handle, token)) = &self.copied_source
+            && let Some(token) = token.take()
+        {
+            loop_handle.remove(token);
+        }
+        // Images of a history which isn't persisted are of no use to the next start.
+        if self.conf.persist {
+            return;
+        }
+        for entry in self.model.iter() {
+            if let Some(path) = &entry.image_path
+                && let Err(err) = fs::remove_file(path)
+            {
+                warn!("Couldn't remove the image of the clipboard entry: {}", err);
+            }
+        }
+    }
+}
+
+/// Clipboard history of the session, kept by a thread watching the clipboard. See
+/// the [module docs](crate::vault::clipboard) for its usage with slint. It is
+/// cheap to clone and all clones refer to the same history.
+#[derive(Clone)]
+pub struct ClipboardHistory {
+    inner: Rc<HistoryInner>,
+}
+
+impl ClipboardHistory {
+    /// Starts watching the clipboard, new entries are added in the event loop of
+    /// the widget whose handle is given. The persisted history is loaded if
+    /// [`HistoryConf::persist`] is set.
+    pub fn new(handle: &WinHandle, conf: HistoryConf) -> Result<Self, Box<dyn Error>> {
+        let directory = match &conf.directory {
+            Some(directory) => directory.clone(),
+            None => env::var_os("XDG_CACHE_HOME")
+                .map(PathBuf::from)
+                .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+                .ok_or("Couldn't find the cache directory")?
+                .join("spell/clipboard"),
+        };
+        fs::create_dir_all(&directory)?;
+        let (copied_sender, copied_channel) = channel::channel::<CopiedData>();
+        let (selection_sender, selection_channel) = channel::channel::<Selection>();
+        let history = ClipboardHistory {
+            inner: Rc::new(HistoryInner {
+                model: Rc::new(VecModel::default()),
+                conf,
+                directory,
+                next_id: Cell::new(0),
+                selection_sender,
+                copied_source: Some((handle.0.clone(), Cell::new(None))),
+            }),
+        };
+        if history.inner.conf.persist {
+            history.load();
+        }
+
+        std::thread::spawn(move || {
+            if let Err(err) = watch_clipboard(copied_sender, selection_channel) {
+                warn!("Clipboard history stopped with error: {}", err);
+            }
+        });
+        // The source only refers to the history weakly, so that the history is
+        // dropped (which removes the source) once the user drops it.
+        let weak_inner = Rc::downgrade(&history.inner);
+        let token = handle
+            .0
+            .insert_source(copied_channel, move |event, _, _| {
+                let Some(inner) = weak_inner.upgrade() else {
+                    return;
+                };
+                match event {
+                    channel::Event::Msg(copied) => ClipboardHistory { inner }.add(copied),
+                    channel::Event::Closed => info!("Clipboard history channel is closed!"),
+                }
+            })
+            .map_err(|err| err.error)?;
+        if let Some((_, source_token)) = &history.inner.copied_source {
+            source_token.set(Some(token));
+        }
+        Ok(history)
+    }
+
+    /// Returns the model of the entries, the newest (or pinned) ones first.
+    pub fn model(&self) -> ModelRc<ClipboardEntry> {
+        ModelRc::from(self.inner.model.clone())
+    }
+
+    /// Returns the model of the entries containing the query, ignoring case. The
+    /// model stays updated with the history.
+    pub fn search(&self, query: &str) -> ModelRc<ClipboardEntry> {
+        let query = query.to_lowercase();
+        ModelRc::new(FilterModel::new(self.inner.model.clone(), move |entry| {
+            entry.matches(&query)
+        }))
+    }
+
+    /// Returns the entry with the given ID.
+    pub fn get(&self, id: u64) -> Option<ClipboardEntry> {
+        self.position(id)
+            .and_then(|index| self.inner.model.row_data(index))
+    }
+
+    /// Pins or unpins the entry with the given ID. Pinned entries are moved above
+    /// the rest.
+    pub fn pin(&self, id: u64, pinned: bool) {
+        let Some(index) = self.position(id) else {
+            return;
+        };
+        let mut entry = self.inner.model.remove(index);
+        entry.pinned = pinned;
+        self.insert(entry);
+        self.save();
+    }
+
+    /// Deletes the entry with the given ID, pinned or not.
+    pub fn delete(&self, id: u64) {
+        if let Some(index) = self.position(id) {
+            let entry = self.inner.model.remove(index);
+            self.remove_image(&entry);
+            self.save();
+        }
+    }
+
+    /// Deletes all the entries which are not pinned.
+    pub fn clear(&self) {
+        let mut index = 0;
+        while let Some(entry) = self.inner.model.row_data(index) {
+            if entry.pinned {
+                index += 1;
+            } else {
+                self.inner.model.remove(index);
+                self.remove_image(&entry);
+            }
+        }
+        self.save();
+    }
+
+    /// Sets the entry with the given ID as the current selection of the session,
+    /// which also moves it to the top of the history.
+    pub fn select(&self, id: u64) {
+        let Some(entry) = self.get(id) else {
+            return;
+        };
+        let selection = match (&entry.text, &entry.image_path) {
+            (Some(text), _) => Selection {
+                mime_types: TEXT_MIME_TYPES.iter().map(|m| m.to_string()).collect(),
+                data: text.as_bytes().to_vec(),
+            },
+            (None, Some(path)) => match fs::read(path) {
+                Ok(data) => Selection {
+                    mime_types: vec![entry.mime_type.clone()],
+                    data,
+                },
+                Err(err) => {
+                    warn!("Couldn't read the image of the clipboard entry: {}", err);
+                    return;
+                }
+            },
+            (None, None) => return,
+        };
+        if self.inner.selection_sender.send(selection).is_err() {
+            warn!("Clipboard history is no longer watching the clipboard");
+        }
+    }
+
+    fn position(&self, id: u64) -> Option<usize> {
+        self.inner.model.iter().position(|entry| entry.id == id)
+    }
+
+    fn next_id(&self) -> u64 {
+        let id = self.inner.next_id.get();
+        self.inner.next_id.set(id + 1);
+        id
+    }
+
+    /// Inserts the entry at the top of the pinned or the other entries.
+    fn insert(&self, entry: ClipboardEntry) {
+        let index = if entry.pinned {
+            0
+        } else {
+            self.inner.model.iter().take_while(|e| e.pinned).count()
+        };
+        self.inner.model.insert(index, entry);
+    }
+
+    fn add(&self, copied: CopiedData) {
+        let hash = stable_hash(&copied.data);
+        // Copying the same data again moves the older entry to the top.
+        let mut pinned = false;
+        if let Some(index) = self.inner.model.iter().position(|entry| entry.hash == hash) {
+            pinned = self.inner.model.remove(index).pinned;
+        }
+        let (text, image_path) = if IMAGE_MIME_TYPES.contains(&copied.mime_type.as_str()) {
+            let extension = copied.mime_type.trim_start_matches("image/");
+            let path = self.inner.directory.join(format!("{hash:x}.{extension}"));
+            if let Err(err) = fs::write(&path, &copied.data) {
+                warn!("Couldn't save the copied image: {}", err);
+                return;
+            }
+            (None, Some(path))
+        } else {
+            let text = String::from_utf8_lossy(&copied.data);
+            if text.trim().is_empty() {
+                return;
+            }
+            (Some(SharedString::from(text.as_ref())), None)
+        };
+        let entry = ClipboardEntry {
+            id: self.next_id(),
+            mime_type: copied.mime_type,
+            text,
+            image_path,
+            pinned,
+            hash,
+        };
+        self.insert(entry);
+
+        // The oldest entry which is not pinned is removed to make space.
+        while self.inner.model.row_count() > self.inner.conf.max_entries {
+            let Some(index) = (0..self.inner.model.row_count())
+                .rev()
+                .find(|index| self.inner.model.row_data(*index).is_some_and(|e| !e.pinned))
+            else {
+                break;
+            };
+            let entry = self.inner.model.remove(index);
+            self.remove_image(&entry);
+        }
+        self.save();
+    }
+
+    fn remove_image(&self, entry: &ClipboardEntry) {
+        if let Some(path) = &entry.image_path
+            && let Err(err) = fs::remove_file(path)
+        {
+            warn!("Couldn't remove the image of the clipboard entry: {}", err);
+        }
+    }
+
+    fn history_file(&self) -> PathBuf {
+        self.inner.directory.join("history.toml")
+    }
+
+    fn load(&self) {
+        let history: StoredHistory = match fs::read_to_string(self.history_file()) {
+            Ok(contents) => match toml::from_str(&contents) {
+                Ok(history) => history,
+                Err(err) => {
+                    warn!("Couldn't parse the clipboard history: {}", err);
+                    return;
+                }
+            },
+            Err(_) => return,
+        };
+        for stored in history.entries {
+            let entry = ClipboardEntry {
+                id: self.next_id(),
+                mime_type: stored.mime_type,
+                text: stored.text.map(SharedString::from),
+                image_path: stored.image_path,
+                pinned: stored.pinned,
+                hash: stored.hash as u64,
+            };
+            self.inner.model.push(entry);
+        }
+    }
+
+    fn save(&self) {
+        if !self.inner.conf.persist {
+            return;
+        }
+        let history = StoredHistory {
+            entries: self
+                .inner
+                .model
+                .iter()
+                .map(|entry| StoredEntry {
+                    mime_type: entry.mime_type,
+                    text: entry.text.map(|text| text.to_string()),
+                    image_path: entry.image_path,
+                    pinned: entry.pinned,
+                    hash: entry.hash as i64,
+                })
+                .collect(),
+        };
+        let result = toml::to_string(&history)
+            .map_err(Box::<dyn Error>::from)
+            .and_then(|contents| Ok(fs::write(self.history_file(), contents)?));
+        if let Err(err) = result {
+            warn!("Couldn't save the clipboard history: {}", err);
+        }
+    }
+}
+
+/// Returns the 64 bit FNV-1a hash of the data. Hashes are saved with the history
+/// and used as the names of image files, so unlike the hashers of std, it has to
+/// stay the same across releases of Rust.
+fn stable_hash(data: &[u8]) -> u64 {
+    const OFFSET_BASIS: u64 = 0xcbf2_9ce4_8422_2325;
+    const PRIME: u64 = 0x0100_0000_01b3;
+    data.iter().fold(OFFSET_BASIS, |hash, byte| {
+        (hash ^ u64::from(*byte)).wrapping_mul(PRIME)
+    })
+}
+
+/// Returns the MIME type to record the offer in, `None` if it has neither text nor
+/// an image or is a secret.
+fn preferred_mime_type(mime_types: &[String]) -> Option<String> {
+    if mime_types.iter().any(|m| m == SECRET_MIME_TYPE) {
+        return None;
+    }
+    IMAGE_MIME_TYPES
+        .iter()
+        .chain(TEXT_MIME_TYPES.iter())
+        .find(|preferred| mime_types.iter().any(|m| m == *preferred))
+        .map(|preferred| preferred.to_string())
+}
+
+// Both the protocols have the same requests and events, they are wrapped so that
+// the watcher doesn't depend on the one in use.
+#[derive(Debug)]
+enum DataControlManager {
+    Ext(ExtDataControlManagerV1),
+    Wlr(ZwlrDataControlManagerV1),
+}
+
+#[derive(Debug)]
+enum DataControlOffer {
+    Ext(ExtDataControlOfferV1),
+    Wlr(ZwlrDataControlOfferV1),
+}
+
+#[derive(Debug)]
+enum DataControlSource {
+    Ext(ExtDataControlSourceV1),
+    Wlr(ZwlrDataControlSourceV1),
+}
+
+#[derive(Debug)]
+enum DataControlDevice {
+    Ext(ExtDataControlDeviceV1),
+    Wlr(ZwlrDataControlDeviceV1),
+}
+
+impl DataControlOffer {
+    fn mime_types(&self) -> Vec<String> {
+        let data = match self {
+            DataControlOffer::Ext(offer) => offer.data::<OfferData>(),
+            DataControlOffer::Wlr(offer) => offer.data::<OfferData>(),
+        };
+        data.map(|data| data.0.lock().unwrap().clone())
+            .unwrap_or_default()
+    }
+
+    fn receive(&self, mime_type: String, fd: std::os::fd::BorrowedFd<'_>) {
+        match self {
+            DataControlOffer::Ext(offer) => offer.receive(mime_type, fd),
+            DataControlOffer::Wlr(offer) => offer.receive(mime_type, fd),
+        }
+    }
+
+    fn destroy(&self) {
+        match self {
+            DataControlOffer::Ext(offer) => offer.destroy(),
+            DataControlOffer::Wlr(offer) => offer.destroy(),
+        }
+    }
+}
+
+impl DataControlSource {
+    fn destroy(&self) {
+        match self {
+            DataControlSource::Ext(source) => source.destroy(),
+            DataControlSource::Wlr(source) => source.destroy(),
+        }
+    }
+}
+
+/// MIME types offered by a data control offer.
+#[derive(Debug, Default)]
+struct OfferData(Mutex<Vec<String>>);
+
+/// State of the thread watching the clipboard.
+struct ClipboardWatcher {
+    manager: DataControlManager,
+    device: DataControlDevice,
+    queue: QueueHandle<ClipboardWatcher>,
+    loop_handle: LoopHandle<'static, ClipboardWatcher>,
+    sender: Sender<CopiedData>,
+    /// Offer of the current selection.
+    offer: Option<DataControlOffer>,
+    /// Source set by the history along with its data.
+    source: Option<(DataControlSource, Rc<[u8]>)>,
+    /// Unset when the clipboard can no longer be watched or the history is dropped.
+    running: bool,
+}
+
+fn watch_clipboard(
+    sender: Sender<CopiedData>,
+    selections: Channel<Selection>,
+) -> Result<(), Box<dyn Error>> {
+    let conn = Connection::connect_to_env()?;
+    let (globals, event_queue) = registry_queue_init::<ClipboardWatcher>(&conn)?;
+    let queue: QueueHandle<ClipboardWatcher> = event_queue.handle();
+    let manager = match globals.bind::<ExtDataControlManagerV1, _, _>(&queue, 1..=1, ()) {
+        Ok(manager) => DataControlManager::Ext(manager),
+        Err(_) => {
+            info!("ext-data-control is not available, falling back to wlr-data-control");
+            DataControlManager::Wlr(globals.bind(&queue, 1..=2, ())?)
+        }
+    };
+    let seat: WlSeat = globals.bind(&queue, 1..=1, ())?;
+    let device = match &manager {
+        DataControlManager::Ext(manager) => {
+            DataControlDevice::Ext(manager.get_data_device(&seat, &queue, ()))
+        }
+        DataControlManager::Wlr(manager) => {
+            DataControlDevice::Wlr(manager.get_data_device(&seat, &queue, ()))
+        }
+    };
+
+    let mut event_loop: EventLoop<'static, ClipboardWatcher> = EventLoop::try_new()?;
+    WaylandSource::new(conn, event_queue)
+        .insert(event_loop.handle())
+        .map_err(|err| err.error)?;
+    event_loop
+        .handle()
+        .insert_source(selections, |event, _, watcher| match event {
+            channel::Event::Msg(selection) => watcher.set_selection(selection),
+            channel::Event::Closed => {
+                info!("Clipboard history is dropped, no longer watching the clipboard");
+                watcher.running = false;
+            }
+        })
+        .map_err(|err| err.error)?;
+    let mut watcher = ClipboardWatcher {
+        manager,
+        device,
+        queue,
+        loop_handle: event_loop.handle(),
+        sender,
+        offer: None,
+        source: None,
+        running: true,
+    };
+    info!("Clipboard history is watching the clipboard");
+    while watcher.running {
+        event_loop.dispatch(None, &mut watcher)?;
+    }
+    Ok(())
+}
+
+impl ClipboardWatcher {
+    fn new_selection(&mut self, offer: Option<DataControlOffer>) {
+        if let Some(old_offer) = std::mem::replace(&mut self.offer, offer) {
+            old_offer.destroy();
+        }
+        let Some(offer) = &self.offer else {
+            return;
+        };
+        let Some(mime_type) = preferred_mime_type(&offer.mime_types()) else {
+            return;
+        };
+        let (reader, writer) = match std::io::pipe() {
+            Ok(pipe) => pipe,
+            Err(err) => {
+                warn!("Couldn't create a pipe for the clipboard: {}", err);
+                return;
+            }
+        };
+        offer.receive(mime_type.clone(), writer.as_fd());
+        drop(writer);
+        read_pipe(&self.loop_handle, reader, move |watcher, result| {
+            match result {
+                Ok(data) => {
+                    let _ = watcher.sender.send(CopiedData { mime_type, data });
+                }
+                Err(err) => warn!("Couldn't read the clipboard: {}", err),
+            };
+        });
+    }
+
+    fn set_selection(&mut self, selection: Selection) {
+        let source = match &self.manager {
+            DataControlManager::Ext(manager) => {
+                DataControlSource::Ext(manager.create_data_source(&self.queue, ()))
+            }
+            DataControlManager::Wlr(manager) => {
+                DataControlSource::Wlr(manager.create_data_source(&self.queue, ()))
+            }
+        };
+        for mime_type in selection.mime_types {
+            match &source {
+                DataControlSource::Ext(source) => source.offer(mime_type),
+                DataControlSource::Wlr(source) => source.offer(mime_type),
+            }
+        }
+        match (&self.device, &source) {
+            (DataControlDevice::Ext(device), DataControlSource::Ext(source)) => {
+                device.set_selection(Some(source))
+            }
+            (DataControlDevice::Wlr(device), DataControlSource::Wlr(source)) => {
+                device.set_selection(Some(source))
+            }
+            _ => unreachable!("Device and source are of the same protocol"),
+        }
+        if let Some((old_source, _)) = self.source.replace((source, selection.data.into())) {
+            old_source.destroy();
+        }
+    }
+
+    fn send(&mut self, fd: std::os::fd::OwnedFd) {
+        if let Some((_, data)) = &self.source {
+            write_pipe(&self.loop_handle, fd, data.clone());
+        }
+    }
+
+    fn cancelled(&mut self) {
+        if let Some((source, _)) = self.source.take() {
+            source.destroy();
+        }
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for ClipboardWatcher {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<WlSeat, ()> for ClipboardWatcher {
+    fn event(
+        _: &mut Self,
+        _: &WlSeat,
+        _: <WlSeat as Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+// Dispatch implementations are the same for both the protocols, apart from their
+// types.
+macro_rules! data_control_dispatch {
+    ($variant:ident, $manager:ty, $device:ty, $device_mod:ident, $offer:ty, $offer_mod:ident, $source:ty, $source_mod:ident) => {
+        impl Dispatch<$manager, ()> for ClipboardWatcher {
+            fn event(
+                _: &mut Self,
+                _: &$manager,
+                _: <$manager as Proxy>::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+            }
+        }
+
+        impl Dispatch<$device, ()> for ClipboardWatcher {
+            fn event(
+                watcher: &mut Self,
+                device: &$device,
+                event: <$device as Proxy>::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                match event {
+                    $device_mod::Event::Selection { id } => {
+                        watcher.new_selection(id.map(DataControlOffer::$variant));
+                    }
+                    // Primary selection is not recorded.
+                    $device_mod::Event::PrimarySelection { id: Some(offer) } => offer.destroy(),
+                    $device_mod::Event::Finished => {
+                        warn!("Clipboard can no longer be watched");
+                        device.destroy();
+                        watcher.running = false;
+                    }
+                    _ => {}
+                }
+            }
+
+            event_created_child!(ClipboardWatcher, $device, [
+                $device_mod::EVT_DATA_OFFER_OPCODE => ($offer, OfferData::default())
+            ]);
+        }
+
+        impl Dispatch<$offer, OfferData> for ClipboardWatcher {
+            fn event(
+                _: &mut Self,
+                _: &$offer,
+                event: <$offer as Proxy>::Event,
+                data: &OfferData,
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                if let $offer_mod::Event::Offer { mime_type } = event {
+                    data.0.lock().unwrap().push(mime_type);
+                }
+            }
+        }
+
+        impl Dispatch<$source, ()> for ClipboardWatcher {
+            fn event(
+                watcher: &mut Self,
+                _: &$source,
+                event: <$source as Proxy>::Event,
+                _: &(),
+                _: &Connection,
+                _: &QueueHandle<Self>,
+            ) {
+                match event {
+                    $source_mod::Event::Send { fd, .. } => watcher.send(fd),
+                    $source_mod::Event::Cancelled => watcher.cancelled(),
+                    _ => {}
+                }
+            }
+        }
+    };
+}
+
+data_control_dispatch!(
+    Ext,
+    ExtDataControlManagerV1,
+    ExtDataControlDeviceV1,
+    ext_data_control_device_v1,
+    ExtDataControlOfferV1,
+    ext_data_control_offer_v1,
+    ExtDataControlSourceV1,
+    ext_data_control_source_v1
+);
+data_control_dispatch!(
+    Wlr,
+    ZwlrDataControlManagerV1,
+    ZwlrDataControlDeviceV1,
+    zwlr_data_control_device_v1,
+    ZwlrDataControlOfferV1,
+    zwlr_data_control_offer_v1,
+    ZwlrDataControlSourceV1,
+    zwlr_data_control_source_v1
+);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn history(max_entries: usize) -> ClipboardHistory {
+        let (selection_sender, _) = channel::channel();
+        ClipboardHistory {
+            inner: Rc::new(HistoryInner {
+                model: Rc::new(VecModel::default()),
+                conf: HistoryConf {
+                    max_entries,
+                    ..HistoryConf::default()
+                },
+                directory: env::temp_dir(),
+                next_id: Cell::new(0),
+                selection_sender,
+                copied_source: None,
+            }),
+        }
+    }
+
+    fn copy(history: &ClipboardHistory, text: &str) {
+        history.add(CopiedData {
+            mime_type: "text/plain".to_string(),
+            data: text.as_bytes().to_vec(),
+        });
+    }
+
+    fn texts(history: &ClipboardHistory) -> Vec<String> {
+        history
+            .inner
+            .model
+            .iter()
+            .map(|entry| entry.text.unwrap_or_default().to_string())
+            .collect()
+    }
+
+    fn mime_types(mime_types: &[&str]) -> Vec<String> {
+        mime_types.iter().map(|m| m.to_string()).collect()
+    }
+
+    #[test]
+    fn stable_hash_is_fnv1a() {
+        let cases: [(&[u8], u64); 3] = [
+            (b"", 0xcbf2_9ce4_8422_2325),
+            (b"a", 0xaf63_dc4c_8601_ec8c),
+            (b"foobar", 0x8594_4171_f739_67e8),
+        ];
+        for (data, hash) in cases {
+            assert_eq!(stable_hash(data), hash, "data: {:?}", data);
+        }
+    }
+
+    #[test]
+    fn preferred_mime_type_prefers_images_and_skips_secrets() {
+        let cases: [(&[&str], Option<&str>); 5] = [
+            (&["text/plain", "image/png"], Some("image/png")),
+            (&["image/bmp", "image/jpeg"], Some("image/jpeg")),
+            (
+                &["TEXT", "text/plain;charset=utf-8"],
+                Some("text/plain;charset=utf-8"),
+            ),
+            (&["application/x-unknown"], None),
+            (&["text/plain", SECRET_MIME_TYPE], None),
+        ];
+        for (offered, preferred) in cases {
+            assert_eq!(
+                preferred_mime_type(&mime_types(offered)).as_deref(),
+                preferred,
+                "offered: {:?}",
+                offered
+            );
+        }
+    }
+
+    #[test]
+    fn add_evicts_oldest_unpinned_entries() {
+        let history = history(3);
+        copy(&history, "first");
+        copy(&history, "second");
+        let first = history.inner.model.row_data(1).unwrap();
+        history.pin(first.id, true);
+        copy(&history, "third");
+        copy(&history, "fourth");
+        assert_eq!(texts(&history), ["first", "fourth", "third"]);
+
+        // Copying an entry again moves it to the top and keeps it pinned.
+        copy(&history, "third");
+        copy(&history, "first");
+        assert_eq!(texts(&history), ["first", "third", "fourth"]);
+        assert!(history.inner.model.row_data(0).unwrap().pinned);
+    }
+
+    #[test]
+    fn add_keeps_pinned_entries_over_the_limit() {
+        let history = history(1);
+        copy(&history, "first");
+        history.pin(history.inner.model.row_data(0).unwrap().id, true);
+        copy(&history, "second");
+        assert_eq!(texts(&history), ["first"]);
+        copy(&history, "   ");
+        assert_eq!(texts(&history), ["first"]);
+    }
+}
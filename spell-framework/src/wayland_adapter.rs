@@ -1,20 +1,36 @@
 //! It provides various widget types for implementing properties
 //! across various functionalities for your shell. The most common widget (or
 //! window as called by many) is [SpellWin]. You can also implement a lock screen
-//! with [`SpellLock`].
+//! with [`SpellLock`] and an on-screen keyboard with [`SpellBoard`].
 
+mod clipboard;
 mod common;
+mod cursor;
+mod data_device;
 mod fractional_scaling;
+mod gestures;
 mod lock;
+mod outputs;
+mod text_input;
 mod viewporter;
 mod window;
 
+pub use clipboard::SpellClipboard;
+pub use data_device::{DropEvent, MimeData};
+pub use gestures::Gesture;
+pub use window::BoardHandle;
+pub use window::BoardModifiers;
+pub use window::SpellBoard;
+pub use window::SpellError;
 pub use window::SpellWin;
 pub use window::SpellXDGPopup;
 pub use window::WinHandle;
 
+pub use outputs::SpellOutputs;
+
+pub(crate) use clipboard::TEXT_MIME_TYPES;
+pub(crate) use data_device::{read_pipe, write_pipe};
+
 pub use lock::LockHandle;
+pub use lock::LockOutput;
 pub use lock::SpellLock;
-
-/// Furture virtual keyboard implementation will be on this type. Currently, it is redundent.
-pub struct SpellBoard;
@@ -0,0 +1,239 @@
+// This module contains the clipboard shared by the widgets. Each widget has its
+// own connection, so the selection is received and set by whichever widget has the
+// keyboard focus, while the data is kept here for the rest of them and for slint.
+use std::{cell::RefCell, future::Future, io, rc::Rc};
+
+use slint::platform::Clipboard;
+use smithay_client_toolkit::{
+    data_device_manager::data_source::CopyPasteSource,
+    primary_selection::selection::PrimarySelectionSource,
+};
+use tokio::sync::oneshot;
+
+use crate::wayland_adapter::data_device::MimeData;
+
+/// MIME types of text in the order of preference.
+pub(crate) const TEXT_MIME_TYPES: [&str; 5] = [
+    "text/plain;charset=utf-8",
+    "UTF8_STRING",
+    "text/plain",
+    "TEXT",
+    "STRING",
+];
+
+pub(crate) type ReadCallback = Box<dyn FnOnce(io::Result<Vec<u8>>)>;
+
+thread_local! {
+    static CLIPBOARD: RefCell<SharedClipboard> = RefCell::new(SharedClipboard::default());
+}
+
+/// Current selection offered by another client (or by a widget itself).
+struct Selection {
+    /// Incremented on every new selection, so that reads of older ones are ignored.
+    generation: u64,
+    mime_types: Vec<String>,
+    /// Text of the selection, read as soon as the selection is received so that it
+    /// can be given to slint without waiting.
+    text: Option<String>,
+    /// Starts reading the selection in the given MIME type on the widget which
+    /// received it.
+    receive: Rc<dyn Fn(String, ReadCallback)>,
+}
+
+#[derive(Default)]
+struct SharedClipboard {
+    generation: u64,
+    /// Selections of the regular clipboard and of the primary selection.
+    selections: [Option<Selection>; 2],
+    /// Data set from the widgets, which is yet to be offered by a widget with
+    /// keyboard focus.
+    pending: [Option<MimeData>; 2],
+}
+
+fn index(clipboard: Clipboard) -> Option<usize> {
+    match clipboard {
+        Clipboard::DefaultClipboard => Some(0),
+        Clipboard::SelectionClipboard => Some(1),
+        _ => None,
+    }
+}
+
+/// Clipboard of the shell, shared by all of its widgets. It supports both the regular
+/// clipboard ([`Clipboard::DefaultClipboard`]) and the primary selection
+/// ([`Clipboard::SelectionClipboard`]), with data in any MIME type like images.
+/// Slint's own copy and paste go through it as well.
+///
+/// Wayland only lets the client with keyboard focus set and receive the selection,
+/// so the data set here is offered once a widget has the keyboard focus, and the
+/// selection of other clients is known after a widget has had it. The clipboard
+/// can only be used from the thread running the widgets.
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SpellClipboard;
+
+impl SpellClipboard {
+    /// Sets the data of the clipboard, it is offered to other clients on the next
+    /// dispatch of a widget with keyboard focus.
+    pub fn set(&self, clipboard: Clipboard, data: MimeData) {
+        let Some(index) = index(clipboard) else {
+            return;
+        };
+        let text = TEXT_MIME_TYPES
+            .iter()
+            .find_map(|mime_type| data.get(mime_type))
+            .map(|text| String::from_utf8_lossy(&text).into_owned());
+        let mime_types = data.mime_types().map(str::to_string).collect();
+        let local_data = data.clone();
+        CLIPBOARD.with_borrow_mut(|shared| {
+            shared.generation += 1;
+            shared.pending[index] = Some(data);
+            // Till the selection is received back from the compositor, it is read
+            // from the data itself.
+            shared.selections[index] = Some(Selection {
+                generation: shared.generation,
+                mime_types,
+                text,
+                receive: Rc::new(move |mime_type, callback| {
+                    callback(
+                        local_data
+                            .get(&mime_type)
+                            .map(|data| data.to_vec())
+                            .ok_or_else(|| io::Error::from(io::ErrorKind::NotFound)),
+                    )
+                }),
+            });
+        });
+        crate::wake_event_loop();
+    }
+
+    /// Sets the text of the clipboard in all the common text MIME types.
+    pub fn set_text(&self, clipboard: Clipboard, text: &str) {
+        let data = TEXT_MIME_TYPES
+            .iter()
+            .fold(MimeData::new(), |data, mime_type| {
+                data.with(*mime_type, text.as_bytes())
+            });
+        self.set(clipboard, data);
+    }
+
+    /// Returns the MIME types in which the current data of the clipboard is offered.
+    pub fn mime_types(&self, clipboard: Clipboard) -> Vec<String> {
+        let Some(index) = index(clipboard) else {
+            return Vec::new();
+        };
+        CLIPBOARD.with_borrow(|shared| {
+            shared.selections[index]
+                .as_ref()
+                .map(|selection| selection.mime_types.clone())
+                .unwrap_or_default()
+        })
+    }
+
+    /// Returns the text of the clipboard, if it holds any.
+    pub fn text(&self, clipboard: Clipboard) -> Option<String> {
+        let index = index(clipboard)?;
+        CLIPBOARD.with_borrow(|shared| {
+            shared.selections[index]
+                .as_ref()
+                .and_then(|selection| selection.text.clone())
+        })
+    }
+
+    /// Reads the data of the clipboard in the given MIME type. The data is read
+    /// inside the event loop, so the future can be awaited from
+    /// [`slint::spawn_local`] without blocking the widgets.
+    pub fn read(
+        &self,
+        clipboard: Clipboard,
+        mime_type: &str,
+    ) -> impl Future<Output = io::Result<Vec<u8>>> + 'static {
+        let (sender, receiver) = oneshot::channel();
+        let receive = index(clipboard).and_then(|index| {
+            CLIPBOARD.with_borrow(|shared| {
+                shared.selections[index]
+                    .as_ref()
+                    .filter(|selection| selection.mime_types.iter().any(|m| m == mime_type))
+                    .map(|selection| selection.receive.clone())
+            })
+        });
+        match receive {
+            Some(receive) => receive(
+                mime_type.to_string(),
+                Box::new(move |result| {
+                    let _ = sender.send(result);
+                }),
+            ),
+            None => {
+                let _ = sender.send(Err(io::Error::new(
+                    io::ErrorKind::NotFound,
+                    format!("Clipboard holds no data of type {}", mime_type),
+                )));
+            }
+        }
+        async move {
+            receiver
+                .await
+                .unwrap_or_else(|_| Err(io::Error::other("Clipboard read was cancelled")))
+        }
+    }
+}
+
+/// Takes the data set from the widgets to be offered as the selection.
+pub(crate) fn take_pending(clipboard: Clipboard) -> Option<MimeData> {
+    let index = index(clipboard)?;
+    CLIPBOARD.with_borrow_mut(|shared| shared.pending[index].take())
+}
+
+/// Replaces the selection with the one received by a widget, `None` clears it. The
+/// text of the selection is read right away.
+pub(crate) fn set_selection(
+    clipboard: Clipboard,
+    mime_types: Vec<String>,
+    receive: Option<Rc<dyn Fn(String, ReadCallback)>>,
+) {
+    let Some(index) = index(clipboard) else {
+        return;
+    };
+    let generation = CLIPBOARD.with_borrow_mut(|shared| {
+        shared.generation += 1;
+        shared.selections[index] = receive.clone().map(|receive| Selection {
+            generation: shared.generation,
+            mime_types: mime_types.clone(),
+            text: None,
+            receive,
+        });
+        shared.generation
+    });
+    let text_type = TEXT_MIME_TYPES
+        .iter()
+        .find(|text_type| mime_types.iter().any(|m| m == *text_type));
+    if let (Some(receive), Some(text_type)) = (receive, text_type) {
+        receive(
+            text_type.to_string(),
+            Box::new(move |result| {
+                let Ok(text) = result else {
+                    return;
+                };
+                CLIPBOARD.with_borrow_mut(|shared| {
+                    if let Some(selection) = shared.selections[index]
+                        .as_mut()
+                        .filter(|selection| selection.generation == generation)
+                    {
+                        selection.text = Some(String::from_utf8_lossy(&text).into_owned());
+                    }
+                });
+            }),
+        );
+    }
+}
+
+/// Clipboard state of a widget, the selection can only be set with the serial of
+/// an input event received by it.
+#[derive(Default)]
+pub(crate) struct ClipboardState {
+    pub(crate) has_focus: bool,
+    /// Serial of the latest input event.
+    pub(crate) serial: Option<u32>,
+    /// Sources offered by the widget along with their data.
+    pub(crate) source: Option<(CopyPasteSource, MimeData)>,
+    pub(crate) primary_source: Option<(PrimarySelectionSource, MimeData)>,
+}
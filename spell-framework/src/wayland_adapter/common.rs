@@ -6,54 +6,214 @@ use slint::{
     platform::{Key, PointerEventButton},
 };
 use smithay_client_toolkit::{
+    globals::GlobalData,
+    output::{OutputInfo, OutputState},
     reexports::{
-        client::{QueueHandle, protocol::wl_pointer},
-        protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape,
+        calloop::{
+            LoopHandle, RegistrationToken,
+            timer::{TimeoutAction, Timer},
+        },
+        client::{
+            Dispatch, QueueHandle,
+            protocol::{wl_output::WlOutput, wl_pointer, wl_surface::WlSurface},
+        },
+        protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::{
+            Shape, WpCursorShapeDeviceV1,
+        },
     },
     seat::{
-        keyboard::{KeyEvent, Keysym},
+        keyboard::{KeyEvent, Keysym, RepeatInfo},
         pointer::{PointerData, cursor_shape::CursorShapeManager},
     },
 };
 
-use crate::wayland_adapter::SpellWin;
+use std::{
+    cell::Cell,
+    rc::Rc,
+    time::{Duration, Instant},
+};
+use tracing::warn;
+
+use crate::{slint_adapter::SpellSkiaWinAdapter, wayland_adapter::cursor::ThemedCursor};
+
+/// Client side key repeat. Compositors only send the press and release of a key,
+/// repeating a held key is left to the clients as per the delay and rate sent in
+/// `wl_keyboard.repeat_info`. Repeats are run by a timer in the widget's event loop.
+#[derive(Debug)]
+pub(crate) struct KeyRepeat {
+    /// Delay before the first repeat and the interval between the following ones,
+    /// `None` when the compositor has disabled key repeat.
+    timing: Option<(Duration, Duration)>,
+    /// Raw code of the key being repeated along with its timer.
+    current: Option<(u32, RegistrationToken)>,
+    /// Time at which the key is to be repeated next.
+    deadline: Rc<Cell<Option<Instant>>>,
+}
+
+impl Default for KeyRepeat {
+    fn default() -> Self {
+        // Used till the compositor sends its own values, 25 repeats per second
+        // after 600ms is the common default of compositors.
+        KeyRepeat {
+            timing: Some((Duration::from_millis(600), Duration::from_millis(40))),
+            current: None,
+            deadline: Rc::new(Cell::new(None)),
+        }
+    }
+}
+
+impl KeyRepeat {
+    pub(crate) fn update_info(&mut self, info: RepeatInfo) {
+        self.timing = match info {
+            RepeatInfo::Repeat { rate, delay } => Some((
+                Duration::from_millis(delay.into()),
+                Duration::from_secs(1) / rate.get(),
+            )),
+            RepeatInfo::Disable => None,
+        };
+    }
+
+    /// Starts repeating a pressed key, `dispatch` is called with the text of the
+    /// key on every repeat. Any previously repeated key is stopped, as only the
+    /// last pressed key is repeated. Modifier keys are not repeated.
+    pub(crate) fn start<T: 'static>(
+        &mut self,
+        loop_handle: &LoopHandle<'static, T>,
+        event: &KeyEvent,
+        text: SharedString,
+        mut dispatch: impl FnMut(&mut T, SharedString) + 'static,
+    ) {
+        self.stop(loop_handle);
+        let Some((delay, interval)) = self.timing else {
+            return;
+        };
+        if event.keysym.is_modifier_key() {
+            return;
+        }
+        let deadline = self.deadline.clone();
+        let timer = Timer::from_duration(delay);
+        match loop_handle.insert_source(timer, move |_, _, state| {
+            dispatch(state, text.clone());
+            deadline.set(Some(Instant::now() + interval));
+            TimeoutAction::ToDuration(interval)
+        }) {
+            Ok(token) => {
+                self.current = Some((event.raw_code, token));
+                self.deadline.set(Some(Instant::now() + delay));
+            }
+            Err(err) => warn!("Couldn't start key repeat: {}", err.error),
+        }
+    }
+
+    /// Stops repeating the key with the given raw code, if it is being repeated.
+    pub(crate) fn stop_key<T>(&mut self, loop_handle: &LoopHandle<'static, T>, raw_code: u32) {
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|(current, _)| *current == raw_code)
+        {
+            self.stop(loop_handle);
+        }
+    }
+
+    /// Stops repeating the current key.
+    pub(crate) fn stop<T>(&mut self, loop_handle: &LoopHandle<'static, T>) {
+        if let Some((_, token)) = self.current.take() {
+            loop_handle.remove(token);
+        }
+        self.deadline.set(None);
+    }
+
+    /// Returns the time left till the next repeat.
+    pub(crate) fn next_timeout(&self) -> Option<Duration> {
+        self.deadline
+            .get()
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+}
 
 #[derive(Debug)]
 pub(crate) struct PointerState {
     pub(crate) pointer: Option<wl_pointer::WlPointer>,
     pub(crate) pointer_data: Option<PointerData>,
-    pub(crate) cursor_shape: CursorShapeManager,
-    pub(crate) current_wayland_cursor: MouseCursor,
+    /// Cursor shapes can only be set if the compositor supports cursor shape,
+    /// cursors are drawn from the xcursor theme otherwise.
+    pub(crate) cursor_shape: Option<CursorShapeManager>,
+    pub(crate) themed_cursor: Option<ThemedCursor>,
+    /// Cursor set since the last enter of the pointer, it has to be set again on
+    /// every enter.
+    pub(crate) current_wayland_cursor: Option<MouseCursor>,
     pub(crate) last_cursor_enter_serial: Option<u32>,
+    /// Serial and surface of the button press which is still held, drags can only
+    /// be started during it.
+    pub(crate) last_press: Option<(u32, WlSurface)>,
 }
 
 impl PointerState {
+    /// Records the enter of the pointer on a surface of the given scale, the cursor
+    /// is set again on the next update.
+    pub(crate) fn enter(&mut self, serial: u32, scale: f32) {
+        self.last_cursor_enter_serial = Some(serial);
+        self.current_wayland_cursor = None;
+        if let Some(themed_cursor) = self.themed_cursor.as_mut() {
+            // Buffer scales are integers, the cursor is scaled down for the rest.
+            themed_cursor.set_scale(scale.ceil() as u32);
+        }
+    }
+
+    /// Records the leave of the pointer, animated cursors are no longer animated.
+    pub(crate) fn leave(&mut self) {
+        self.current_wayland_cursor = None;
+        if let Some(themed_cursor) = self.themed_cursor.as_mut() {
+            themed_cursor.clear();
+        }
+    }
+
     /// Updates the cursor shape
     ///
     /// If the cursor is [MouseCursor::None], the cursor will be hidden
     ///
-    /// If the cursor is not [MouseCursor::None], the cursor will be set to the shape corresponding to the cursor
+    /// If the cursor is not [MouseCursor::None], the cursor will be set to the shape corresponding to the cursor.
+    /// The cursor is drawn from the xcursor theme if the compositor doesn't support cursor shape.
     ///
     /// The cursor is only updated when it doesn't match the current cursor
-    pub(crate) fn update_cursor(
-        &mut self,
-        mouse_cursor: MouseCursor,
-        queue: &QueueHandle<SpellWin>,
-    ) {
+    pub(crate) fn update_cursor<D>(&mut self, mouse_cursor: MouseCursor, queue: &QueueHandle<D>)
+    where
+        D: Dispatch<WpCursorShapeDeviceV1, GlobalData> + 'static,
+    {
         if let Some(serial) = self.last_cursor_enter_serial
             && let Some(pointer) = self.pointer.as_ref()
-            && mouse_cursor != self.current_wayland_cursor
+            && Some(mouse_cursor) != self.current_wayland_cursor
         {
             if mouse_cursor == MouseCursor::None {
                 pointer.set_cursor(serial, None, 0, 0);
-            } else {
-                self.cursor_shape
+                if let Some(themed_cursor) = self.themed_cursor.as_mut() {
+                    themed_cursor.clear();
+                }
+            } else if let Some(cursor_shape) = self.cursor_shape.as_ref() {
+                cursor_shape
                     .get_shape_device(pointer, queue)
                     .set_shape(serial, mouse_cursor_to_shape(mouse_cursor));
+            } else if let Some(themed_cursor) = self.themed_cursor.as_mut() {
+                themed_cursor.set(pointer, serial, mouse_cursor);
             }
-            self.current_wayland_cursor = mouse_cursor;
+            self.current_wayland_cursor = Some(mouse_cursor);
         }
     }
+
+    /// Shows the next frame of the cursor if it is animated.
+    pub(crate) fn animate_cursor(&mut self) {
+        if let Some(themed_cursor) = self.themed_cursor.as_mut() {
+            themed_cursor.animate();
+        }
+    }
+
+    /// Returns the time left till the next frame of an animated cursor.
+    pub(crate) fn cursor_timeout(&self) -> Option<Duration> {
+        self.themed_cursor
+            .as_ref()
+            .and_then(|themed_cursor| themed_cursor.next_timeout())
+    }
 }
 
 // Uses the official evdev pointer button codes defined in:
@@ -78,6 +238,34 @@ pub(super) fn map_pointer_button(button: u32) -> PointerEventButton {
     }
 }
 
+/// Returns the outputs known to the given state along with their info.
+pub(super) fn outputs_with_info(output_state: &OutputState) -> Vec<(WlOutput, OutputInfo)> {
+    output_state
+        .outputs()
+        .filter_map(|output| {
+            let info = output_state.info(&output)?;
+            Some((output, info))
+        })
+        .collect()
+}
+
+/// Damages the parts of the surface's buffer changed by the last draw of the
+/// adapter. Whole buffer is damaged if `full` is set or the changed parts are
+/// not known.
+pub(super) fn damage_surface(surface: &WlSurface, adapter: &SpellSkiaWinAdapter, full: bool) {
+    match adapter.damaged_rects() {
+        Some(rects) if !full => {
+            for (x, y, width, height) in rects {
+                surface.damage_buffer(x, y, width, height);
+            }
+        }
+        _ => {
+            let size = adapter.size.get();
+            surface.damage_buffer(0, 0, size.width as i32, size.height as i32);
+        }
+    }
+}
+
 /// Maps the slint cursor enum to the wayland cursor shape enum
 ///
 /// [MouseCursor::None] is handled internally by the program because there
@@ -116,73 +304,92 @@ pub(super) fn mouse_cursor_to_shape(cursor: MouseCursor) -> Shape {
     }
 }
 
+/// Keysyms which are sent to slint as its special keys, rest of the keys are sent
+/// as their text.
+const KEY_MAP: [(Keysym, Key); 58] = [
+    (Keysym::BackSpace, Key::Backspace),
+    (Keysym::Tab, Key::Tab),
+    (Keysym::Return, Key::Return),
+    (Keysym::Escape, Key::Escape),
+    (Keysym::BackTab, Key::Backtab),
+    (Keysym::Delete, Key::Delete),
+    (Keysym::Shift_L, Key::Shift),
+    (Keysym::Shift_R, Key::ShiftR),
+    (Keysym::Control_L, Key::Control),
+    (Keysym::Control_R, Key::ControlR),
+    (Keysym::Alt_L, Key::Alt),
+    (Keysym::Alt_R, Key::AltGr),
+    (Keysym::Caps_Lock, Key::CapsLock),
+    (Keysym::Meta_L, Key::Meta),
+    (Keysym::Meta_R, Key::MetaR),
+    (Keysym::space, Key::Space),
+    (Keysym::Up, Key::UpArrow),
+    (Keysym::uparrow, Key::UpArrow),
+    (Keysym::Down, Key::DownArrow),
+    (Keysym::downarrow, Key::DownArrow),
+    (Keysym::Left, Key::LeftArrow),
+    (Keysym::leftarrow, Key::LeftArrow),
+    (Keysym::Right, Key::RightArrow),
+    (Keysym::rightarrow, Key::RightArrow),
+    (Keysym::F1, Key::F1),
+    (Keysym::F2, Key::F2),
+    (Keysym::F3, Key::F3),
+    (Keysym::F4, Key::F4),
+    (Keysym::F5, Key::F5),
+    (Keysym::F6, Key::F6),
+    (Keysym::F7, Key::F7),
+    (Keysym::F8, Key::F8),
+    (Keysym::F9, Key::F9),
+    (Keysym::F10, Key::F10),
+    (Keysym::F11, Key::F11),
+    (Keysym::F12, Key::F12),
+    (Keysym::F13, Key::F13),
+    (Keysym::F14, Key::F14),
+    (Keysym::F15, Key::F15),
+    (Keysym::F16, Key::F16),
+    (Keysym::F17, Key::F17),
+    (Keysym::F18, Key::F18),
+    (Keysym::F19, Key::F19),
+    (Keysym::F20, Key::F20),
+    (Keysym::F21, Key::F21),
+    (Keysym::F22, Key::F22),
+    (Keysym::F23, Key::F23),
+    (Keysym::F24, Key::F24),
+    (Keysym::Insert, Key::Insert),
+    (Keysym::Home, Key::Home),
+    (Keysym::End, Key::End),
+    (Keysym::Page_Up, Key::PageUp),
+    (Keysym::Page_Down, Key::PageDown),
+    (Keysym::Scroll_Lock, Key::ScrollLock),
+    (Keysym::Pause, Key::Pause),
+    (Keysym::Sys_Req, Key::SysReq),
+    (Keysym::XF86_Stop, Key::Stop),
+    (Keysym::Menu, Key::Menu),
+];
+
 // Uses the /// Maps wayland specific keys into slint key events and then parsing the
 /// information as a SharedString.
 /// In case the matching is not present sharedstring is created from utf8
 /// representation of event.
 pub(super) fn get_string(event: KeyEvent) -> SharedString {
-    let mut key: Option<Key> = None;
-    match event.keysym {
-        Keysym::BackSpace => key = Some(Key::Backspace),
-        Keysym::Tab => key = Some(Key::Tab),
-        Keysym::Return => key = Some(Key::Return),
-        Keysym::Escape => key = Some(Key::Escape),
-        Keysym::BackTab => key = Some(Key::Backtab),
-        Keysym::Delete => key = Some(Key::Delete),
-        Keysym::Shift_L => key = Some(Key::Shift),
-        Keysym::Shift_R => key = Some(Key::ShiftR),
-        Keysym::Control_L => key = Some(Key::Control),
-        Keysym::Control_R => key = Some(Key::ControlR),
-        Keysym::Alt_L => key = Some(Key::Alt),
-        Keysym::Alt_R => key = Some(Key::AltGr),
-        Keysym::Caps_Lock => key = Some(Key::CapsLock),
-        Keysym::Meta_L => key = Some(Key::Meta),
-        Keysym::Meta_R => key = Some(Key::MetaR),
-        Keysym::space => key = Some(Key::Space),
-        Keysym::Up | Keysym::uparrow => key = Some(Key::UpArrow),
-        Keysym::Down | Keysym::downarrow => key = Some(Key::DownArrow),
-        Keysym::Left | Keysym::leftarrow => key = Some(Key::LeftArrow),
-        Keysym::Right | Keysym::rightarrow => key = Some(Key::RightArrow),
-        Keysym::F1 => key = Some(Key::F1),
-        Keysym::F2 => key = Some(Key::F2),
-        Keysym::F3 => key = Some(Key::F3),
-        Keysym::F4 => key = Some(Key::F4),
-        Keysym::F5 => key = Some(Key::F5),
-        Keysym::F6 => key = Some(Key::F6),
-        Keysym::F7 => key = Some(Key::F7),
-        Keysym::F8 => key = Some(Key::F8),
-        Keysym::F9 => key = Some(Key::F9),
-        Keysym::F10 => key = Some(Key::F10),
-        Keysym::F11 => key = Some(Key::F11),
-        Keysym::F12 => key = Some(Key::F12),
-        Keysym::F13 => key = Some(Key::F13),
-        Keysym::F14 => key = Some(Key::F14),
-        Keysym::F15 => key = Some(Key::F15),
-        Keysym::F16 => key = Some(Key::F16),
-        Keysym::F17 => key = Some(Key::F17),
-        Keysym::F18 => key = Some(Key::F18),
-        Keysym::F19 => key = Some(Key::F19),
-        Keysym::F20 => key = Some(Key::F20),
-        Keysym::F21 => key = Some(Key::F21),
-        Keysym::F22 => key = Some(Key::F22),
-        Keysym::F23 => key = Some(Key::F23),
-        Keysym::F24 => key = Some(Key::F24),
-        Keysym::Insert => key = Some(Key::Insert),
-        Keysym::Home => key = Some(Key::Home),
-        Keysym::End => key = Some(Key::End),
-        Keysym::Page_Up => key = Some(Key::PageUp),
-        Keysym::Page_Down => key = Some(Key::PageDown),
-        Keysym::Scroll_Lock => key = Some(Key::ScrollLock),
-        Keysym::Pause => key = Some(Key::Pause),
-        Keysym::Sys_Req => key = Some(Key::SysReq),
-        Keysym::XF86_Stop => key = Some(Key::Stop),
-        Keysym::Menu => key = Some(Key::Menu),
-        _ => {}
-    }
-
-    if let Some(key) = key {
-        key.into()
-    } else {
-        SharedString::from(event.utf8.unwrap_or_default())
+    match KEY_MAP.iter().find(|(keysym, _)| *keysym == event.keysym) {
+        Some((_, key)) => (*key).into(),
+        None => SharedString::from(event.utf8.unwrap_or_default()),
+    }
+}
+
+/// Returns the keysym of a key as sent by slint, either one of its special keys or
+/// a single character.
+pub(super) fn get_keysym(text: &str) -> Option<Keysym> {
+    if let Some((keysym, _)) = KEY_MAP
+        .iter()
+        .find(|(_, key)| SharedString::from(*key) == text)
+    {
+        return Some(*keysym);
+    }
+    let mut chars = text.chars();
+    match (chars.next(), chars.next()) {
+        (Some(ch), None) => Some(Keysym::from_char(ch)),
+        _ => None,
     }
 }
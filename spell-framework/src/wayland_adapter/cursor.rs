@@ -0,0 +1,296 @@
+// This module contains the cursors drawn by the widgets themselves from the user's
+// xcursor theme. They are only used when the compositor doesn't support cursor
+// shape, in which case the client has to attach the cursor image to a surface of
+// its own.
+use std::{
+    env,
+    time::{Duration, Instant},
+};
+
+use i_slint_core::items::MouseCursor;
+use smithay_client_toolkit::{
+    compositor::{CompositorState, SurfaceData},
+    reexports::client::{
+        Connection, Dispatch, QueueHandle,
+        protocol::{wl_pointer::WlPointer, wl_shm::WlShm, wl_surface::WlSurface},
+    },
+    shm::Shm,
+};
+use tracing::{info, warn};
+use wayland_cursor::CursorTheme;
+
+/// Size of the cursors when `XCURSOR_SIZE` is not set.
+const DEFAULT_CURSOR_SIZE: u32 = 24;
+
+/// Names of the arrow cursor, used for cursors missing from the theme.
+const DEFAULT_NAMES: &[&str] = &["default", "left_ptr"];
+
+/// Cursor drawn on a surface of the widget from the xcursor theme set by
+/// `XCURSOR_THEME` and `XCURSOR_SIZE`.
+pub(crate) struct ThemedCursor {
+    theme: CursorTheme,
+    conn: Connection,
+    shm: WlShm,
+    name: String,
+    size: u32,
+    /// Integer scale the theme is loaded at, so that the cursor is sharp on
+    /// HiDPI outputs.
+    scale: u32,
+    surface: WlSurface,
+    /// Pointer on which the cursor is set along with the serial of its enter.
+    pointer: Option<(WlPointer, u32)>,
+    /// Name of the shown cursor along with the time it was shown, frames of
+    /// animated cursors are picked from it.
+    current: Option<(&'static str, Instant)>,
+    /// Index of the frame attached to the surface.
+    frame: Option<usize>,
+    /// Time at which the next frame of an animated cursor is to be shown.
+    deadline: Option<Instant>,
+}
+
+impl std::fmt::Debug for ThemedCursor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ThemedCursor")
+            .field("scale", &self.scale)
+            .field("current", &self.current)
+            .field("frame", &self.frame)
+            .finish()
+    }
+}
+
+impl ThemedCursor {
+    /// Loads the cursor theme of the user. The cursor images themselves are only
+    /// read once they are shown.
+    pub(crate) fn load<D>(
+        conn: &Connection,
+        compositor: &CompositorState,
+        shm: &Shm,
+        qh: &QueueHandle<D>,
+    ) -> Option<Self>
+    where
+        D: Dispatch<WlSurface, SurfaceData> + 'static,
+    {
+        let name = env::var("XCURSOR_THEME").unwrap_or_else(|_| "default".to_string());
+        let size = env::var("XCURSOR_SIZE")
+            .ok()
+            .and_then(|size| size.parse().ok())
+            .unwrap_or(DEFAULT_CURSOR_SIZE);
+        match CursorTheme::load_from_name(conn, shm.wl_shm().clone(), &name, size) {
+            Ok(theme) => {
+                info!("Loaded cursor theme {} of size {}", name, size);
+                Some(ThemedCursor {
+                    theme,
+                    conn: conn.clone(),
+                    shm: shm.wl_shm().clone(),
+                    name,
+                    size,
+                    scale: 1,
+                    surface: compositor.create_surface(qh),
+                    pointer: None,
+                    current: None,
+                    frame: None,
+                    deadline: None,
+                })
+            }
+            Err(err) => {
+                warn!("Couldn't load cursor theme {}: {}", name, err);
+                None
+            }
+        }
+    }
+
+    /// Loads the theme at the given integer scale, which is the scale of the surface
+    /// the pointer is on rounded up. The shown cursor is drawn again at it.
+    pub(crate) fn set_scale(&mut self, scale: u32) {
+        let scale = scale.max(1);
+        if scale == self.scale {
+            return;
+        }
+        match CursorTheme::load_from_name(
+            &self.conn,
+            self.shm.clone(),
+            &self.name,
+            self.size * scale,
+        ) {
+            Ok(theme) => {
+                info!("Loaded cursor theme {} at scale {}", self.name, scale);
+                self.theme = theme;
+                self.scale = scale;
+                self.surface.set_buffer_scale(scale as i32);
+                // Frames of the old theme are of no use.
+                self.frame = None;
+                self.show_frame();
+            }
+            Err(err) => warn!(
+                "Couldn't load cursor theme {} at scale {}: {}",
+                self.name, scale, err
+            ),
+        }
+    }
+
+    /// Shows the cursor on the pointer which entered with the given serial. The
+    /// arrow cursor is shown if the theme doesn't have the cursor.
+    pub(crate) fn set(&mut self, pointer: &WlPointer, serial: u32, cursor: MouseCursor) {
+        let name = cursor_names(cursor)
+            .iter()
+            .chain(DEFAULT_NAMES)
+            .copied()
+            .find(|name| self.theme.get_cursor(name).is_some());
+        let Some(name) = name else {
+            warn!("Cursor theme has no cursor for {:?}", cursor);
+            return;
+        };
+        self.pointer = Some((pointer.clone(), serial));
+        self.current = Some((name, Instant::now()));
+        self.frame = None;
+        self.show_frame();
+    }
+
+    /// Stops showing the cursor, it is no longer animated.
+    pub(crate) fn clear(&mut self) {
+        self.pointer = None;
+        self.current = None;
+        self.frame = None;
+        self.deadline = None;
+    }
+
+    /// Shows the next frame of an animated cursor if it is due.
+    pub(crate) fn animate(&mut self) {
+        if self
+            .deadline
+            .is_some_and(|deadline| deadline <= Instant::now())
+        {
+            self.show_frame();
+        }
+    }
+
+    /// Returns the time left till the next frame of an animated cursor.
+    pub(crate) fn next_timeout(&self) -> Option<Duration> {
+        self.deadline
+            .map(|deadline| deadline.saturating_duration_since(Instant::now()))
+    }
+
+    fn show_frame(&mut self) {
+        let (Some((name, shown_at)), Some((pointer, serial))) =
+            (self.current, self.pointer.as_ref())
+        else {
+            return;
+        };
+        let Some(cursor) = self.theme.get_cursor(name) else {
+            return;
+        };
+        let elapsed = u32::try_from(shown_at.elapsed().as_millis()).unwrap_or(u32::MAX);
+        let delays: Vec<u32> = (0..cursor.image_count())
+            .map(|index| cursor[index].delay())
+            .collect();
+        let (index, time_left) = frame_at(&delays, elapsed);
+        self.deadline =
+            time_left.map(|time_left| Instant::now() + Duration::from_millis(time_left.into()));
+        if self.frame == Some(index) {
+            return;
+        }
+        self.frame = Some(index);
+
+        let image = &cursor[index];
+        let (width, height) = image.dimensions();
+        let (hotspot_x, hotspot_y) = image.hotspot();
+        // Frames can have different hotspots, so the cursor is set for every one.
+        // Hotspot is in the surface coordinates, which are scaled down.
+        pointer.set_cursor(
+            *serial,
+            Some(&self.surface),
+            (hotspot_x / self.scale) as i32,
+            (hotspot_y / self.scale) as i32,
+        );
+        self.surface.attach(Some(&**image), 0, 0);
+        self.surface
+            .damage_buffer(0, 0, width as i32, height as i32);
+        self.surface.commit();
+    }
+}
+
+/// Returns the frame of a cursor with frames of the given delays to be shown
+/// `elapsed` milliseconds after it was first shown, along with the time left till
+/// the next frame. Time left is `None` for cursors which are not animated.
+fn frame_at(delays: &[u32], elapsed: u32) -> (usize, Option<u32>) {
+    let total: u32 = delays.iter().sum();
+    if delays.len() < 2 || total == 0 {
+        return (0, None);
+    }
+    let mut left = elapsed % total;
+    for (index, delay) in delays.iter().copied().enumerate() {
+        if left < delay {
+            return (index, Some(delay - left));
+        }
+        left -= delay;
+    }
+    (0, None)
+}
+
+/// Maps the cursor to its names in xcursor themes. Names of the CSS cursors come
+/// first, followed by the older X11 names used by some themes.
+fn cursor_names(cursor: MouseCursor) -> &'static [&'static str] {
+    match cursor {
+        MouseCursor::Default => DEFAULT_NAMES,
+        MouseCursor::Help => &["help", "question_arrow", "whats_this", "left_ptr_help"],
+        MouseCursor::Pointer => &["pointer", "hand2", "hand1", "pointing_hand"],
+        MouseCursor::Progress => &["progress", "left_ptr_watch", "half-busy"],
+        MouseCursor::Wait => &["wait", "watch"],
+        MouseCursor::Crosshair => &["crosshair", "cross", "tcross"],
+        MouseCursor::Text => &["text", "xterm", "ibeam"],
+        MouseCursor::Alias => &["alias", "dnd-link", "link"],
+        MouseCursor::Copy => &["copy", "dnd-copy"],
+        MouseCursor::Move => &["move", "fleur", "all-scroll", "dnd-move"],
+        MouseCursor::NoDrop => &["no-drop", "dnd-no-drop", "forbidden"],
+        MouseCursor::NotAllowed => &["not-allowed", "crossed_circle", "forbidden"],
+        MouseCursor::Grab => &["grab", "openhand", "hand1"],
+        MouseCursor::Grabbing => &["grabbing", "closedhand", "fleur"],
+        MouseCursor::ColResize => &["col-resize", "sb_h_double_arrow", "split_h"],
+        MouseCursor::RowResize => &["row-resize", "sb_v_double_arrow", "split_v"],
+        MouseCursor::NResize => &["n-resize", "top_side"],
+        MouseCursor::EResize => &["e-resize", "right_side"],
+        MouseCursor::SResize => &["s-resize", "bottom_side"],
+        MouseCursor::WResize => &["w-resize", "left_side"],
+        MouseCursor::NeResize => &["ne-resize", "top_right_corner"],
+        MouseCursor::NwResize => &["nw-resize", "top_left_corner"],
+        MouseCursor::SeResize => &["se-resize", "bottom_right_corner"],
+        MouseCursor::SwResize => &["sw-resize", "bottom_left_corner"],
+        MouseCursor::EwResize => &["ew-resize", "sb_h_double_arrow", "h_double_arrow"],
+        MouseCursor::NsResize => &["ns-resize", "sb_v_double_arrow", "v_double_arrow"],
+        MouseCursor::NeswResize => &["nesw-resize", "fd_double_arrow", "size_bdiag"],
+        MouseCursor::NwseResize => &["nwse-resize", "bd_double_arrow", "size_fdiag"],
+        _ => DEFAULT_NAMES,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::frame_at;
+
+    #[test]
+    fn single_frame_is_not_animated() {
+        assert_eq!(frame_at(&[50], 0), (0, None));
+        assert_eq!(frame_at(&[50], 120), (0, None));
+    }
+
+    #[test]
+    fn zero_total_delay_is_not_animated() {
+        assert_eq!(frame_at(&[0, 0, 0], 30), (0, None));
+    }
+
+    #[test]
+    fn frames_follow_their_delays() {
+        let delays = [100, 50, 200];
+        assert_eq!(frame_at(&delays, 0), (0, Some(100)));
+        assert_eq!(frame_at(&delays, 99), (0, Some(1)));
+        assert_eq!(frame_at(&delays, 100), (1, Some(50)));
+        assert_eq!(frame_at(&delays, 170), (2, Some(180)));
+    }
+
+    #[test]
+    fn elapsed_time_wraps_past_the_total() {
+        let delays = [100, 50, 200];
+        assert_eq!(frame_at(&delays, 350), (0, Some(100)));
+        assert_eq!(frame_at(&delays, 350 * 3 + 120), (1, Some(30)));
+    }
+}
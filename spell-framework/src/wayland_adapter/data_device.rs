@@ -0,0 +1,194 @@
+// This module contains the transfer of data through wl_data_device, used for drag
+// and drop and the clipboard. Data is read from and written to the pipes of the
+// other client inside the event loop, so that large transfers don't block the widget.
+use std::{
+    fs::File,
+    io::{ErrorKind, Read, Write},
+    os::fd::OwnedFd,
+    rc::Rc,
+};
+
+use smithay_client_toolkit::{
+    data_device_manager::data_source::DragSource,
+    reexports::calloop::{Interest, LoopHandle, Mode, PostAction, generic::Generic},
+};
+use tracing::warn;
+
+/// Size of the chunks written to a pipe, writes of at most this size don't block
+/// once the pipe is writable.
+const PIPE_CHUNK: usize = 4096;
+
+/// Events of a drag and drop made over a widget, delivered to the callback set with
+/// [`SpellWin::on_drop`](crate::wayland_adapter::SpellWin::on_drop). Positions are
+/// in logical pixels of the surface, the same as the ones of slint.
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum DropEvent {
+    /// A drag entered the widget.
+    Enter {
+        /// MIME types offered by the source of the drag.
+        mime_types: Vec<String>,
+        /// Type chosen out of the ones accepted by the widget, the drag is rejected
+        /// if it is `None`.
+        accepted: Option<String>,
+        /// Horizontal position of the drag.
+        x: f64,
+        /// Vertical position of the drag.
+        y: f64,
+    },
+    /// The drag moved over the widget.
+    Motion {
+        /// Horizontal position of the drag.
+        x: f64,
+        /// Vertical position of the drag.
+        y: f64,
+    },
+    /// The drag left the widget without a drop.
+    Leave,
+    /// Data was dropped on the widget.
+    Drop {
+        /// Accepted MIME type in which the data is received.
+        mime_type: String,
+        /// Data sent by the source of the drag.
+        data: Vec<u8>,
+        /// Horizontal position of the drop.
+        x: f64,
+        /// Vertical position of the drop.
+        y: f64,
+    },
+}
+
+/// Data offered by a drag started from a widget or set as the clipboard. The same
+/// data is usually offered in multiple MIME types (like `text/uri-list` and
+/// `text/plain`) and the receiving client picks one of them.
+#[derive(Debug, Clone, Default)]
+pub struct MimeData {
+    offers: Vec<(String, Rc<[u8]>)>,
+}
+
+impl MimeData {
+    /// Creates an empty offer, types are added with [`MimeData::with`].
+    pub fn new() -> Self {
+        MimeData::default()
+    }
+
+    /// Offers the data in the given MIME type.
+    pub fn with(mut self, mime_type: impl Into<String>, data: impl Into<Vec<u8>>) -> Self {
+        self.offers.push((mime_type.into(), data.into().into()));
+        self
+    }
+
+    pub(crate) fn mime_types(&self) -> impl Iterator<Item = &str> {
+        self.offers.iter().map(|(mime_type, _)| mime_type.as_str())
+    }
+
+    pub(crate) fn get(&self, mime_type: &str) -> Option<Rc<[u8]>> {
+        self.offers
+            .iter()
+            .find(|(offered, _)| offered == mime_type)
+            .map(|(_, data)| data.clone())
+    }
+}
+
+/// State of drag and drop for a widget, both as a target and as a source.
+#[derive(Default)]
+pub(crate) struct DndState {
+    /// MIME types accepted on drop in the order of preference.
+    pub(crate) accepted_types: Vec<String>,
+    pub(crate) callback: Option<Box<dyn FnMut(DropEvent)>>,
+    /// MIME type accepted for the current drag over the widget.
+    pub(crate) accepted: Option<String>,
+    /// Set on drop, the compositor sends a leave after it which is not forwarded.
+    pub(crate) dropped: bool,
+    /// Drag started from the widget along with its data.
+    pub(crate) source: Option<(DragSource, MimeData)>,
+}
+
+impl DndState {
+    /// Returns the first accepted MIME type present in the offered ones.
+    pub(crate) fn negotiate(&self, offered: &[String]) -> Option<String> {
+        self.accepted_types
+            .iter()
+            .find(|mime_type| offered.contains(mime_type))
+            .cloned()
+    }
+
+    pub(crate) fn emit(&mut self, event: DropEvent) {
+        if let Some(callback) = self.callback.as_mut() {
+            callback(event);
+        }
+    }
+}
+
+/// Reads the pipe till the other end is closed, then calls `on_done` with the data.
+pub(crate) fn read_pipe<D: 'static>(
+    loop_handle: &LoopHandle<'static, D>,
+    pipe: impl Into<OwnedFd>,
+    on_done: impl FnOnce(&mut D, std::io::Result<Vec<u8>>) + 'static,
+) {
+    let file = File::from(pipe.into());
+    let mut data = Vec::new();
+    let mut on_done = Some(on_done);
+    let result = loop_handle.insert_source(
+        Generic::new(file, Interest::READ, Mode::Level),
+        move |_, file, state| {
+            let mut buffer = [0; PIPE_CHUNK];
+            // SAFETY: The file is neither dropped nor replaced here.
+            let result = match unsafe { file.get_mut() }.read(&mut buffer) {
+                Ok(0) => Ok(std::mem::take(&mut data)),
+                Ok(len) => {
+                    data.extend_from_slice(&buffer[..len]);
+                    return Ok(PostAction::Continue);
+                }
+                Err(err) if err.kind() == ErrorKind::Interrupted => {
+                    return Ok(PostAction::Continue);
+                }
+                Err(err) => Err(err),
+            };
+            if let Some(on_done) = on_done.take() {
+                on_done(state, result);
+            }
+            Ok(PostAction::Remove)
+        },
+    );
+    if let Err(err) = result {
+        warn!("Couldn't read from the pipe: {}", err);
+    }
+}
+
+/// Writes the data to the pipe in chunks whenever it is writable, closing it at
+/// the end.
+pub(crate) fn write_pipe<D: 'static>(
+    loop_handle: &LoopHandle<'static, D>,
+    pipe: impl Into<OwnedFd>,
+    data: Rc<[u8]>,
+) {
+    let file = File::from(pipe.into());
+    let mut written = 0;
+    let result = loop_handle.insert_source(
+        Generic::new(file, Interest::WRITE, Mode::Level),
+        move |_, file, _| {
+            let end = (written + PIPE_CHUNK).min(data.len());
+            // SAFETY: The file is neither dropped nor replaced here.
+            match unsafe { file.get_mut() }.write(&data[written..end]) {
+                Ok(len) => written += len,
+                Err(err) if err.kind() == ErrorKind::Interrupted => {}
+                Err(err) => {
+                    // The other client closing the pipe early is not an error.
+                    if err.kind() != ErrorKind::BrokenPipe {
+                        warn!("Couldn't write to the pipe: {}", err);
+                    }
+                    return Ok(PostAction::Remove);
+                }
+            }
+            if written < data.len() {
+                Ok(PostAction::Continue)
+            } else {
+                Ok(PostAction::Remove)
+            }
+        },
+    );
+    if let Err(err) = result {
+        warn!("Couldn't write to the pipe: {}", err);
+    }
+}
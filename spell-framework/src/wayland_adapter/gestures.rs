@@ -0,0 +1,684 @@
+// This module contains the tracking of touch points, which are sent to slint as
+// pointer events, and the recognition of multi-finger gestures from them. Gestures
+// of touchpads are received from the compositor through pointer-gestures protocol.
+use std::collections::HashMap;
+
+use slint::{
+    LogicalPosition,
+    platform::{PointerEventButton, WindowEvent},
+};
+use smithay_client_toolkit::globals::GlobalData;
+use smithay_client_toolkit::reexports::{
+    client::{
+        Connection, Dispatch, Proxy, QueueHandle,
+        globals::{BindError, GlobalList},
+        protocol::{wl_pointer::WlPointer, wl_surface::WlSurface},
+    },
+    protocols::wp::pointer_gestures::zv1::client::{
+        zwp_pointer_gesture_hold_v1::{self, ZwpPointerGestureHoldV1},
+        zwp_pointer_gesture_pinch_v1::{self, ZwpPointerGesturePinchV1},
+        zwp_pointer_gesture_swipe_v1::{self, ZwpPointerGestureSwipeV1},
+        zwp_pointer_gestures_v1::ZwpPointerGesturesV1,
+    },
+};
+
+/// Distance in logical pixels the fingers need to move together before a swipe
+/// is recognised.
+const SWIPE_THRESHOLD: f64 = 16.0;
+/// Relative change in the distance between the fingers needed before a pinch is
+/// recognised.
+const PINCH_THRESHOLD: f64 = 0.1;
+
+/// Multi-finger gestures made on a widget, either on a touchscreen or a touchpad.
+/// Every gesture is made of a begin, a number of updates and an end event. They
+/// are delivered to the callback set with
+/// [`SpellWin::on_gesture`](crate::wayland_adapter::SpellWin::on_gesture).
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[non_exhaustive]
+pub enum Gesture {
+    /// Fingers started moving together in a direction.
+    SwipeBegin {
+        /// Number of fingers making the gesture.
+        fingers: u32,
+    },
+    /// Fingers moved since the last update.
+    SwipeUpdate {
+        /// Horizontal movement in logical pixels.
+        dx: f64,
+        /// Vertical movement in logical pixels.
+        dy: f64,
+    },
+    /// Fingers are lifted.
+    SwipeEnd {
+        /// Set if the gesture was interrupted.
+        cancelled: bool,
+    },
+    /// Fingers started moving closer or apart.
+    PinchBegin {
+        /// Number of fingers making the gesture.
+        fingers: u32,
+    },
+    /// Fingers moved since the last update.
+    PinchUpdate {
+        /// Horizontal movement of the center of the fingers in logical pixels.
+        dx: f64,
+        /// Vertical movement of the center of the fingers in logical pixels.
+        dy: f64,
+        /// Distance between the fingers relative to the start of the gesture.
+        scale: f64,
+        /// Angle in degrees turned since the last update.
+        rotation: f64,
+    },
+    /// Fingers are lifted.
+    PinchEnd {
+        /// Set if the gesture was interrupted.
+        cancelled: bool,
+    },
+    /// Fingers are placed on the touchpad without moving. Only sent for touchpads.
+    HoldBegin {
+        /// Number of fingers making the gesture.
+        fingers: u32,
+    },
+    /// Fingers are lifted.
+    HoldEnd {
+        /// Set if the fingers moved or the gesture was interrupted (like by a
+        /// swipe starting).
+        cancelled: bool,
+    },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum GestureKind {
+    Swipe,
+    Pinch,
+}
+
+/// State of a multi-finger touch, from which a gesture is recognised.
+#[derive(Debug)]
+struct TouchGesture {
+    fingers: u32,
+    kind: Option<GestureKind>,
+    start_center: (f64, f64),
+    start_spread: f64,
+    last_center: (f64, f64),
+    last_angle: f64,
+}
+
+/// Touch points on the surfaces of a widget. The first point put down acts as the
+/// pointer for slint, while two or more points are tracked as a gesture.
+#[derive(Debug, Default)]
+pub(crate) struct TouchTracker {
+    points: HashMap<i32, (WlSurface, (f64, f64))>,
+    /// Id of the touch point sent to slint as the pointer.
+    primary: Option<i32>,
+    gesture: Option<TouchGesture>,
+}
+
+/// Events resulting from a touch event, pointer events are to be dispatched to
+/// the adapter of their surface.
+#[derive(Debug, Default)]
+pub(crate) struct TouchOutput {
+    pub pointer: Vec<(WlSurface, WindowEvent)>,
+    pub gestures: Vec<Gesture>,
+}
+
+fn logical_position(position: (f64, f64)) -> LogicalPosition {
+    LogicalPosition {
+        x: position.0 as f32,
+        y: position.1 as f32,
+    }
+}
+
+impl TouchTracker {
+    pub(crate) fn down(
+        &mut self,
+        id: i32,
+        surface: WlSurface,
+        position: (f64, f64),
+    ) -> TouchOutput {
+        let mut output = TouchOutput::default();
+        self.points.insert(id, (surface.clone(), position));
+        if self.points.len() == 1 {
+            self.primary = Some(id);
+            output.pointer.push((
+                surface.clone(),
+                WindowEvent::PointerMoved {
+                    position: logical_position(position),
+                },
+            ));
+            output.pointer.push((
+                surface,
+                WindowEvent::PointerPressed {
+                    position: logical_position(position),
+                    button: PointerEventButton::Left,
+                },
+            ));
+            return output;
+        }
+
+        // A second finger turns the touch into a gesture, the press of the first
+        // finger is dropped without a click.
+        if let Some(primary) = self.primary.take()
+            && let Some((surface, _)) = self.points.get(&primary)
+        {
+            output
+                .pointer
+                .push((surface.clone(), WindowEvent::PointerExited));
+        }
+        let fingers = self.points.len() as u32;
+        // Fingers added during a recognised gesture don't change it, before that
+        // the gesture is started again with them.
+        if !self
+            .gesture
+            .as_ref()
+            .is_some_and(|gesture| gesture.kind.is_some())
+        {
+            let center = self.center();
+            self.gesture = Some(TouchGesture {
+                fingers,
+                kind: None,
+                start_center: center,
+                start_spread: self.spread(center),
+                last_center: center,
+                last_angle: self.angle(),
+            });
+        }
+        output
+    }
+
+    pub(crate) fn motion(&mut self, id: i32, position: (f64, f64)) -> TouchOutput {
+        let mut output = TouchOutput::default();
+        let Some((surface, point)) = self.points.get_mut(&id) else {
+            return output;
+        };
+        *point = position;
+        if self.primary == Some(id) {
+            output.pointer.push((
+                surface.clone(),
+                WindowEvent::PointerMoved {
+                    position: logical_position(position),
+                },
+            ));
+            return output;
+        }
+
+        let center = self.center();
+        let spread = self.spread(center);
+        let angle = self.angle();
+        let Some(gesture) = self.gesture.as_mut() else {
+            return output;
+        };
+        let scale = if gesture.start_spread > 0.0 {
+            spread / gesture.start_spread
+        } else {
+            1.0
+        };
+        if gesture.kind.is_none() {
+            let moved = ((center.0 - gesture.start_center.0).powi(2)
+                + (center.1 - gesture.start_center.1).powi(2))
+            .sqrt();
+            gesture.kind = if (scale - 1.0).abs() > PINCH_THRESHOLD {
+                output.gestures.push(Gesture::PinchBegin {
+                    fingers: gesture.fingers,
+                });
+                Some(GestureKind::Pinch)
+            } else if moved > SWIPE_THRESHOLD {
+                output.gestures.push(Gesture::SwipeBegin {
+                    fingers: gesture.fingers,
+                });
+                Some(GestureKind::Swipe)
+            } else {
+                None
+            };
+        }
+        let (dx, dy) = (
+            center.0 - gesture.last_center.0,
+            center.1 - gesture.last_center.1,
+        );
+        match gesture.kind {
+            Some(GestureKind::Swipe) => output.gestures.push(Gesture::SwipeUpdate { dx, dy }),
+            Some(GestureKind::Pinch) => {
+                // Angles are kept within a half turn of each other.
+                let mut rotation = angle - gesture.last_angle;
+                if rotation > 180.0 {
+                    rotation -= 360.0;
+                } else if rotation < -180.0 {
+                    rotation += 360.0;
+                }
+                output.gestures.push(Gesture::PinchUpdate {
+                    dx,
+                    dy,
+                    scale,
+                    rotation,
+                });
+            }
+            None => {}
+        }
+        gesture.last_center = center;
+        gesture.last_angle = angle;
+        output
+    }
+
+    pub(crate) fn up(&mut self, id: i32) -> TouchOutput {
+        let mut output = TouchOutput::default();
+        let Some((surface, position)) = self.points.remove(&id) else {
+            return output;
+        };
+        if self.primary == Some(id) {
+            self.primary = None;
+            output.pointer.push((
+                surface.clone(),
+                WindowEvent::PointerReleased {
+                    position: logical_position(position),
+                    button: PointerEventButton::Left,
+                },
+            ));
+            // Unlike a pointer, a finger doesn't hover over the surface.
+            output.pointer.push((surface, WindowEvent::PointerExited));
+        }
+        if self.points.len() < 2
+            && let Some(gesture) = self.gesture.take()
+        {
+            output.gestures.extend(gesture.end(false));
+        }
+        output
+    }
+
+    /// Called when the compositor takes over the touch sequence, every touch point
+    /// is dropped.
+    pub(crate) fn cancel(&mut self) -> TouchOutput {
+        let mut output = TouchOutput::default();
+        if let Some(primary) = self.primary.take()
+            && let Some((surface, _)) = self.points.get(&primary)
+        {
+            output
+                .pointer
+                .push((surface.clone(), WindowEvent::PointerExited));
+        }
+        if let Some(gesture) = self.gesture.take() {
+            output.gestures.extend(gesture.end(true));
+        }
+        self.points.clear();
+        output
+    }
+
+    fn center(&self) -> (f64, f64) {
+        let count = self.points.len().max(1) as f64;
+        let (x, y) = self
+            .points
+            .values()
+            .fold((0.0, 0.0), |(x, y), (_, point)| (x + point.0, y + point.1));
+        (x / count, y / count)
+    }
+
+    /// Average distance of the points from their center.
+    fn spread(&self, center: (f64, f64)) -> f64 {
+        let count = self.points.len().max(1) as f64;
+        self.points
+            .values()
+            .map(|(_, point)| ((point.0 - center.0).powi(2) + (point.1 - center.1).powi(2)).sqrt())
+            .sum::<f64>()
+            / count
+    }
+
+    /// Angle in degrees of the line between the two earliest touch points.
+    fn angle(&self) -> f64 {
+        let mut ids: Vec<&i32> = self.points.keys().collect();
+        ids.sort();
+        match (ids.first(), ids.get(1)) {
+            (Some(first), Some(second)) => {
+                let a = self.points[*first].1;
+                let b = self.points[*second].1;
+                (b.1 - a.1).atan2(b.0 - a.0).to_degrees()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+impl TouchGesture {
+    fn end(self, cancelled: bool) -> Option<Gesture> {
+        match self.kind {
+            Some(GestureKind::Swipe) => Some(Gesture::SwipeEnd { cancelled }),
+            Some(GestureKind::Pinch) => Some(Gesture::PinchEnd { cancelled }),
+            None => None,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct PointerGesturesState {
+    manager: ZwpPointerGesturesV1,
+}
+
+/// Owned gesture objects of a pointer, when this is dropped, the underlying
+/// interfaces are destroyed and gestures are no longer received.
+#[derive(Debug)]
+pub struct PointerGestures {
+    swipe: ZwpPointerGestureSwipeV1,
+    pinch: ZwpPointerGesturePinchV1,
+    /// Hold gestures are only available from the third version of the protocol.
+    hold: Option<ZwpPointerGestureHoldV1>,
+}
+
+impl PointerGesturesState {
+    pub(crate) fn bind<State>(
+        globals: &GlobalList,
+        queue_handle: &QueueHandle<State>,
+    ) -> Result<Self, BindError>
+    where
+        State: Dispatch<ZwpPointerGesturesV1, GlobalData, State> + PointerGestureHandler + 'static,
+    {
+        let manager = globals.bind(queue_handle, 1..=3, GlobalData)?;
+        Ok(PointerGesturesState { manager })
+    }
+
+    pub(crate) fn get_gestures<State>(
+        &self,
+        pointer: &WlPointer,
+        queue_handle: &QueueHandle<State>,
+    ) -> PointerGestures
+    where
+        State: Dispatch<ZwpPointerGestureSwipeV1, GlobalData>
+            + Dispatch<ZwpPointerGesturePinchV1, GlobalData>
+            + Dispatch<ZwpPointerGestureHoldV1, GlobalData>
+            + 'static,
+    {
+        PointerGestures {
+            swipe: self
+                .manager
+                .get_swipe_gesture(pointer, queue_handle, GlobalData),
+            pinch: self
+                .manager
+                .get_pinch_gesture(pointer, queue_handle, GlobalData),
+            hold: (self.manager.version() >= 3).then(|| {
+                self.manager
+                    .get_hold_gesture(pointer, queue_handle, GlobalData)
+            }),
+        }
+    }
+}
+
+impl Drop for PointerGestures {
+    fn drop(&mut self) {
+        self.swipe.destroy();
+        self.pinch.destroy();
+        if let Some(hold) = &self.hold {
+            hold.destroy();
+        }
+    }
+}
+
+impl<D> Dispatch<ZwpPointerGesturesV1, GlobalData, D> for PointerGesturesState
+where
+    D: Dispatch<ZwpPointerGesturesV1, GlobalData> + PointerGestureHandler + 'static,
+{
+    fn event(
+        _: &mut D,
+        _: &ZwpPointerGesturesV1,
+        _: <ZwpPointerGesturesV1 as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        unreachable!("ZwpPointerGesturesV1 has no events")
+    }
+}
+
+impl<D> Dispatch<ZwpPointerGestureSwipeV1, GlobalData, D> for PointerGesturesState
+where
+    D: Dispatch<ZwpPointerGestureSwipeV1, GlobalData> + PointerGestureHandler + 'static,
+{
+    fn event(
+        state: &mut D,
+        _: &ZwpPointerGestureSwipeV1,
+        event: <ZwpPointerGestureSwipeV1 as Proxy>::Event,
+        _: &GlobalData,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+    ) {
+        let gesture = match event {
+            zwp_pointer_gesture_swipe_v1::Event::Begin { fingers, .. } => {
+                Gesture::SwipeBegin { fingers }
+            }
+            zwp_pointer_gesture_swipe_v1::Event::Update { dx, dy, .. } => {
+                Gesture::SwipeUpdate { dx, dy }
+            }
+            zwp_pointer_gesture_swipe_v1::Event::End { cancelled, .. } => Gesture::SwipeEnd {
+                cancelled: cancelled != 0,
+            },
+            _ => unreachable!("ZwpPointerGestureSwipeV1 has no other events"),
+        };
+        state.pointer_gesture(conn, qh, gesture);
+    }
+}
+
+impl<D> Dispatch<ZwpPointerGesturePinchV1, GlobalData, D> for PointerGesturesState
+where
+    D: Dispatch<ZwpPointerGesturePinchV1, GlobalData> + PointerGestureHandler + 'static,
+{
+    fn event(
+        state: &mut D,
+        _: &ZwpPointerGesturePinchV1,
+        event: <ZwpPointerGesturePinchV1 as Proxy>::Event,
+        _: &GlobalData,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+    ) {
+        let gesture = match event {
+            zwp_pointer_gesture_pinch_v1::Event::Begin { fingers, .. } => {
+                Gesture::PinchBegin { fingers }
+            }
+            zwp_pointer_gesture_pinch_v1::Event::Update {
+                dx,
+                dy,
+                scale,
+                rotation,
+                ..
+            } => Gesture::PinchUpdate {
+                dx,
+                dy,
+                scale,
+                rotation,
+            },
+            zwp_pointer_gesture_pinch_v1::Event::End { cancelled, .. } => Gesture::PinchEnd {
+                cancelled: cancelled != 0,
+            },
+            _ => unreachable!("ZwpPointerGesturePinchV1 has no other events"),
+        };
+        state.pointer_gesture(conn, qh, gesture);
+    }
+}
+
+impl<D> Dispatch<ZwpPointerGestureHoldV1, GlobalData, D> for PointerGesturesState
+where
+    D: Dispatch<ZwpPointerGestureHoldV1, GlobalData> + PointerGestureHandler + 'static,
+{
+    fn event(
+        state: &mut D,
+        _: &ZwpPointerGestureHoldV1,
+        event: <ZwpPointerGestureHoldV1 as Proxy>::Event,
+        _: &GlobalData,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+    ) {
+        let gesture = match event {
+            zwp_pointer_gesture_hold_v1::Event::Begin { fingers, .. } => {
+                Gesture::HoldBegin { fingers }
+            }
+            zwp_pointer_gesture_hold_v1::Event::End { cancelled, .. } => Gesture::HoldEnd {
+                cancelled: cancelled != 0,
+            },
+            _ => unreachable!("ZwpPointerGestureHoldV1 has no other events"),
+        };
+        state.pointer_gesture(conn, qh, gesture);
+    }
+}
+
+pub trait PointerGestureHandler: Sized {
+    /// Called with the gestures made on the touchpad while the pointer is over a
+    /// surface of the widget.
+    fn pointer_gesture(&mut self, conn: &Connection, qh: &QueueHandle<Self>, gesture: Gesture);
+}
+
+macro_rules! delegate_pointer_gestures {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay_client_toolkit::reexports::protocols::wp::pointer_gestures::zv1::client::zwp_pointer_gestures_v1::ZwpPointerGesturesV1: smithay_client_toolkit::globals::GlobalData
+        ] => $crate::wayland_adapter::gestures::PointerGesturesState);
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay_client_toolkit::reexports::protocols::wp::pointer_gestures::zv1::client::zwp_pointer_gesture_swipe_v1::ZwpPointerGestureSwipeV1: smithay_client_toolkit::globals::GlobalData
+        ] => $crate::wayland_adapter::gestures::PointerGesturesState);
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay_client_toolkit::reexports::protocols::wp::pointer_gestures::zv1::client::zwp_pointer_gesture_pinch_v1::ZwpPointerGesturePinchV1: smithay_client_toolkit::globals::GlobalData
+        ] => $crate::wayland_adapter::gestures::PointerGesturesState);
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay_client_toolkit::reexports::protocols::wp::pointer_gestures::zv1::client::zwp_pointer_gesture_hold_v1::ZwpPointerGestureHoldV1: smithay_client_toolkit::globals::GlobalData
+        ] => $crate::wayland_adapter::gestures::PointerGesturesState);
+    };
+}
+pub(crate) use delegate_pointer_gestures;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use smithay_client_toolkit::reexports::client::backend::Backend;
+    use std::os::unix::net::UnixStream;
+
+    /// Surface of a connection to nowhere, the tracker only stores it.
+    fn surface() -> WlSurface {
+        let (stream, _) = UnixStream::pair().unwrap();
+        let backend = Backend::connect(stream).unwrap();
+        WlSurface::inert(backend.downgrade())
+    }
+
+    fn gestures(outputs: impl IntoIterator<Item = TouchOutput>) -> Vec<Gesture> {
+        outputs
+            .into_iter()
+            .flat_map(|output| output.gestures)
+            .collect()
+    }
+
+    /// Moves two fingers put down at (0, 0) and (100, 0) to the right in small
+    /// steps, so that the distance between them stays about the same.
+    fn swipe(tracker: &mut TouchTracker) -> Vec<Gesture> {
+        let surface = surface();
+        tracker.down(0, surface.clone(), (0.0, 0.0));
+        tracker.down(1, surface, (100.0, 0.0));
+        let mut outputs = Vec::new();
+        for step in 1..=6 {
+            let x = 5.0 * step as f64;
+            outputs.push(tracker.motion(0, (x, 0.0)));
+            outputs.push(tracker.motion(1, (100.0 + x, 0.0)));
+        }
+        gestures(outputs)
+    }
+
+    #[test]
+    fn single_finger_is_a_click() {
+        let mut tracker = TouchTracker::default();
+        let down = tracker.down(0, surface(), (10.0, 20.0));
+        let events: Vec<_> = down.pointer.iter().map(|(_, event)| event).collect();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                WindowEvent::PointerMoved { .. },
+                WindowEvent::PointerPressed {
+                    button: PointerEventButton::Left,
+                    ..
+                }
+            ]
+        ));
+
+        let up = tracker.up(0);
+        let events: Vec<_> = up.pointer.iter().map(|(_, event)| event).collect();
+        assert!(matches!(
+            events.as_slice(),
+            [
+                WindowEvent::PointerReleased {
+                    button: PointerEventButton::Left,
+                    ..
+                },
+                WindowEvent::PointerExited
+            ]
+        ));
+        assert!(up.gestures.is_empty());
+    }
+
+    #[test]
+    fn second_finger_cancels_the_click() {
+        let mut tracker = TouchTracker::default();
+        let surface = surface();
+        tracker.down(0, surface.clone(), (0.0, 0.0));
+        let down = tracker.down(1, surface, (100.0, 0.0));
+        let events: Vec<_> = down.pointer.iter().map(|(_, event)| event).collect();
+        assert!(matches!(events.as_slice(), [WindowEvent::PointerExited]));
+
+        // Lifting the fingers no longer releases the pointer.
+        let up = [tracker.up(1), tracker.up(0)];
+        assert!(up.iter().all(|output| output.pointer.is_empty()));
+        assert!(gestures(up).is_empty());
+    }
+
+    #[test]
+    fn fingers_moving_together_swipe() {
+        let mut tracker = TouchTracker::default();
+        let gestures = swipe(&mut tracker);
+        assert_eq!(gestures.first(), Some(&Gesture::SwipeBegin { fingers: 2 }));
+        assert!(gestures[1..].iter().all(|gesture| matches!(
+            gesture,
+            Gesture::SwipeUpdate { dx, dy } if *dx > 0.0 && *dy == 0.0
+        )));
+        assert_eq!(
+            tracker.up(0).gestures,
+            vec![Gesture::SwipeEnd { cancelled: false }]
+        );
+    }
+
+    #[test]
+    fn fingers_moving_apart_pinch() {
+        let mut tracker = TouchTracker::default();
+        let surface = surface();
+        tracker.down(0, surface.clone(), (0.0, 0.0));
+        tracker.down(1, surface, (100.0, 0.0));
+        let gestures = tracker.motion(1, (120.0, 0.0)).gestures;
+        assert_eq!(gestures.len(), 2);
+        assert_eq!(gestures[0], Gesture::PinchBegin { fingers: 2 });
+        let Gesture::PinchUpdate {
+            scale, rotation, ..
+        } = gestures[1]
+        else {
+            panic!("Expected a pinch update, got {:?}", gestures[1]);
+        };
+        assert!((scale - 1.2).abs() < 1e-9);
+        assert_eq!(rotation, 0.0);
+    }
+
+    #[test]
+    fn cancel_ends_the_gesture() {
+        let mut tracker = TouchTracker::default();
+        swipe(&mut tracker);
+        assert_eq!(
+            tracker.cancel().gestures,
+            vec![Gesture::SwipeEnd { cancelled: true }]
+        );
+        // Every touch point is dropped.
+        assert!(tracker.up(0).gestures.is_empty());
+        assert!(tracker.up(1).gestures.is_empty());
+    }
+
+    #[test]
+    fn rotation_wraps_around_half_turn() {
+        let mut tracker = TouchTracker::default();
+        let surface = surface();
+        // Line from the first to the second finger is at about 179 degrees.
+        tracker.down(0, surface.clone(), (100.0, 100.0));
+        tracker.down(1, surface, (0.0, 101.0));
+        // It turns to about -179 degrees, while the fingers move apart.
+        let gestures = tracker.motion(1, (-20.0, 99.0)).gestures;
+        let Some(Gesture::PinchUpdate { rotation, .. }) = gestures.last() else {
+            panic!("Expected a pinch update, got {:?}", gestures);
+        };
+        assert!(*rotation > 0.0 && *rotation < 2.0, "rotation: {}", rotation);
+    }
+}
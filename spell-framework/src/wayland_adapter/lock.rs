@@ -1,43 +1,53 @@
 use crate::{
     SpellAssociatedNew,
-    configure::{LayerConf, set_up_tracing},
-    slint_adapter::{SpellLockShell, SpellMultiWinHandler, SpellSkiaWinAdapter},
+    configure::set_up_tracing,
+    slint_adapter::{SpellLockShell, SpellMultiWinHandler},
     wayland_adapter::{
-        common::PointerState,
-        lock::{self, wayland::SpellSlintLock},
+        SpellError,
+        common::{KeyRepeat, PointerState},
+        cursor::ThemedCursor,
+        fractional_scaling::{FractionalScaleState, delegate_fractional_scale},
+        gestures::TouchTracker,
+        lock::{self, wayland::LockSurface},
+        text_input::{TextInput, TextInputState, delegate_text_input},
+        viewporter::{ViewporterState, delegate_viewporter},
     },
 };
-use i_slint_core::items::MouseCursor;
 use nonstick::{
     AuthnFlags, ConversationAdapter, Result as PamResult, Transaction, TransactionBuilder,
 };
-use slint::PhysicalSize;
 use smithay_client_toolkit::{
     compositor::CompositorState,
     delegate_compositor, delegate_keyboard, delegate_output, delegate_pointer, delegate_registry,
     delegate_seat, delegate_session_lock, delegate_shm, delegate_touch,
-    output::{self, OutputState},
+    output::OutputState,
     reexports::{
         calloop::{
-            self, EventLoop, LoopHandle, RegistrationToken,
+            self, EventLoop, LoopHandle,
             channel::{self, Sender},
         },
         calloop_wayland_source::WaylandSource,
         client::{
             Connection, QueueHandle,
             globals::registry_queue_init,
-            protocol::{wl_keyboard::WlKeyboard, wl_shm, wl_touch::WlTouch},
+            protocol::{
+                wl_keyboard::WlKeyboard, wl_output::WlOutput, wl_surface::WlSurface,
+                wl_touch::WlTouch,
+            },
         },
     },
     registry::RegistryState,
     seat::{SeatState, pointer::cursor_shape::CursorShapeManager},
-    session_lock::{SessionLock, SessionLockState, SessionLockSurface},
-    shm::{
-        Shm,
-        slot::{Buffer, Slot, SlotPool},
-    },
+    session_lock::{SessionLock, SessionLockState},
+    shm::Shm,
+};
+use std::{
+    any::Any,
+    cell::RefCell,
+    os::fd::{AsRawFd, BorrowedFd},
+    process::Command,
+    rc::Rc,
 };
-use std::{cell::RefCell, process::Command, rc::Rc};
 use tracing::{Level, info, span, warn};
 
 mod input;
@@ -45,10 +55,15 @@ mod internal;
 mod nonstick_impl;
 mod wayland;
 
+type LockComponentFactory = Box<dyn FnMut(&LockOutput) -> Box<dyn Any>>;
+
 /// SpellLock is a struct which represents a window lock. It can be run and initialised
 /// on a custom lockscreen implementation with slint.
-/// Know limitations include the abscence to verify from fingerprints and unideal issues on
-/// multi-monitor setup. You can add the path of binary of your lock in your compositor config and idle
+/// Know limitations include the abscence to verify from fingerprints. Slint components
+/// created after invoking the lock are shown on the outputs in the order they were
+/// connected; use [`SpellLock::set_component_factory`] to choose the component of every
+/// output instead. Without a factory, monitors plugged in during the lock are covered
+/// with black, as no component is left for them. You can add the path of binary of your lock in your compositor config and idle
 /// manager config to use the program. It will be linked to spell-cli directly in coming releases.
 ///
 /// ## Example
@@ -93,6 +108,7 @@ mod wayland;
 /// ```
 pub struct SpellLock {
     loop_handle: LoopHandle<'static, SpellLock>,
+    queue: QueueHandle<SpellLock>,
     conn: Connection,
     compositor_state: CompositorState,
     registry_state: RegistryState,
@@ -102,16 +118,29 @@ pub struct SpellLock {
     touch_state: Option<WlTouch>,
     seat_state: SeatState,
     shm: Shm,
+    text_input_state: Option<TextInputState>,
+    text_input: Option<TextInput>,
+    /// Lock surfaces are scaled with integer buffer scales in absence of fractional
+    /// scaling, as in [`SpellWin`](crate::wayland_adapter::SpellWin).
+    fractional_scale_state: Option<FractionalScaleState>,
+    viewporter_state: Option<ViewporterState>,
     session_lock: Option<SessionLock>,
-    lock_surfaces: Vec<SessionLockSurface>,
-    slint_part: Option<SpellSlintLock>,
+    /// Lock surfaces of the outputs, created and destroyed along with the outputs.
+    lock_surfaces: Vec<LockSurface>,
+    /// Hands out the adapters of the lock surfaces to slint components.
+    window_manager: Rc<RefCell<SpellMultiWinHandler>>,
+    /// Creates the component of every lock surface, set with
+    /// [`SpellLock::set_component_factory`].
+    component_factory: Option<LockComponentFactory>,
+    keyboard_focus: Option<WlSurface>,
     is_locked: bool,
     /// span used for logging and tracing lockscreen eveents.
     pub span: span::Span,
     unlock_screen: Sender<bool>,
     // TODO, check if it need internal mutability?
     event_loop: Rc<RefCell<EventLoop<'static, SpellLock>>>,
-    backspace: Option<RegistrationToken>,
+    key_repeat: KeyRepeat,
+    touch: TouchTracker,
 }
 
 impl std::fmt::Debug for SpellLock {
@@ -125,34 +154,69 @@ impl std::fmt::Debug for SpellLock {
 impl SpellLock {
     /// This function creates an instance of SpellLock which can be combined with
     /// slint windows to create a lockscreen.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the compositor lacks a global required by the lock,
+    /// use [`SpellLock::try_invoke_lock_spell`] to handle that case.
     pub fn invoke_lock_spell() -> Self {
-        let conn = Connection::connect_to_env().unwrap();
+        SpellLock::try_invoke_lock_spell().unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as [`SpellLock::invoke_lock_spell`], but returns an error instead of
+    /// panicking when the session can't be locked on the compositor.
+    pub fn try_invoke_lock_spell() -> Result<Self, SpellError> {
+        let conn = Connection::connect_to_env().map_err(SpellError::Connect)?;
         let _ = set_up_tracing("SpellLock");
-        let (globals, mut event_queue) = registry_queue_init(&conn).unwrap();
+        let (globals, mut event_queue) =
+            registry_queue_init(&conn).map_err(SpellError::Registry)?;
         let qh: QueueHandle<SpellLock> = event_queue.handle();
         let registry_state = RegistryState::new(&globals);
-        let shm = Shm::bind(&globals, &qh).unwrap();
+        let shm = Shm::bind(&globals, &qh).map_err(|source| SpellError::MissingGlobal {
+            name: "wl_shm",
+            source,
+        })?;
         let event_loop: EventLoop<'static, SpellLock> =
-            EventLoop::try_new().expect("Failed to initialize the event loop!");
+            EventLoop::try_new().map_err(SpellError::EventLoop)?;
         let output_state = OutputState::new(&globals, &qh);
         let session_lock_state = SessionLockState::new(&globals, &qh);
         let compositor_state =
-            CompositorState::bind(&globals, &qh).expect("Faild to create compositor state");
-        let cursor_manager =
-            CursorShapeManager::bind(&globals, &qh).expect("cursor shape is not available");
-        let mut win_handler_vec: Vec<(String, (u32, u32))> = Vec::new();
+            CompositorState::bind(&globals, &qh).map_err(|source| SpellError::MissingGlobal {
+                name: "wl_compositor",
+                source,
+            })?;
+        let cursor_manager = CursorShapeManager::bind(&globals, &qh)
+            .inspect_err(|err| info!("Cursor shape is not available: {}", err))
+            .ok();
+        let text_input_state = TextInputState::bind(&globals, &qh)
+            .inspect_err(|err| info!("Text input is not available: {}", err))
+            .ok();
+        let fractional_scale_state = FractionalScaleState::bind(&globals, &qh)
+            .inspect_err(|err| info!("Fractional scale is not available: {}", err))
+            .ok();
+        let viewporter_state = ViewporterState::bind(&globals, &qh)
+            .inspect_err(|err| info!("Viewporter is not available: {}", err))
+            .ok();
         let lock_surfaces = Vec::new();
 
+        let themed_cursor = cursor_manager
+            .is_none()
+            .then(|| ThemedCursor::load(&conn, &compositor_state, &shm, &qh))
+            .flatten();
         let pointer_state = PointerState {
             pointer: None,
             pointer_data: None,
             cursor_shape: cursor_manager,
+            themed_cursor,
             last_cursor_enter_serial: None,
-            current_wayland_cursor: MouseCursor::Default,
+            last_press: None,
+            current_wayland_cursor: None,
         };
         let (sender, rx) = channel::channel::<bool>();
+        let window_manager = SpellMultiWinHandler::new_lock();
         let mut spell_lock = SpellLock {
             loop_handle: event_loop.handle().clone(),
+            queue: qh.clone(),
             conn: conn.clone(),
             compositor_state,
             output_state,
@@ -161,116 +225,47 @@ impl SpellLock {
             pointer_state,
             registry_state,
             seat_state: SeatState::new(&globals, &qh),
-            slint_part: None,
+            window_manager: window_manager.clone(),
+            component_factory: None,
+            keyboard_focus: None,
             shm,
+            text_input_state,
+            text_input: None,
+            fractional_scale_state,
+            viewporter_state,
             session_lock: None,
             lock_surfaces,
             unlock_screen: sender,
             span: span!(Level::INFO, "lock", name = "lock-screen",),
             is_locked: true,
             event_loop: Rc::new(RefCell::new(event_loop)),
-            backspace: None,
+            key_repeat: KeyRepeat::default(),
+            touch: TouchTracker::default(),
         };
 
         let _ = event_queue.roundtrip(&mut spell_lock);
 
-        let session_lock = Some(
-            session_lock_state
-                .lock(&qh)
-                .expect("ext-session-lock not supported"),
-        );
+        let session_lock = Some(session_lock_state.lock(&qh).map_err(SpellError::Registry)?);
 
         spell_lock.session_lock = session_lock;
-        for output in spell_lock.output_state.outputs() {
-            let output_info: output::OutputInfo = spell_lock.output_state.info(&output).unwrap();
-            let output_name: String = output_info.name.unwrap_or_else(|| "SomeOutput".to_string());
-            let dimensions = (
-                output_info.logical_size.unwrap().0 as u32,
-                output_info.logical_size.unwrap().1 as u32,
-            );
-            win_handler_vec.push((output_name, dimensions));
-
-            let session_lock = spell_lock.session_lock.as_ref().unwrap();
-            let surface = spell_lock.compositor_state.create_surface(&qh);
-
-            // It's important to keep the `SessionLockSurface` returned here around, as the
-            // surface will be destroyed when the `SessionLockSurface` is dropped.
-            let lock_surface = session_lock.create_lock_surface(surface, &output, &qh);
-            spell_lock.lock_surfaces.push(lock_surface);
+        // Lock surfaces of the outputs added later are created as they are announced.
+        let outputs: Vec<WlOutput> = spell_lock.output_state.outputs().collect();
+        for output in outputs {
+            spell_lock.add_lock_surface(output);
         }
-        let multi_handler = SpellMultiWinHandler::new_lock(win_handler_vec);
-        let sizes: Vec<PhysicalSize> = multi_handler
-            .borrow()
-            .windows
-            .iter()
-            .map(|(_, conf)| {
-                if let LayerConf::Lock(width, height) = conf {
-                    PhysicalSize {
-                        width: *width,
-                        height: *height,
-                    }
-                } else {
-                    panic!("Shouldn't enter here");
-                }
-            })
-            .collect();
-
-        let mut pool = SlotPool::new(
-            (sizes[0].width * sizes[0].height * 4) as usize,
-            &spell_lock.shm,
-        )
-        .expect("Couldn't create pool");
-        let mut buffer_slots: Vec<RefCell<Slot>> = Vec::new();
-        let buffers: Vec<Buffer> = sizes
-            .iter()
-            .map(|physical_size| {
-                let stride = physical_size.width as i32 * 4;
-                let (wayland_buffer, _) = pool
-                    .create_buffer(
-                        physical_size.width as i32,
-                        physical_size.height as i32,
-                        stride,
-                        wl_shm::Format::Argb8888,
-                    )
-                    .expect("Creating Buffer");
-                buffer_slots.push(RefCell::new(wayland_buffer.slot()));
-                wayland_buffer
-            })
-            .collect();
+
         let (slint_event_sender, slint_event_receiver) =
             calloop::channel::channel::<Box<dyn FnOnce() + Send>>();
-        let pool: Rc<RefCell<SlotPool>> = Rc::new(RefCell::new(pool));
-        let mut adapters: Vec<Rc<SpellSkiaWinAdapter>> = Vec::new();
-        buffer_slots
-            .into_iter()
-            .enumerate()
-            .for_each(|(index, slot)| {
-                let adapter = SpellSkiaWinAdapter::new(
-                    pool.clone(),
-                    slot,
-                    sizes[index].width,
-                    sizes[index].height,
-                );
-                adapters.push(adapter);
-            });
-
-        multi_handler.borrow_mut().adapter = adapters.clone();
-        spell_lock.slint_part = Some(SpellSlintLock {
-            adapters,
-            size: sizes,
-            wayland_buffer: buffers,
-        });
-
         spell_lock.set_event_sources(slint_event_receiver, rx);
         let _ = slint::platform::set_platform(Box::new(SpellLockShell::new(
-            multi_handler,
+            window_manager,
             slint_event_sender,
         )));
 
         WaylandSource::new(spell_lock.conn.clone(), event_queue)
             .insert(spell_lock.loop_handle.clone())
-            .unwrap();
-        spell_lock
+            .map_err(|err| SpellError::EventLoop(err.error))?;
+        Ok(spell_lock)
     }
 
     fn unlock_finger(&mut self, error_callback: Box<dyn FnOnce() + Send>) {
@@ -356,6 +351,58 @@ impl SpellLock {
         Ok(())
     }
 
+    /// Sets the factory creating the slint component of every output, so that each one
+    /// can show a different component. It is called with the details of the output
+    /// whenever a lock surface is created, and its return value is kept alive till the
+    /// surface is destroyed. The component of an output is created again when it
+    /// becomes or stops being the primary one, as on unplugging the primary monitor.
+    /// Exactly one slint component has to be created in each call, and no component
+    /// should be created outside of the factory once it is set.
+    ///
+    /// ## Example
+    ///
+    /// ```rust,ignore
+    /// use spell_framework::{cast_spell, wayland_adapter::SpellLock};
+    /// use slint::ComponentHandle;
+    /// slint::include_modules!();
+    ///
+    /// fn main() -> Result<(), Box<dyn std::error::Error>> {
+    ///     let mut lock = SpellLock::invoke_lock_spell();
+    ///     let lock_handle = lock.get_handler();
+    ///     lock.set_component_factory(move |output| {
+    ///         if output.is_primary {
+    ///             let login = LockScreen::new().unwrap();
+    ///             let lock_handle = lock_handle.clone();
+    ///             login.on_check_pass(move |password| {
+    ///                 lock_handle.unlock(
+    ///                     None,
+    ///                     password.to_string(),
+    ///                     Box::new(|| {}),
+    ///                     Box::new(|| {}),
+    ///                 );
+    ///             });
+    ///             Box::new(login)
+    ///         } else {
+    ///             // Any other component of your slint files, like a clock.
+    ///             Box::new(LockClock::new().unwrap())
+    ///         }
+    ///     });
+    ///     cast_spell!(lock: lock)
+    /// }
+    /// ```
+    pub fn set_component_factory<F>(&mut self, factory: F)
+    where
+        F: FnMut(&LockOutput) -> Box<dyn Any> + 'static,
+    {
+        // Adapters are no longer handed out in the order of outputs.
+        self.window_manager.borrow_mut().pending.clear();
+        self.component_factory = Some(Box::new(factory));
+        for lock_surface in &mut self.lock_surfaces {
+            lock_surface.component = None;
+        }
+        self.update_components();
+    }
+
     /// Provides a lockscreen handler used to invoke the unlock
     /// callback with the user entered password.For more details
     /// view [`LockHandle`].
@@ -369,10 +416,20 @@ impl SpellAssociatedNew for SpellLock {
         let event_loop = self.event_loop.clone();
         event_loop
             .borrow_mut()
-            .dispatch(std::time::Duration::from_millis(1), self)?;
+            .dispatch(std::time::Duration::ZERO, self)?;
+        let qh = self.queue.clone();
+        self.converter_lock(&qh);
+        self.update_text_input();
+        self.pointer_state.animate_cursor();
+        self.conn.flush()?;
         Ok(())
     }
 
+    fn event_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: The fd belongs to the event loop which lives as long as `self`.
+        unsafe { BorrowedFd::borrow_raw(self.event_loop.borrow().as_raw_fd()) }
+    }
+
     fn is_locked(&self) -> bool {
         self.is_locked
     }
@@ -380,6 +437,16 @@ impl SpellAssociatedNew for SpellLock {
     fn get_span(&self) -> span::Span {
         self.span.clone()
     }
+
+    fn next_timeout(&self) -> Option<std::time::Duration> {
+        [
+            self.key_repeat.next_timeout(),
+            self.pointer_state.cursor_timeout(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
 }
 
 delegate_keyboard!(SpellLock);
@@ -391,6 +458,26 @@ delegate_pointer!(SpellLock);
 delegate_touch!(SpellLock);
 delegate_session_lock!(SpellLock);
 delegate_seat!(SpellLock);
+delegate_text_input!(SpellLock);
+delegate_fractional_scale!(SpellLock);
+delegate_viewporter!(SpellLock);
+
+/// Details of the output of a lock surface, given to the factory set with
+/// [`SpellLock::set_component_factory`].
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub struct LockOutput {
+    /// Name of the output, like `DP-1`.
+    pub name: String,
+    /// Width and height of the output in logical pixels.
+    pub size: (u32, u32),
+    /// Integer scale of the output. Lock surfaces are still rendered at fractional
+    /// scales when the compositor supports them.
+    pub scale: i32,
+    /// Whether the output is the primary one, chosen as in
+    /// [`MonitorSelector::Primary`](crate::layer_properties::MonitorSelector::Primary).
+    pub is_primary: bool,
+}
 
 /// Struct to handle unlocking of a SpellLock instance. It can be captured from
 /// [`SpellLock::get_handler`].
@@ -398,6 +485,13 @@ delegate_seat!(SpellLock);
 pub struct LockHandle(LoopHandle<'static, SpellLock>);
 
 impl LockHandle {
+    /// Queues a callback to run on the lock in its next dispatch and wakes
+    /// the event loop so that the dispatch happens right away.
+    fn insert_idle<F: FnOnce(&mut SpellLock) + 'static>(&self, callback: F) {
+        self.0.insert_idle(callback);
+        crate::wake_event_loop();
+    }
+
     /// Call this method to unlock Spelllock. It also takes two callbacks which
     /// are invoked when the password parsed is wrong or right (i.e. resulting
     /// in an screen unlock) respectively. Callbacks can be used to invoke UI
@@ -409,7 +503,7 @@ impl LockHandle {
         on_err_callback: Box<dyn FnOnce()>,
         on_unlock_callback: Box<dyn FnOnce()>,
     ) {
-        self.0.insert_idle(move |app_data| {
+        self.insert_idle(move |app_data| {
             if app_data
                 .unlock(username.as_deref(), &password, on_unlock_callback)
                 .is_err()
@@ -423,7 +517,7 @@ impl LockHandle {
     /// error_callback is executed when fingerprint is not registered and fails
     /// to unlock the lockscreen.
     pub fn verify_fingerprint(&self, error_callback: Box<dyn FnOnce() + Send>) {
-        self.0.insert_idle(move |app_data| {
+        self.insert_idle(move |app_data| {
             app_data.unlock_finger(error_callback);
         });
     }
@@ -2,12 +2,10 @@ use crate::wayland_adapter::{
     SpellLock,
     common::{self, get_string},
 };
+use i_slint_core::items::MouseCursor;
 use slint::{SharedString, platform::WindowEvent};
 use smithay_client_toolkit::{
-    reexports::{
-        client::{Connection, QueueHandle, protocol::wl_pointer},
-        protocols::wp::cursor_shape::v1::client::wp_cursor_shape_device_v1::Shape,
-    },
+    reexports::client::{Connection, QueueHandle, protocol::wl_pointer},
     seat::{
         keyboard::KeyboardHandler,
         pointer::{PointerEvent, PointerEventKind, PointerHandler},
@@ -24,9 +22,11 @@ impl TouchHandler for SpellLock {
         _touch: &smithay_client_toolkit::reexports::client::protocol::wl_touch::WlTouch,
         _serial: u32,
         _time: u32,
-        _id: i32,
+        id: i32,
     ) {
-        info!("Up event from touch");
+        trace!("Up event from touch");
+        let output = self.touch.up(id);
+        self.handle_touch(output);
     }
     fn down(
         &mut self,
@@ -35,11 +35,13 @@ impl TouchHandler for SpellLock {
         _touch: &smithay_client_toolkit::reexports::client::protocol::wl_touch::WlTouch,
         _serial: u32,
         _time: u32,
-        _surface: smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
-        _id: i32,
+        surface: smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
+        id: i32,
         position: (f64, f64),
     ) {
-        info!("Down event produced with posaition: {position:?}");
+        trace!("Down event produced with position: {position:?}");
+        let output = self.touch.down(id, surface, position);
+        self.handle_touch(output);
     }
 
     fn motion(
@@ -48,17 +50,11 @@ impl TouchHandler for SpellLock {
         _qh: &QueueHandle<Self>,
         _touch: &smithay_client_toolkit::reexports::client::protocol::wl_touch::WlTouch,
         _time: u32,
-        _id: i32,
+        id: i32,
         position: (f64, f64),
     ) {
-        self.slint_part.as_ref().unwrap().adapters[0]
-            .try_dispatch_event(WindowEvent::PointerMoved {
-                position: slint::LogicalPosition {
-                    x: position.0 as f32,
-                    y: position.1 as f32,
-                },
-            })
-            .unwrap_or_else(|err| warn!("Touch move event failed with error: {:?}", err));
+        let output = self.touch.motion(id, position);
+        self.handle_touch(output);
     }
 
     fn shape(
@@ -70,7 +66,7 @@ impl TouchHandler for SpellLock {
         major: f64,
         minor: f64,
     ) {
-        info!("Shape data released. Major: {major}, Minor: {minor}");
+        trace!("Shape data released. Major: {major}, Minor: {minor}");
     }
     fn orientation(
         &mut self,
@@ -80,7 +76,7 @@ impl TouchHandler for SpellLock {
         _id: i32,
         orientation: f64,
     ) {
-        info!("Orientation data released: {orientation}.")
+        trace!("Orientation data released: {orientation}.")
     }
     fn cancel(
         &mut self,
@@ -89,6 +85,8 @@ impl TouchHandler for SpellLock {
         _touch: &smithay_client_toolkit::reexports::client::protocol::wl_touch::WlTouch,
     ) {
         info!("Active touch sequence cancelled");
+        let output = self.touch.cancel();
+        self.handle_touch(output);
     }
 }
 
@@ -103,17 +101,13 @@ impl PointerHandler for SpellLock {
         use PointerEventKind::*;
         for event in events {
             // Ignore events for other surfaces
-            for surface in self.lock_surfaces.iter() {
-                if event.surface != *surface.wl_surface() {
-                    continue;
-                }
-            }
+            let Some(adapter) = self.adapter_for_surface(&event.surface) else {
+                continue;
+            };
             match event.kind {
                 Enter { .. } => {
                     info!("Pointer entered: {:?}", event.position);
 
-                    // TODO this code is redundent, as it doesn't set the cursor shape.
-                    let pointer = &self.pointer_state.pointer.as_ref().unwrap();
                     let serial_no: Option<u32> = self
                         .pointer_state
                         .pointer_data
@@ -121,15 +115,14 @@ impl PointerHandler for SpellLock {
                         .unwrap()
                         .latest_enter_serial();
                     if let Some(no) = serial_no {
-                        self.pointer_state
-                            .cursor_shape
-                            .get_shape_device(pointer, qh)
-                            .set_shape(no, Shape::Pointer);
+                        self.pointer_state.enter(no, adapter.scale_factor.get());
+                        self.pointer_state.update_cursor(MouseCursor::Pointer, qh);
                     }
                 }
                 Leave { .. } => {
                     info!("Pointer left: {:?}", event.position);
-                    self.slint_part.as_ref().unwrap().adapters[0]
+                    self.pointer_state.leave();
+                    adapter
                         .try_dispatch_event(WindowEvent::PointerExited)
                         .unwrap_or_else(|err| {
                             warn!("Pointer left event failed with error: {:?}", err)
@@ -137,7 +130,7 @@ impl PointerHandler for SpellLock {
                 }
                 Motion { .. } => {
                     // debug!("Pointer entered @{:?}", event.position);
-                    self.slint_part.as_ref().unwrap().adapters[0]
+                    adapter
                         .try_dispatch_event(WindowEvent::PointerMoved {
                             position: slint::LogicalPosition {
                                 x: event.position.0 as f32,
@@ -151,7 +144,7 @@ impl PointerHandler for SpellLock {
                 Press { button, .. } => {
                     trace!("Press {:x} @ {:?}", button, event.position);
 
-                    self.slint_part.as_ref().unwrap().adapters[0]
+                    adapter
                         .try_dispatch_event(WindowEvent::PointerPressed {
                             position: slint::LogicalPosition {
                                 x: event.position.0 as f32,
@@ -166,7 +159,7 @@ impl PointerHandler for SpellLock {
                 Release { button, .. } => {
                     trace!("Release {:x} @ {:?}", button, event.position);
 
-                    self.slint_part.as_ref().unwrap().adapters[0]
+                    adapter
                         .try_dispatch_event(WindowEvent::PointerReleased {
                             position: slint::LogicalPosition {
                                 x: event.position.0 as f32,
@@ -184,7 +177,7 @@ impl PointerHandler for SpellLock {
                     ..
                 } => {
                     trace!("Scroll H:{horizontal:?}, V:{vertical:?}");
-                    self.slint_part.as_ref().unwrap().adapters[0]
+                    adapter
                         .try_dispatch_event(WindowEvent::PointerScrolled {
                             position: slint::LogicalPosition {
                                 x: event.position.0 as f32,
@@ -208,12 +201,13 @@ impl KeyboardHandler for SpellLock {
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
         _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
-        _surface: &smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
+        surface: &smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
         _serial: u32,
         _raw: &[u32],
         _keysyms: &[smithay_client_toolkit::seat::keyboard::Keysym],
     ) {
         info!("Keyboard focus entered");
+        self.keyboard_focus = Some(surface.clone());
     }
 
     fn leave(
@@ -225,6 +219,8 @@ impl KeyboardHandler for SpellLock {
         _serial: u32,
     ) {
         info!("Keyboard focus left");
+        self.keyboard_focus = None;
+        self.key_repeat.stop(&self.loop_handle);
     }
 
     fn press_key(
@@ -235,18 +231,30 @@ impl KeyboardHandler for SpellLock {
         _serial: u32,
         event: smithay_client_toolkit::seat::keyboard::KeyEvent,
     ) {
-        let string_val: SharedString = get_string(event);
-        // if string_val == <slint::platform::Key as Into<SharedString>>::into(Key::Backspace) {
-        //     self.loop_handle.enable(&self.backspace.unwrap()).unwrap();
-        //     self.slint_part.as_ref().unwrap().adapters[0]
-        //         .try_dispatch_event(WindowEvent::KeyPressed { text: string_val })
-        //         .unwrap();
-        // } else {
+        let string_val: SharedString = get_string(event.clone());
         info!("Key pressed with value : {:?}", string_val);
-        self.slint_part.as_ref().unwrap().adapters[0]
-            .try_dispatch_event(WindowEvent::KeyPressed { text: string_val })
+        let Some(adapter) = self.focused_adapter() else {
+            return;
+        };
+        adapter
+            .try_dispatch_event(WindowEvent::KeyPressed {
+                text: string_val.clone(),
+            })
             .unwrap_or_else(|err| warn!("Key press event failed with error: {:?}", err));
-        //}
+        self.key_repeat.start(
+            &self.loop_handle,
+            &event,
+            string_val,
+            |lock: &mut SpellLock, text| {
+                if let Some(adapter) = lock.focused_adapter() {
+                    adapter
+                        .try_dispatch_event(WindowEvent::KeyPressRepeated { text })
+                        .unwrap_or_else(|err| {
+                            warn!("Key repeat event failed with error: {:?}", err)
+                        });
+                }
+            },
+        );
     }
 
     fn release_key(
@@ -258,15 +266,13 @@ impl KeyboardHandler for SpellLock {
         event: smithay_client_toolkit::seat::keyboard::KeyEvent,
     ) {
         info!("Key is released");
-        // if let Err(err) = self.loop_handle.disable(&self.backspace.unwrap()) {
-        //     warn!("{}", err);
-        // }
-        // let key_sym = Keysym::new(event.raw_code);
-        // event.keysym = key_sym;
+        self.key_repeat.stop_key(&self.loop_handle, event.raw_code);
         let string_val: SharedString = get_string(event);
-        self.slint_part.as_ref().unwrap().adapters[0]
-            .try_dispatch_event(WindowEvent::KeyReleased { text: string_val })
-            .unwrap_or_else(|err| warn!("Key release event failed with error: {:?}", err));
+        if let Some(adapter) = self.focused_adapter() {
+            adapter
+                .try_dispatch_event(WindowEvent::KeyReleased { text: string_val })
+                .unwrap_or_else(|err| warn!("Key release event failed with error: {:?}", err));
+        }
     }
 
     // TODO needs to be implemented to enable functionalities of ctl, shift, alt etc.
@@ -293,14 +299,14 @@ impl KeyboardHandler for SpellLock {
     ) {
         trace!("Repeated key entered");
     }
-    // TODO This method needs to be implemented after the looping mecha is changed to calloop.
     fn update_repeat_info(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
         _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
-        _info: smithay_client_toolkit::seat::keyboard::RepeatInfo,
+        info: smithay_client_toolkit::seat::keyboard::RepeatInfo,
     ) {
         trace!("Repeat info updation called");
+        self.key_repeat.update_info(info);
     }
 }
@@ -1,36 +1,327 @@
-use slint::platform::{Key, WindowAdapter};
-use smithay_client_toolkit::reexports::{
-    calloop::{
-        self,
-        channel::{self, Channel},
-        timer::{TimeoutAction, Timer},
+use slint::platform::{WindowAdapter, WindowEvent};
+use smithay_client_toolkit::{
+    reexports::{
+        calloop::{
+            self,
+            channel::{self, Channel},
+        },
+        client::{
+            QueueHandle,
+            protocol::{wl_output::WlOutput, wl_shm, wl_surface::WlSurface},
+        },
     },
-    client::QueueHandle,
+    shm::slot::SlotPool,
 };
-use tracing::warn;
+use std::{cell::RefCell, rc::Rc};
+use tracing::{info, warn};
 
-use crate::wayland_adapter::SpellLock;
+use crate::{
+    configure::MonitorSelector,
+    slint_adapter::SpellSkiaWinAdapter,
+    wayland_adapter::{
+        SpellLock,
+        common::{damage_surface, outputs_with_info},
+        gestures::TouchOutput,
+        lock::{LockOutput, wayland::LockSurface},
+        text_input::TextInput,
+    },
+};
+
+impl LockSurface {
+    /// Attaches a black buffer of the size of the surface, unless one of the same
+    /// size is attached already.
+    fn fill(&mut self) {
+        let size = self.adapter.size.get();
+        let (width, height) = (size.width as i32, size.height as i32);
+        if self
+            .fill_buffer
+            .as_ref()
+            .is_some_and(|buffer| buffer.height() == height && buffer.stride() == width * 4)
+        {
+            return;
+        }
+        let mut pool = self.adapter.buffer_slint.pool.borrow_mut();
+        let (buffer, canvas) =
+            match pool.create_buffer(width, height, width * 4, wl_shm::Format::Argb8888) {
+                Ok(buffer) => buffer,
+                Err(err) => {
+                    warn!("Couldn't create the buffer of the lock surface: {}", err);
+                    return;
+                }
+            };
+        for pixel in canvas.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[0, 0, 0, 255]);
+        }
+        let surface = self.surface.wl_surface();
+        if let Err(err) = buffer.attach_to(surface) {
+            warn!("Couldn't attach the buffer of the lock surface: {}", err);
+            return;
+        }
+        surface.damage_buffer(0, 0, width, height);
+        surface.commit();
+        self.fill_buffer = Some(buffer);
+    }
+}
 
 impl SpellLock {
+    /// Returns the adapter of the lock surface with the given surface.
+    pub(super) fn adapter_for_surface(
+        &self,
+        surface: &WlSurface,
+    ) -> Option<Rc<SpellSkiaWinAdapter>> {
+        self.lock_surfaces
+            .iter()
+            .find(|lock_surface| lock_surface.surface.wl_surface() == surface)
+            .map(|lock_surface| lock_surface.adapter.clone())
+    }
+
+    /// Returns the adapter of the lock surface with keyboard focus.
+    pub(super) fn focused_adapter(&self) -> Option<Rc<SpellSkiaWinAdapter>> {
+        self.adapter_for_surface(self.keyboard_focus.as_ref()?)
+    }
+
+    /// Creates the lock surface of the output, along with its own pool and adapter
+    /// sized as per the output. The adapter is given to the next slint component
+    /// created. Nothing is done if the session is not locked.
+    pub(super) fn add_lock_surface(&mut self, output: WlOutput) {
+        let Some(session_lock) = self.session_lock.as_ref() else {
+            return;
+        };
+        if self
+            .lock_surfaces
+            .iter()
+            .any(|lock_surface| lock_surface.output == output)
+        {
+            return;
+        }
+        let info = self.output_state.info(&output);
+        let name = info
+            .as_ref()
+            .and_then(|info| info.name.clone())
+            .unwrap_or_else(|| "SomeOutput".to_string());
+        // The size is corrected on configure if the output's size isn't known yet.
+        let (width, height) = info
+            .and_then(|info| info.logical_size)
+            .map_or((1, 1), |(width, height)| {
+                (width.max(1) as u32, height.max(1) as u32)
+            });
+
+        let surface = self.compositor_state.create_surface(&self.queue);
+        // It's important to keep the `SessionLockSurface` returned here around, as the
+        // surface will be destroyed when the `SessionLockSurface` is dropped.
+        let surface = session_lock.create_lock_surface(surface, &output, &self.queue);
+        // Buffers are rendered at the preferred scale of each output, sent through
+        // the fractional scale of its surface.
+        let viewport = match (
+            self.fractional_scale_state.as_ref(),
+            self.viewporter_state.as_ref(),
+        ) {
+            (Some(fractional_scale_state), Some(viewporter_state)) => {
+                let fractional_scale =
+                    fractional_scale_state.get_scale(surface.wl_surface(), &self.queue);
+                Some(viewporter_state.get_viewport(
+                    surface.wl_surface(),
+                    &self.queue,
+                    fractional_scale,
+                ))
+            }
+            _ => None,
+        };
+        // Buffers are created by the adapter when drawing, the pool grows as needed.
+        let pool = match SlotPool::new((width * height * 4 * 2) as usize, &self.shm) {
+            Ok(pool) => pool,
+            Err(err) => {
+                warn!("Couldn't create pool for output {}: {}", name, err);
+                return;
+            }
+        };
+        let adapter = SpellSkiaWinAdapter::new(Rc::new(RefCell::new(pool)), width, height);
+        // Components made by the factory are given their adapters one at a time.
+        if self.component_factory.is_none() {
+            self.window_manager.borrow_mut().add_lock(adapter.clone());
+        }
+        info!(
+            "Lock surface created for output {} with width: {}, height: {}",
+            name, width, height
+        );
+        self.lock_surfaces.push(LockSurface {
+            output,
+            viewport,
+            surface,
+            adapter,
+            component: None,
+            is_primary: false,
+            fill_buffer: None,
+            is_configured: false,
+        });
+        self.update_components();
+    }
+
+    /// Destroys the lock surface of the output along with its adapter.
+    pub(super) fn remove_lock_surface(&mut self, output: &WlOutput) {
+        let Some(index) = self
+            .lock_surfaces
+            .iter()
+            .position(|lock_surface| lock_surface.output == *output)
+        else {
+            return;
+        };
+        let lock_surface = self.lock_surfaces.remove(index);
+        self.window_manager
+            .borrow_mut()
+            .remove_lock(&lock_surface.adapter);
+        if self.keyboard_focus.as_ref() == Some(lock_surface.surface.wl_surface()) {
+            self.keyboard_focus = None;
+        }
+        info!("Lock surface of the removed output is destroyed");
+        // Another output becomes primary if the primary one is removed.
+        self.update_components();
+    }
+
+    /// Creates the components of the lock surfaces with the factory set by the user.
+    /// Components of the outputs which have become or are no longer primary are
+    /// created again, so that the login form always stays on the primary output.
+    pub(super) fn update_components(&mut self) {
+        let Some(factory) = self.component_factory.as_mut() else {
+            return;
+        };
+        let outputs = outputs_with_info(&self.output_state);
+        let primary = MonitorSelector::Primary
+            .resolve(&outputs)
+            .map(|(output, _)| output.clone());
+        for lock_surface in &mut self.lock_surfaces {
+            let is_primary = primary.as_ref() == Some(&lock_surface.output);
+            if lock_surface.component.is_some() && lock_surface.is_primary == is_primary {
+                continue;
+            }
+            let info = outputs
+                .iter()
+                .find(|(output, _)| *output == lock_surface.output)
+                .map(|(_, info)| info);
+            let size = lock_surface.adapter.size_original.get();
+            let lock_output = LockOutput {
+                name: info
+                    .and_then(|info| info.name.clone())
+                    .unwrap_or_else(|| "SomeOutput".to_string()),
+                size: (size.width, size.height),
+                scale: info.map_or(1, |info| info.scale_factor),
+                is_primary,
+            };
+            // The old component is dropped before its adapter is given to the new one.
+            lock_surface.component = None;
+            self.window_manager
+                .borrow_mut()
+                .add_lock(lock_surface.adapter.clone());
+            let component = factory(&lock_output);
+            let mut window_manager = self.window_manager.borrow_mut();
+            if window_manager.is_pending(&lock_surface.adapter) {
+                warn!(
+                    "No slint component was created for output {}",
+                    lock_output.name
+                );
+                window_manager.remove_lock(&lock_surface.adapter);
+            } else {
+                info!(
+                    "Lock component created for output {}, primary: {}",
+                    lock_output.name, is_primary
+                );
+            }
+            lock_surface.component = Some(component);
+            lock_surface.is_primary = is_primary;
+            lock_surface.adapter.request_redraw();
+        }
+    }
+
+    /// Re-renders the lock surface at the given scale, which has a denominator of
+    /// 120. The surface is scaled by its viewport if it has one and by an integer
+    /// buffer scale otherwise.
+    pub(super) fn rescale_surface(&mut self, surface: &WlSurface, scale: u32) {
+        let Some(lock_surface) = self
+            .lock_surfaces
+            .iter()
+            .find(|lock_surface| lock_surface.surface.wl_surface() == surface)
+        else {
+            return;
+        };
+        let adapter = &lock_surface.adapter;
+        let (width, height, scale_factor) = adapter.changed_scale_factor(scale);
+        adapter
+            .try_dispatch_event(WindowEvent::ScaleFactorChanged { scale_factor })
+            .unwrap_or_else(|err| warn!("Scale factor event failed with error: {:?}", err));
+        match lock_surface.viewport.as_ref() {
+            Some(viewport) => {
+                let size = adapter.size_original.get();
+                viewport.set_source(0., 0., width.into(), height.into());
+                viewport.set_destination(size.width as i32, size.height as i32);
+            }
+            None => surface.set_buffer_scale((scale / 120) as i32),
+        }
+        // Frame of the old scale is of no use.
+        adapter.frame_pending.set(false);
+        adapter.request_redraw();
+    }
+
+    /// Dispatches the pointer events of touch points to the adapters of their
+    /// surfaces. Gestures are of no use on a lockscreen.
+    pub(super) fn handle_touch(&mut self, output: TouchOutput) {
+        for (surface, event) in output.pointer {
+            if let Some(adapter) = self.adapter_for_surface(&surface) {
+                adapter
+                    .try_dispatch_event(event)
+                    .unwrap_or_else(|err| warn!("Touch event failed with error: {:?}", err));
+            }
+        }
+    }
+
+    /// Sends the input method requests made by slint since the last call to the
+    /// compositor.
+    pub(super) fn update_text_input(&mut self) {
+        let Some(surface) = self.text_input.as_ref().and_then(TextInput::focus) else {
+            return;
+        };
+        let Some(adapter) = self.adapter_for_surface(&surface) else {
+            return;
+        };
+        if let Some(text_input) = self.text_input.as_mut() {
+            text_input.update(&adapter, false);
+        }
+    }
+
     pub(super) fn converter_lock(&mut self, qh: &QueueHandle<Self>) {
         slint::platform::update_timers_and_animations();
-        let width: u32 = self.slint_part.as_ref().unwrap().size[0].width;
-        let height: u32 = self.slint_part.as_ref().unwrap().size[0].height;
-        let window_adapter = self.slint_part.as_ref().unwrap().adapters[0].clone();
-        let _redraw_val: bool = window_adapter.draw_if_needed();
-
-        let buffer = &self.slint_part.as_ref().unwrap().wayland_buffer[0];
-        self.lock_surfaces[0]
-            .wl_surface()
-            .damage_buffer(0, 0, width as i32, height as i32);
-        self.lock_surfaces[0]
-            .wl_surface()
-            .frame(qh, self.lock_surfaces[0].wl_surface().clone());
-        self.lock_surfaces[0]
-            .wl_surface()
-            .attach(Some(buffer.wl_buffer()), 0, 0);
-
-        self.lock_surfaces[0].wl_surface().commit();
+        for lock_surface in &mut self.lock_surfaces {
+            // Buffers can only be attached after the first configure.
+            if !lock_surface.is_configured {
+                continue;
+            }
+            // Surfaces whose adapter isn't given to a slint component have nothing
+            // to draw, they are filled so that their output is still covered.
+            if self
+                .window_manager
+                .borrow()
+                .is_pending(&lock_surface.adapter)
+            {
+                lock_surface.fill();
+                continue;
+            }
+            lock_surface.fill_buffer = None;
+            let adapter = &lock_surface.adapter;
+            // Waits for the compositor to present the last frame.
+            if adapter.frame_pending.get() {
+                continue;
+            }
+
+            // Frames are only requested after a draw, so an idle lockscreen doesn't
+            // keep the compositor busy.
+            if adapter.draw_if_needed() {
+                let surface = lock_surface.surface.wl_surface();
+                damage_surface(surface, adapter, false);
+                surface.frame(qh, surface.clone());
+                adapter.frame_pending.set(true);
+                adapter.attach_buffer(surface);
+                surface.commit();
+            }
+        }
     }
 
     pub(super) fn set_event_sources(
@@ -44,31 +335,13 @@ impl SpellLock {
                 if let calloop::channel::Event::Msg(callback) = event {
                     callback();
 
-                    if let Some(slint_part) = &data.slint_part {
-                        for adapter in &slint_part.adapters {
-                            adapter.request_redraw();
-                        }
+                    for lock_surface in &data.lock_surfaces {
+                        lock_surface.adapter.request_redraw();
                     }
                 }
             })
             .unwrap();
 
-        self.backspace = Some(
-            loop_handle
-                .insert_source(
-                    Timer::from_duration(std::time::Duration::from_millis(1500)),
-                    |_, _, data| {
-                        data.slint_part.as_ref().unwrap().adapters[0]
-                            .try_dispatch_event(slint::platform::WindowEvent::KeyPressed {
-                                text: Key::Backspace.into(),
-                            })
-                            .unwrap();
-                        TimeoutAction::ToDuration(std::time::Duration::from_millis(1500))
-                    },
-                )
-                .unwrap(),
-        );
-
         let _ = loop_handle
             .clone()
             .insert_source(rx, move |event, _, data| match event {
@@ -87,7 +360,5 @@ impl SpellLock {
                     warn!("Unlock channel to open thread is closed.");
                 }
             });
-
-        loop_handle.disable(&self.backspace.unwrap()).unwrap();
     }
 }
@@ -1,4 +1,3 @@
-use slint::PhysicalSize;
 use smithay_client_toolkit::{
     compositor::CompositorHandler,
     output::{OutputHandler, OutputState},
@@ -14,9 +13,18 @@ use smithay_client_toolkit::{
     },
     shm::{Shm, ShmHandler, slot::Buffer},
 };
-use tracing::info;
+use std::{any::Any, rc::Rc};
+use tracing::{info, trace};
 
-use crate::{slint_adapter::SpellSkiaWinAdapter, wayland_adapter::lock::SpellLock};
+use crate::{
+    slint_adapter::SpellSkiaWinAdapter,
+    wayland_adapter::{
+        fractional_scaling::FractionalScaleHandler,
+        lock::SpellLock,
+        text_input::{TextInputChanges, TextInputHandler},
+        viewporter::Viewport,
+    },
+};
 
 impl ProvidesRegistryState for SpellLock {
     fn registry(&mut self) -> &mut RegistryState {
@@ -40,9 +48,11 @@ impl OutputHandler for SpellLock {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
+        output: wl_output::WlOutput,
     ) {
         info!("New output source added");
+        // Outputs plugged in while locked have to be covered as well.
+        self.add_lock_surface(output);
     }
 
     fn update_output(
@@ -52,15 +62,18 @@ impl OutputHandler for SpellLock {
         _output: wl_output::WlOutput,
     ) {
         info!("Updated output source");
+        // Moving an output can change which one is primary.
+        self.update_components();
     }
 
     fn output_destroyed(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
+        output: wl_output::WlOutput,
     ) {
         info!("Output is destroyed");
+        self.remove_lock_surface(&output);
     }
 }
 
@@ -69,10 +82,14 @@ impl CompositorHandler for SpellLock {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _new_factor: i32,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
     ) {
-        info!("Scale factor changed");
+        info!("Scale factor changed: {}", new_factor);
+        // Preferred scale of fractional scale is used whenever it is available.
+        if self.fractional_scale_state.is_none() || self.viewporter_state.is_none() {
+            self.rescale_surface(surface, new_factor.max(1) as u32 * 120);
+        }
     }
 
     fn transform_changed(
@@ -89,9 +106,13 @@ impl CompositorHandler for SpellLock {
         &mut self,
         _conn: &Connection,
         qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
+        surface: &wl_surface::WlSurface,
         _time: u32,
     ) {
+        // The last frame of the surface is presented, a new one can be drawn.
+        if let Some(adapter) = self.adapter_for_surface(surface) {
+            adapter.frame_pending.set(false);
+        }
         self.converter_lock(qh);
     }
 
@@ -134,10 +155,34 @@ impl SessionLockHandler for SpellLock {
         &mut self,
         _conn: &Connection,
         qh: &QueueHandle<Self>,
-        _surface: SessionLockSurface,
-        _configure: SessionLockSurfaceConfigure,
+        surface: SessionLockSurface,
+        configure: SessionLockSurfaceConfigure,
         _serial: u32,
     ) {
+        let Some(lock_surface) = self
+            .lock_surfaces
+            .iter_mut()
+            .find(|lock_surface| lock_surface.surface.wl_surface() == surface.wl_surface())
+        else {
+            return;
+        };
+        // Buffers have to be of the size sent by the compositor, which is the size
+        // of the output.
+        let (width, height) = configure.new_size;
+        let size = lock_surface.adapter.size_original.get();
+        if (size.width, size.height) != (width, height) {
+            info!(
+                "Lock surface resized to width: {}, height: {}",
+                width, height
+            );
+            let (physical_width, physical_height) = lock_surface.adapter.resize(width, height);
+            if let Some(viewport) = lock_surface.viewport.as_ref() {
+                viewport.set_source(0., 0., physical_width.into(), physical_height.into());
+                viewport.set_destination(width as i32, height as i32);
+            }
+            lock_surface.adapter.frame_pending.set(false);
+        }
+        lock_surface.is_configured = true;
         self.converter_lock(qh);
     }
 }
@@ -163,6 +208,9 @@ impl SeatHandler for SpellLock {
                 .get_keyboard(qh, &seat, None)
                 .expect("Failed to create keyboard");
             self.keyboard_state = Some(keyboard);
+            if let Some(text_input_state) = &self.text_input_state {
+                self.text_input = Some(text_input_state.get_text_input(&seat, qh));
+            }
         }
         if capability == Capability::Touch && self.touch_state.is_none() {
             info!("Setting touch Capability");
@@ -194,6 +242,7 @@ impl SeatHandler for SpellLock {
         if capability == Capability::Keyboard && self.keyboard_state.is_some() {
             info!("Unsettting keyboard capability");
             self.keyboard_state.take().unwrap().release();
+            self.text_input = None;
         }
         if capability == Capability::Pointer && self.pointer_state.pointer.is_some() {
             info!("Unsetting pointer capability");
@@ -208,9 +257,76 @@ impl SeatHandler for SpellLock {
     fn remove_seat(&mut self, _: &Connection, _: &QueueHandle<Self>, _: wl_seat::WlSeat) {}
 }
 
-/// It is an internal struct used by [`SpellLock`] internally.
-pub struct SpellSlintLock {
-    pub(crate) adapters: Vec<std::rc::Rc<SpellSkiaWinAdapter>>,
-    pub(crate) size: Vec<PhysicalSize>,
-    pub(crate) wayland_buffer: Vec<Buffer>,
+/// Lock surface covering an output, along with the adapter drawing on it.
+pub(crate) struct LockSurface {
+    pub(crate) output: wl_output::WlOutput,
+    /// Viewport of the surface, `None` without fractional scaling. It is declared
+    /// before the surface so that it is destroyed first.
+    pub(crate) viewport: Option<Viewport>,
+    pub(crate) surface: SessionLockSurface,
+    pub(crate) adapter: Rc<SpellSkiaWinAdapter>,
+    /// Component created for the output by the factory set with
+    /// [`SpellLock::set_component_factory`], if any.
+    pub(crate) component: Option<Box<dyn Any>>,
+    /// Whether the component was created for the primary output.
+    pub(crate) is_primary: bool,
+    /// Buffer attached while the surface has no slint component.
+    pub(crate) fill_buffer: Option<Buffer>,
+    /// Set once the compositor configures the surface, buffers can't be attached
+    /// before that.
+    pub(crate) is_configured: bool,
+}
+
+impl FractionalScaleHandler for SpellLock {
+    fn preferred_scale(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        scale: u32,
+    ) {
+        info!("Preferred scale of lock surface: {}", scale);
+        self.rescale_surface(surface, scale);
+    }
+}
+
+impl TextInputHandler for SpellLock {
+    fn text_input_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+    ) {
+        trace!("Text input focus entered");
+        let Some(adapter) = self.adapter_for_surface(surface) else {
+            return;
+        };
+        if let Some(text_input) = self.text_input.as_mut() {
+            text_input.update(&adapter, true);
+        }
+    }
+
+    fn text_input_leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+    ) {
+        trace!("Text input focus left");
+        if let Some(text_input) = self.text_input.as_mut() {
+            text_input.disable();
+        }
+    }
+
+    fn text_input_done(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        changes: TextInputChanges,
+    ) {
+        if let Some(adapter) = self.adapter_for_surface(surface) {
+            changes.apply(&adapter);
+        }
+    }
 }
@@ -0,0 +1,260 @@
+use crate::{
+    SpellAssociatedNew, SpellRuntime,
+    configure::{MonitorSelector, WindowConf},
+    wayland_adapter::{SpellError, common::outputs_with_info},
+};
+use smithay_client_toolkit::{
+    delegate_output, delegate_registry,
+    output::{OutputHandler, OutputState},
+    reexports::{
+        calloop::EventLoop,
+        calloop_wayland_source::WaylandSource,
+        client::{
+            Connection, QueueHandle, globals::registry_queue_init, protocol::wl_output::WlOutput,
+        },
+    },
+    registry::{ProvidesRegistryState, RegistryState},
+    registry_handlers,
+};
+use std::{
+    cell::RefCell,
+    error::Error,
+    os::fd::{AsRawFd, BorrowedFd},
+    rc::Rc,
+};
+use tracing::{Level, info, span, warn};
+
+type WidgetFactory = Box<dyn FnMut(&str, WindowConf) -> Box<dyn SpellAssociatedNew>>;
+
+/// `SpellOutputs` creates instances of a widget on monitors and keeps them in sync
+/// with the monitors connected to the system. If the [`WindowConf`] is built with
+/// [`on_all_outputs`](crate::layer_properties::WindowConfBuilder::on_all_outputs),
+/// an instance is created on every monitor. Otherwise, a single instance is created
+/// on the monitor given in `WindowConf` (or any connected monitor if none is given)
+/// whenever that monitor is connected. Instances are created on plugging in a monitor
+/// and are destroyed when it is removed, so docking a laptop doesn't need a restart
+/// of the shell. Layer name of every instance is `layer_name-output_name`.
+///
+/// Instances don't have IPC sockets, as they come and go with the monitors. State
+/// shared by the instances can be set up in the `setup` callback of
+/// `invoke_spell_per_output` instead.
+///
+/// It is created by `invoke_spell_per_output` of types generated by
+/// [`generate_widgets`](crate::generate_widgets) and is run by [`cast_spell`](crate::cast_spell)
+/// like other widgets.
+///
+/// ## Example
+///
+/// ```rust
+/// use spell_framework::{cast_spell, generate_widgets, layer_properties::WindowConf};
+/// slint::include_modules!();
+/// generate_widgets![TopBar];
+///
+/// fn main() -> Result<(), Box<dyn std::error::Error>> {
+///     let window_conf = WindowConf::builder()
+///         .height(30)
+///         .on_all_outputs()
+///         .build()?;
+///     let bars = TopBarSpell::invoke_spell_per_output("top-bar", window_conf, |ui| {
+///         ui.set_title("Spell".into());
+///     });
+///     cast_spell!(outputs: bars)
+/// }
+/// ```
+pub struct SpellOutputs {
+    layer_name: String,
+    window_conf: WindowConf,
+    factory: WidgetFactory,
+    watcher: OutputWatcher,
+    // Output along with the layer name of the instance created on it.
+    instances: Vec<(WlOutput, String)>,
+    conn: Connection,
+    event_loop: Rc<RefCell<EventLoop<'static, OutputWatcher>>>,
+    span: span::Span,
+}
+
+impl std::fmt::Debug for SpellOutputs {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpellOutputs")
+            .field("layer_name", &self.layer_name)
+            .field("instances", &self.instances)
+            .finish()
+    }
+}
+
+impl SpellOutputs {
+    /// Creates an instance of `SpellOutputs`. `factory` is called with the layer
+    /// name and the [`WindowConf`] (with the monitor set) of every instance to be
+    /// created. It is used internally by [`generate_widgets`](crate::generate_widgets),
+    /// prefer `invoke_spell_per_output` of generated types over this function. An
+    /// error is returned if the outputs of the compositor can't be watched.
+    pub fn new<F>(layer_name: &str, window_conf: WindowConf, factory: F) -> Result<Self, SpellError>
+    where
+        F: FnMut(&str, WindowConf) -> Box<dyn SpellAssociatedNew> + 'static,
+    {
+        let conn = Connection::connect_to_env().map_err(SpellError::Connect)?;
+        let (globals, mut event_queue) =
+            registry_queue_init(&conn).map_err(SpellError::Registry)?;
+        let qh: QueueHandle<OutputWatcher> = event_queue.handle();
+        let event_loop: EventLoop<'static, OutputWatcher> =
+            EventLoop::try_new().map_err(SpellError::EventLoop)?;
+        let mut watcher = OutputWatcher {
+            registry_state: RegistryState::new(&globals),
+            output_state: OutputState::new(&globals, &qh),
+            changes: Vec::new(),
+        };
+        // Outputs present at the start are reported as added here.
+        if let Err(err) = event_queue.roundtrip(&mut watcher) {
+            warn!("Received roundtrip error: {}", err);
+        }
+        WaylandSource::new(conn.clone(), event_queue)
+            .insert(event_loop.handle())
+            .map_err(|err| SpellError::EventLoop(err.error))?;
+
+        Ok(SpellOutputs {
+            layer_name: layer_name.to_string(),
+            window_conf,
+            factory: Box::new(factory),
+            watcher,
+            instances: Vec::new(),
+            conn,
+            event_loop: Rc::new(RefCell::new(event_loop)),
+            span: span!(Level::INFO, "outputs", name = layer_name),
+        })
+    }
+
+    fn apply_changes(&mut self) {
+        let changes = std::mem::take(&mut self.watcher.changes);
+        for change in changes {
+            match change {
+                OutputChange::Added(output) => self.add_instance(output),
+                OutputChange::Removed(output) => self.remove_instance(&output),
+            }
+        }
+    }
+
+    fn add_instance(&mut self, output: WlOutput) {
+        let Some(output_name) = self
+            .watcher
+            .output_state
+            .info(&output)
+            .and_then(|info| info.name)
+        else {
+            warn!("Output without a name is ignored");
+            return;
+        };
+        if !self.window_conf.all_outputs {
+            let is_target = match &self.window_conf.monitor {
+                Some(MonitorSelector::Focused) | None => true,
+                Some(selector) => selector
+                    .resolve(&outputs_with_info(&self.watcher.output_state))
+                    .is_some_and(|(target, _)| *target == output),
+            };
+            if !is_target || !self.instances.is_empty() {
+                return;
+            }
+        }
+        let instance_name = format!("{}-{}", self.layer_name, output_name);
+        info!("Creating {} on output {}", instance_name, output_name);
+        let mut window_conf = self.window_conf.clone();
+        window_conf.monitor = Some(MonitorSelector::Name(output_name));
+        let widget = (self.factory)(&instance_name, window_conf);
+        SpellRuntime::new().add_boxed_widget(widget);
+        self.instances.push((output, instance_name));
+    }
+
+    fn remove_instance(&mut self, output: &WlOutput) {
+        if let Some(index) = self.instances.iter().position(|(out, _)| out == output) {
+            let (_, instance_name) = self.instances.remove(index);
+            info!("Removing {} as its output is destroyed", instance_name);
+            SpellRuntime::new().remove_widget(&instance_name);
+        }
+        // A single instance moves to another connected monitor if possible.
+        if !self.window_conf.all_outputs && self.instances.is_empty() {
+            let outputs: Vec<WlOutput> = self
+                .watcher
+                .output_state
+                .outputs()
+                .filter(|out| out != output)
+                .collect();
+            for out in outputs {
+                self.add_instance(out);
+            }
+        }
+    }
+}
+
+impl SpellAssociatedNew for SpellOutputs {
+    fn on_call(&mut self) -> Result<(), Box<dyn Error>> {
+        let event_loop = self.event_loop.clone();
+        event_loop
+            .borrow_mut()
+            .dispatch(std::time::Duration::ZERO, &mut self.watcher)?;
+        self.apply_changes();
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn event_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: The event loop is owned by `self` and lives as long as it.
+        unsafe { BorrowedFd::borrow_raw(self.event_loop.borrow().as_raw_fd()) }
+    }
+
+    fn get_span(&self) -> span::Span {
+        self.span.clone()
+    }
+
+    fn layer_name(&self) -> Option<&str> {
+        Some(&self.layer_name)
+    }
+}
+
+impl Drop for SpellOutputs {
+    fn drop(&mut self) {
+        for (_, instance_name) in self.instances.drain(..) {
+            SpellRuntime::new().remove_widget(&instance_name);
+        }
+    }
+}
+
+enum OutputChange {
+    Added(WlOutput),
+    Removed(WlOutput),
+}
+
+/// Wayland state of [`SpellOutputs`], it only listens to the outputs.
+struct OutputWatcher {
+    registry_state: RegistryState,
+    output_state: OutputState,
+    changes: Vec<OutputChange>,
+}
+
+impl ProvidesRegistryState for OutputWatcher {
+    fn registry(&mut self) -> &mut RegistryState {
+        &mut self.registry_state
+    }
+    registry_handlers![OutputState];
+}
+
+impl OutputHandler for OutputWatcher {
+    fn output_state(&mut self) -> &mut OutputState {
+        &mut self.output_state
+    }
+
+    fn new_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, output: WlOutput) {
+        info!("New output source added");
+        self.changes.push(OutputChange::Added(output));
+    }
+
+    fn update_output(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _output: WlOutput) {
+        info!("Updated output source");
+    }
+
+    fn output_destroyed(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, output: WlOutput) {
+        info!("Output is destroyed");
+        self.changes.push(OutputChange::Removed(output));
+    }
+}
+
+delegate_output!(OutputWatcher);
+delegate_registry!(OutputWatcher);
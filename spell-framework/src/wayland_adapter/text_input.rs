@@ -0,0 +1,316 @@
+// This module implements the text-input-v3 protocol, through which the compositor's
+// input method (IME) sends composed text to the widgets. Requests are made when
+// slint asks for an input method on focusing a `TextInput`.
+use std::{ops::Range, sync::Mutex};
+
+use i_slint_core::{
+    input::{KeyEvent, KeyEventType},
+    items::InputType,
+    window::{InputMethodProperties, WindowInner},
+};
+use smithay_client_toolkit::globals::GlobalData;
+use smithay_client_toolkit::reexports::{
+    client::{
+        Connection, Dispatch, Proxy, QueueHandle,
+        globals::{BindError, GlobalList},
+        protocol::{wl_seat::WlSeat, wl_surface::WlSurface},
+    },
+    protocols::wp::text_input::zv3::client::{
+        zwp_text_input_manager_v3::ZwpTextInputManagerV3,
+        zwp_text_input_v3::{ContentHint, ContentPurpose, Event, ZwpTextInputV3},
+    },
+};
+use tracing::trace;
+
+use crate::slint_adapter::SpellSkiaWinAdapter;
+
+/// Maximum length of the surrounding text in bytes, as required by the protocol.
+const MAX_SURROUNDING_TEXT: usize = 4000;
+
+#[derive(Debug)]
+pub struct TextInputState {
+    manager: ZwpTextInputManagerV3,
+}
+
+/// An owned instance of ZwpTextInputV3 for a seat, when this is dropped, the underlying
+/// interface is destroyed.
+#[derive(Debug)]
+pub struct TextInput {
+    text_input: ZwpTextInputV3,
+    /// Whether the text input is enabled on the focused surface.
+    enabled: bool,
+}
+
+/// Changes sent by the input method, applied at once on `done` event.
+#[derive(Debug, Default)]
+pub struct TextInputChanges {
+    /// Preedit string along with the begin and end of its cursor in bytes.
+    pub preedit: Option<(String, i32, i32)>,
+    /// Text to be inserted at the cursor.
+    pub commit: Option<String>,
+    /// Bytes to be deleted before and after the cursor.
+    pub delete_surrounding: (u32, u32),
+}
+
+/// User data of ZwpTextInputV3, storing the focused surface and changes which are
+/// yet to be applied.
+#[derive(Debug, Default)]
+pub struct TextInputData {
+    focus: Mutex<Option<WlSurface>>,
+    pending: Mutex<TextInputChanges>,
+}
+
+impl TextInputState {
+    pub(crate) fn bind<State>(
+        globals: &GlobalList,
+        queue_handle: &QueueHandle<State>,
+    ) -> Result<Self, BindError>
+    where
+        State: Dispatch<ZwpTextInputManagerV3, GlobalData, State> + TextInputHandler + 'static,
+    {
+        let manager = globals.bind(queue_handle, 1..=1, GlobalData)?;
+        Ok(TextInputState { manager })
+    }
+
+    pub(crate) fn get_text_input<State>(
+        &self,
+        seat: &WlSeat,
+        queue_handle: &QueueHandle<State>,
+    ) -> TextInput
+    where
+        State: Dispatch<ZwpTextInputV3, TextInputData> + 'static,
+    {
+        TextInput {
+            text_input: self
+                .manager
+                .get_text_input(seat, queue_handle, TextInputData::default()),
+            enabled: false,
+        }
+    }
+}
+
+impl TextInput {
+    /// Surface which has the text input focus.
+    pub(crate) fn focus(&self) -> Option<WlSurface> {
+        self.text_input
+            .data::<TextInputData>()
+            .and_then(|data| data.focus.lock().unwrap().clone())
+    }
+
+    /// Enables, updates or disables the text input as per the input method requests
+    /// of slint for the focused surface. The state is sent again if `force` is set,
+    /// which is needed after the surface gains focus.
+    pub(crate) fn update(&mut self, adapter: &SpellSkiaWinAdapter, force: bool) {
+        if !adapter.input_method_changed.replace(false) && !force {
+            return;
+        }
+        match adapter.input_method.borrow().as_ref() {
+            Some(properties) => {
+                if !self.enabled {
+                    trace!("Enabling text input");
+                    self.text_input.enable();
+                    self.enabled = true;
+                }
+                self.set_state(properties);
+                self.text_input.commit();
+            }
+            None => self.disable(),
+        }
+    }
+
+    /// Disables the text input, done when slint no longer needs an input method or
+    /// the surface loses focus.
+    pub(crate) fn disable(&mut self) {
+        if self.enabled {
+            trace!("Disabling text input");
+            self.text_input.disable();
+            self.text_input.commit();
+            self.enabled = false;
+        }
+    }
+
+    fn set_state(&self, properties: &InputMethodProperties) {
+        // Longer text is not sent at all, as the input methods only need the text
+        // around the cursor.
+        if properties.text.len() <= MAX_SURROUNDING_TEXT {
+            let anchor = properties
+                .anchor_position
+                .unwrap_or(properties.cursor_position);
+            self.text_input.set_surrounding_text(
+                properties.text.to_string(),
+                properties.cursor_position as i32,
+                anchor as i32,
+            );
+        }
+        let (hint, purpose) = match properties.input_type {
+            InputType::Password => (
+                ContentHint::HiddenText | ContentHint::SensitiveData,
+                ContentPurpose::Password,
+            ),
+            InputType::Number => (ContentHint::None, ContentPurpose::Digits),
+            InputType::Decimal => (ContentHint::None, ContentPurpose::Number),
+            _ => (ContentHint::None, ContentPurpose::Normal),
+        };
+        self.text_input.set_content_type(hint, purpose);
+        self.text_input.set_cursor_rectangle(
+            properties.cursor_rect_origin.x as i32,
+            properties.cursor_rect_origin.y as i32,
+            properties.cursor_rect_size.width as i32,
+            properties.cursor_rect_size.height as i32,
+        );
+    }
+}
+
+impl Drop for TextInput {
+    fn drop(&mut self) {
+        self.text_input.destroy();
+    }
+}
+
+impl TextInputChanges {
+    /// Forwards the changes to the focused text input of slint. Deleted text and
+    /// committed text are sent as a single commit, with the deletion as the range
+    /// of text to be replaced, followed by the new preedit string.
+    pub(crate) fn apply(self, adapter: &SpellSkiaWinAdapter) {
+        let (before, after) = self.delete_surrounding;
+        if self.commit.is_some() || before > 0 || after > 0 {
+            commit_composition(
+                &adapter.window,
+                self.commit.unwrap_or_default(),
+                (before > 0 || after > 0).then_some(-(before as i32)..after as i32),
+            );
+        }
+        // A missing preedit string clears the current one.
+        let (preedit, cursor_begin, cursor_end) = self.preedit.unwrap_or_default();
+        update_composition(
+            &adapter.window,
+            preedit,
+            (cursor_begin >= 0 && cursor_end >= 0).then_some(cursor_begin..cursor_end),
+        );
+    }
+}
+
+// Slint has no public API for input method composition, so the two functions below
+// send it through `WindowInner::process_key_input` of i-slint-core. It is internal
+// to slint and changes without notice, which is why it is used nowhere else. It is
+// only known to work with the slint revision pinned in the workspace manifest, so
+// these functions have to be checked again whenever that revision is bumped.
+
+/// Commits the text to the focused text input of slint, replacing the given range
+/// around the cursor (in bytes) if any.
+fn commit_composition(window: &slint::Window, text: String, replacement_range: Option<Range<i32>>) {
+    WindowInner::from_pub(window).process_key_input(KeyEvent {
+        event_type: KeyEventType::CommitComposition,
+        text: text.into(),
+        replacement_range,
+        ..Default::default()
+    });
+}
+
+/// Replaces the preedit text of the focused text input of slint, with the given
+/// range of it (in bytes) selected if any.
+fn update_composition(window: &slint::Window, preedit: String, selection: Option<Range<i32>>) {
+    WindowInner::from_pub(window).process_key_input(KeyEvent {
+        event_type: KeyEventType::UpdateComposition,
+        preedit_text: preedit.into(),
+        preedit_selection: selection,
+        ..Default::default()
+    });
+}
+
+impl<D> Dispatch<ZwpTextInputManagerV3, GlobalData, D> for TextInputState
+where
+    D: Dispatch<ZwpTextInputManagerV3, GlobalData> + TextInputHandler + 'static,
+{
+    fn event(
+        _: &mut D,
+        _: &ZwpTextInputManagerV3,
+        _: <ZwpTextInputManagerV3 as Proxy>::Event,
+        _: &GlobalData,
+        _: &Connection,
+        _: &QueueHandle<D>,
+    ) {
+        unreachable!("ZwpTextInputManagerV3 has no events")
+    }
+}
+
+impl<D> Dispatch<ZwpTextInputV3, TextInputData, D> for TextInputState
+where
+    D: Dispatch<ZwpTextInputV3, TextInputData> + TextInputHandler + 'static,
+{
+    fn event(
+        state: &mut D,
+        _: &ZwpTextInputV3,
+        event: <ZwpTextInputV3 as Proxy>::Event,
+        data: &TextInputData,
+        conn: &Connection,
+        qh: &QueueHandle<D>,
+    ) {
+        match event {
+            Event::Enter { surface } => {
+                *data.focus.lock().unwrap() = Some(surface.clone());
+                state.text_input_enter(conn, qh, &surface);
+            }
+            Event::Leave { surface } => {
+                *data.focus.lock().unwrap() = None;
+                *data.pending.lock().unwrap() = TextInputChanges::default();
+                state.text_input_leave(conn, qh, &surface);
+            }
+            Event::PreeditString {
+                text,
+                cursor_begin,
+                cursor_end,
+            } => {
+                data.pending.lock().unwrap().preedit =
+                    Some((text.unwrap_or_default(), cursor_begin, cursor_end));
+            }
+            Event::CommitString { text } => {
+                data.pending.lock().unwrap().commit = text;
+            }
+            Event::DeleteSurroundingText {
+                before_length,
+                after_length,
+            } => {
+                data.pending.lock().unwrap().delete_surrounding = (before_length, after_length);
+            }
+            Event::Done { .. } => {
+                let changes = std::mem::take(&mut *data.pending.lock().unwrap());
+                let focus = data.focus.lock().unwrap().clone();
+                if let Some(surface) = focus {
+                    state.text_input_done(conn, qh, &surface, changes);
+                }
+            }
+            _ => unreachable!("ZwpTextInputV3 has no other events"),
+        }
+    }
+}
+
+pub trait TextInputHandler: Sized {
+    /// Called when a surface gains the text input focus, the state of the text input
+    /// needs to be sent again.
+    fn text_input_enter(&mut self, conn: &Connection, qh: &QueueHandle<Self>, surface: &WlSurface);
+
+    /// Called when a surface loses the text input focus.
+    fn text_input_leave(&mut self, conn: &Connection, qh: &QueueHandle<Self>, surface: &WlSurface);
+
+    /// Called with the changes sent by the input method for the focused surface.
+    fn text_input_done(
+        &mut self,
+        conn: &Connection,
+        qh: &QueueHandle<Self>,
+        surface: &WlSurface,
+        changes: TextInputChanges,
+    );
+}
+
+macro_rules! delegate_text_input {
+    ($(@<$( $lt:tt $( : $clt:tt $(+ $dlt:tt )* )? ),+>)? $ty: ty) => {
+            smithay_client_toolkit::reexports::client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay_client_toolkit::reexports::protocols::wp::text_input::zv3::client::zwp_text_input_manager_v3::ZwpTextInputManagerV3: smithay_client_toolkit::globals::GlobalData
+        ] => $crate::wayland_adapter::text_input::TextInputState);
+        smithay_client_toolkit::reexports::client::delegate_dispatch!($(@< $( $lt $( : $clt $(+ $dlt )* )? ),+ >)? $ty: [
+            smithay_client_toolkit::reexports::protocols::wp::text_input::zv3::client::zwp_text_input_v3::ZwpTextInputV3: $crate::wayland_adapter::text_input::TextInputData
+        ] => $crate::wayland_adapter::text_input::TextInputState);
+    };
+}
+pub(crate) use delegate_text_input;
@@ -1,32 +1,37 @@
 use crate::{
     PopupSlint, SpellAssociatedNew,
-    configure::{Dimension, HomeHandle, PopupConf, WindowConf, set_up_tracing},
-    slint_adapter::{ADAPTERS, SpellLayerShell, SpellSkiaWinAdapter},
+    configure::{Dimension, HomeHandle, MonitorSelector, PopupConf, WindowConf, set_up_tracing},
+    slint_adapter::{ADAPTERS, SLINT_EVENTS, SpellLayerShell, SpellSkiaWinAdapter},
     wayland_adapter::{
-        common::PointerState,
+        clipboard::ClipboardState,
+        common::{KeyRepeat, PointerState, outputs_with_info},
+        cursor::ThemedCursor,
+        data_device::{DndState, DropEvent, MimeData},
         fractional_scaling::{FractionalScaleState, delegate_fractional_scale},
+        gestures::{
+            Gesture, PointerGestures, PointerGesturesState, TouchTracker, delegate_pointer_gestures,
+        },
+        text_input::{TextInput, TextInputState, delegate_text_input},
         viewporter::{Viewport, ViewporterState, delegate_viewporter},
         window,
     },
 };
-use i_slint_core::items::MouseCursor;
 use smithay_client_toolkit::{
     compositor::{CompositorState, Region},
-    delegate_compositor, delegate_keyboard, delegate_layer, delegate_output, delegate_pointer,
-    delegate_registry, delegate_seat, delegate_shm, delegate_touch, delegate_xdg_popup,
-    delegate_xdg_shell,
+    data_device_manager::{DataDeviceManagerState, data_device::DataDevice},
+    delegate_compositor, delegate_data_device, delegate_keyboard, delegate_layer, delegate_output,
+    delegate_pointer, delegate_primary_selection, delegate_registry, delegate_seat, delegate_shm,
+    delegate_touch, delegate_xdg_popup, delegate_xdg_shell,
     output::OutputState,
+    primary_selection::{PrimarySelectionManagerState, device::PrimarySelectionDevice},
     reexports::{
         calloop::{self, EventLoop, LoopHandle},
         calloop_wayland_source::WaylandSource,
         client::{
-            Connection, QueueHandle,
-            globals::registry_queue_init,
+            ConnectError, Connection, QueueHandle,
+            globals::{BindError, GlobalError, registry_queue_init},
             protocol::{
-                wl_keyboard::WlKeyboard,
-                wl_output::{self, WlOutput},
-                wl_shm,
-                wl_surface::WlSurface,
+                wl_data_device_manager::DndAction, wl_keyboard::WlKeyboard, wl_output::WlOutput,
                 wl_touch::WlTouch,
             },
         },
@@ -35,34 +40,79 @@ use smithay_client_toolkit::{
     seat::{SeatState, pointer::cursor_shape::CursorShapeManager},
     shell::{
         WaylandSurface,
-        wlr_layer::{KeyboardInteractivity, LayerShell, LayerSurface},
+        wlr_layer::{Anchor, KeyboardInteractivity, Layer, LayerShell, LayerSurface},
         xdg::XdgShell,
     },
-    shm::{
-        Shm,
-        slot::{Buffer, SlotPool},
-    },
+    shm::{Shm, slot::SlotPool},
 };
 use std::{
     cell::{Cell, RefCell},
-    collections::HashMap,
-    os::unix::net::UnixListener,
+    os::{
+        fd::{AsRawFd, BorrowedFd},
+        unix::net::UnixListener,
+    },
     rc::Rc,
-    sync::{Once, OnceLock, RwLock},
+    sync::Once,
 };
 use tracing::{Level, info, span, trace, warn};
 
+mod board;
 mod input;
 mod internal;
 mod popup;
 mod wayland;
+pub use board::{BoardHandle, BoardModifiers, SpellBoard};
 pub use popup::SpellXDGPopup;
 
-#[allow(clippy::type_complexity)]
-static AVAILABLE_MONITORS: OnceLock<RwLock<HashMap<String, (wl_output::WlOutput, i32, i32)>>> =
-    OnceLock::new();
 static SET_SLINT_PLATFORM: Once = Once::new();
 
+/// Error returned when a widget can't be created. Protocols which are optional for
+/// a widget (like fractional scaling or cursor shapes) never cause it, the widget
+/// falls back to a simpler behaviour without them.
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum SpellError {
+    /// Couldn't connect to the wayland compositor.
+    Connect(ConnectError),
+    /// The globals of the compositor couldn't be fetched, or the session couldn't
+    /// be locked as ext-session-lock isn't supported.
+    Registry(GlobalError),
+    /// The compositor doesn't support a global required by the widget.
+    MissingGlobal {
+        /// Interface name of the missing global.
+        name: &'static str,
+        /// Error received on binding the global.
+        source: BindError,
+    },
+    /// The event loop couldn't be created or the wayland connection couldn't be
+    /// inserted in it.
+    EventLoop(calloop::Error),
+}
+
+impl std::fmt::Display for SpellError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SpellError::Connect(err) => write!(f, "Couldn't connect to compositor: {}", err),
+            SpellError::Registry(err) => write!(f, "Couldn't fetch globals: {}", err),
+            SpellError::MissingGlobal { name, source } => {
+                write!(f, "{} is not available: {}", name, source)
+            }
+            SpellError::EventLoop(err) => write!(f, "Couldn't set up the event loop: {}", err),
+        }
+    }
+}
+
+impl std::error::Error for SpellError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SpellError::Connect(err) => Some(err),
+            SpellError::Registry(err) => Some(err),
+            SpellError::MissingGlobal { source, .. } => Some(source),
+            SpellError::EventLoop(err) => Some(err),
+        }
+    }
+}
+
 #[derive(Debug)]
 struct States {
     registry_state: RegistryState,
@@ -73,8 +123,21 @@ struct States {
     keyboard_state: Option<WlKeyboard>,
     touch_state: Option<WlTouch>,
     shm: Shm,
-    viewporter_state: ViewporterState,
-    fractional_scale_state: FractionalScaleState,
+    /// Fractional scaling needs both viewporter and fractional scale, integer
+    /// buffer scales are used in their absence.
+    viewporter_state: Option<ViewporterState>,
+    fractional_scale_state: Option<FractionalScaleState>,
+    /// Not every compositor supports text input, IME is unavailable without it.
+    text_input_state: Option<TextInputState>,
+    text_input: Option<TextInput>,
+    /// Touchpad gestures are only received if the compositor supports pointer-gestures.
+    pointer_gestures_state: Option<PointerGesturesState>,
+    pointer_gestures: Option<PointerGestures>,
+    /// Drag and drop is unavailable without data device manager.
+    data_device_manager: Option<DataDeviceManagerState>,
+    data_device: Option<DataDevice>,
+    primary_selection_manager: Option<PrimarySelectionManagerState>,
+    primary_selection_device: Option<PrimarySelectionDevice>,
 }
 
 /// `SpellWin` is the main type for implementing widgets, it covers various properties
@@ -89,19 +152,34 @@ pub struct SpellWin {
     /// Span required for proper logging.
     pub span: span::Span,
     queue: QueueHandle<SpellWin>,
-    buffer: Option<Buffer>,
     states: States,
     layer: Option<LayerSurface>,
     first_configure: Cell<bool>,
+    /// Set once the compositor configures the layer surface, buffers can't be
+    /// attached before that.
+    is_configured: Cell<bool>,
     natural_scroll: bool,
     is_hidden: Cell<bool>,
     config: WindowConf,
     input_region: Region,
     opaque_region: Region,
     viewport: Option<Viewport>,
-    xdg_shell: XdgShell,
+    /// Output on which the layer is created along with its logical size.
+    output: Option<(WlOutput, i32, i32)>,
+    /// Popups can't be created without xdg shell.
+    xdg_shell: Option<XdgShell>,
+    layer_shell: LayerShell,
     popup_manager: window::popup::PopupManager,
     event_loop: Rc<RefCell<EventLoop<'static, SpellWin>>>,
+    conn: Connection,
+    key_repeat: KeyRepeat,
+    touch: TouchTracker,
+    gesture_callback: Option<Box<dyn FnMut(Gesture)>>,
+    dnd: DndState,
+    clipboard: ClipboardState,
+    /// Set when the widget is a [`SpellBoard`].
+    board: Option<board::Board>,
+    log_timer: slint::Timer,
 }
 
 impl std::fmt::Debug for SpellWin {
@@ -121,29 +199,65 @@ impl SpellWin {
         mut window_conf: WindowConf,
         layer_name: String,
         handle: HomeHandle,
-    ) -> Self {
-        let (globals, mut event_queue) = registry_queue_init(conn).unwrap();
+    ) -> Result<Self, SpellError> {
+        let (globals, mut event_queue) = registry_queue_init(conn).map_err(SpellError::Registry)?;
         let qh: QueueHandle<SpellWin> = event_queue.handle();
         let compositor =
-            CompositorState::bind(&globals, &qh).expect("wl_compositor is not available");
+            CompositorState::bind(&globals, &qh).map_err(|source| SpellError::MissingGlobal {
+                name: "wl_compositor",
+                source,
+            })?;
         let event_loop: EventLoop<'static, SpellWin> =
-            EventLoop::try_new().expect("Failed to initialize the event loop!");
-        let layer_shell = LayerShell::bind(&globals, &qh).expect("layer shell is not available");
-        let shm = Shm::bind(&globals, &qh).expect("wl_shm is not available");
-        let cursor_manager =
-            CursorShapeManager::bind(&globals, &qh).expect("cursor shape is not available");
+            EventLoop::try_new().map_err(SpellError::EventLoop)?;
+        let layer_shell =
+            LayerShell::bind(&globals, &qh).map_err(|source| SpellError::MissingGlobal {
+                name: "zwlr_layer_shell_v1",
+                source,
+            })?;
+        let shm = Shm::bind(&globals, &qh).map_err(|source| SpellError::MissingGlobal {
+            name: "wl_shm",
+            source,
+        })?;
+        let cursor_manager = CursorShapeManager::bind(&globals, &qh)
+            .inspect_err(|err| info!("Cursor shape is not available: {}", err))
+            .ok();
         let surface = compositor.create_surface(&qh);
-        let viewporter_state =
-            ViewporterState::bind(&globals, &qh).expect("Couldn't set viewporter");
-        let fractional_scale_state: FractionalScaleState =
-            FractionalScaleState::bind(&globals, &qh).expect("Fractional Scale couldn't be set");
-        let xdg_shell = XdgShell::bind(&globals, &qh).expect("Couldn't bind xdg_shell");
+        // Without viewporter, fractional scales can't be applied to the surface and
+        // integer buffer scales are used instead.
+        let viewporter_state = ViewporterState::bind(&globals, &qh)
+            .inspect_err(|err| info!("Viewporter is not available: {}", err))
+            .ok();
+        let fractional_scale_state = FractionalScaleState::bind(&globals, &qh)
+            .inspect_err(|err| info!("Fractional scale is not available: {}", err))
+            .ok();
+        let xdg_shell = XdgShell::bind(&globals, &qh)
+            .inspect_err(|err| info!("Xdg shell is not available: {}", err))
+            .ok();
+        let text_input_state = TextInputState::bind(&globals, &qh)
+            .inspect_err(|err| info!("Text input is not available: {}", err))
+            .ok();
+        let pointer_gestures_state = PointerGesturesState::bind(&globals, &qh)
+            .inspect_err(|err| info!("Pointer gestures are not available: {}", err))
+            .ok();
+        let data_device_manager = DataDeviceManagerState::bind(&globals, &qh)
+            .inspect_err(|err| info!("Data device manager is not available: {}", err))
+            .ok();
+        let primary_selection_manager = PrimarySelectionManagerState::bind(&globals, &qh)
+            .inspect_err(|err| info!("Primary selection is not available: {}", err))
+            .ok();
+        // Theme is only loaded when the compositor can't draw the cursors itself.
+        let themed_cursor = cursor_manager
+            .is_none()
+            .then(|| ThemedCursor::load(conn, &compositor, &shm, &qh))
+            .flatten();
         let pointer_state = PointerState {
             pointer: None,
             pointer_data: None,
             cursor_shape: cursor_manager,
-            current_wayland_cursor: MouseCursor::Default,
+            themed_cursor,
+            current_wayland_cursor: None,
             last_cursor_enter_serial: None,
+            last_press: None,
         };
         let input_region = Region::new(&compositor).expect("Couldn't create region");
         let opaque_region = Region::new(&compositor).expect("Couldn't create opaque region");
@@ -153,7 +267,6 @@ impl SpellWin {
             loop_handle: event_loop.handle(),
             ipc_handler: None,
             queue: qh.clone(),
-            buffer: None,
             states: States {
                 registry_state: RegistryState::new(&globals),
                 seat_state: SeatState::new(&globals, &qh),
@@ -165,9 +278,18 @@ impl SpellWin {
                 shm,
                 viewporter_state,
                 fractional_scale_state,
+                text_input_state,
+                text_input: None,
+                pointer_gestures_state,
+                pointer_gestures: None,
+                data_device_manager,
+                data_device: None,
+                primary_selection_manager,
+                primary_selection_device: None,
             },
             layer: None,
             first_configure: Cell::new(true),
+            is_configured: Cell::new(false),
             natural_scroll: window_conf.natural_scroll,
             is_hidden: Cell::new(false),
             config: window_conf.clone(),
@@ -175,68 +297,46 @@ impl SpellWin {
             input_region,
             opaque_region,
             viewport: None,
+            output: None,
             xdg_shell,
+            layer_shell,
             popup_manager: window::popup::PopupManager::new(),
             event_loop: Rc::new(RefCell::new(event_loop)),
+            conn: conn.clone(),
+            key_repeat: KeyRepeat::default(),
+            touch: TouchTracker::default(),
+            gesture_callback: None,
+            dnd: DndState::default(),
+            clipboard: ClipboardState::default(),
+            board: None,
+            log_timer: slint::Timer::default(),
             span: span!(Level::INFO, "widget", name = layer_name.as_str(),),
         };
 
-        if AVAILABLE_MONITORS.get().is_none() {
-            match SpellWin::get_available_monitors(&mut event_queue, &mut win) {
-                Some(monitors) => {
-                    let _ = AVAILABLE_MONITORS.get_or_init(|| RwLock::new(monitors));
-                }
-                None => warn!("Failed to get available monitors"),
-            }
+        // Monitors are fetched for every window so that the ones plugged in after
+        // the start of the program are also known.
+        let monitors = SpellWin::get_available_monitors(&mut event_queue, &mut win);
+        if monitors.is_none() {
+            warn!("Failed to get available monitors");
         }
-
-        let mut output_info: Option<(wl_output::WlOutput, i32, i32)> =
-            if let Some(name) = &window_conf.monitor_name {
-                let output = AVAILABLE_MONITORS
-                    .get()
-                    .and_then(|monitors| monitors.read().ok())
-                    .and_then(|monitors| monitors.get(name).cloned());
+        win.output = match &window_conf.monitor {
+            Some(MonitorSelector::Focused) | None => None,
+            Some(selector) => {
+                let output = monitors.as_ref().and_then(|monitors| {
+                    let (output, info) = selector.resolve(monitors)?;
+                    let (width, height) = info.logical_size?;
+                    Some((output.clone(), width, height))
+                });
                 if output.is_none() {
-                    warn!("Monitor '{}' not found, using default monitor", name);
+                    warn!("Monitor {:?} not found, using default monitor", selector);
                 }
                 output
-            } else {
-                None
-            };
-
-        match window_conf.width {
-            Dimension::Pixel(x) => window_conf.evaluated_width = x,
-            Dimension::Full => {
-                window_conf.evaluated_width = output_info
-                    .as_ref()
-                    .expect("Output info couldn't be retrieved")
-                    .1 as u32
             }
-            Dimension::Percentage(y) => {
-                window_conf.evaluated_width = output_info
-                    .as_mut()
-                    .expect("Output info couldn't be retrieved")
-                    .1 as u32
-                    / y;
-            }
-        }
+        };
 
-        match window_conf.height {
-            Dimension::Pixel(x) => window_conf.evaluated_height = x,
-            Dimension::Full => {
-                window_conf.evaluated_height = output_info
-                    .as_ref()
-                    .expect("Output info couldn't be retrieved")
-                    .1 as u32
-            }
-            Dimension::Percentage(y) => {
-                window_conf.evaluated_height = output_info
-                    .as_ref()
-                    .expect("Output info couldn't be retrieved")
-                    .1 as u32
-                    / y;
-            }
-        }
+        let (width, height) = win.evaluate_size(&window_conf.width, &window_conf.height);
+        window_conf.evaluated_width = width;
+        window_conf.evaluated_height = height;
         win.config = window_conf.clone();
 
         info!(
@@ -244,8 +344,9 @@ impl SpellWin {
             window_conf.evaluated_width, window_conf.evaluated_height
         );
 
-        let mut pool = SlotPool::new(
-            (window_conf.evaluated_width * window_conf.evaluated_height * 4) as usize,
+        // Pool is sized for two buffers of the swapchain, it grows if more are needed.
+        let pool = SlotPool::new(
+            (window_conf.evaluated_width * window_conf.evaluated_height * 4 * 2) as usize,
             &win.states.shm,
         )
         .expect("Failed to create pool");
@@ -256,33 +357,20 @@ impl SpellWin {
             window_conf.evaluated_height as i32,
         );
 
-        let stride = window_conf.evaluated_width as i32 * 4;
-        let (way_pri_buffer, _) = pool
-            .create_buffer(
-                window_conf.evaluated_width as i32,
-                window_conf.evaluated_height as i32,
-                stride,
-                wl_shm::Format::Argb8888,
-            )
-            .expect("Creating Buffer");
-
-        let primary_slot = way_pri_buffer.slot();
         let adapter_value: Rc<SpellSkiaWinAdapter> = SpellSkiaWinAdapter::new(
             Rc::new(RefCell::new(pool)),
-            RefCell::new(primary_slot),
             window_conf.evaluated_width,
             window_conf.evaluated_height,
         );
         // win.popup_manager.set_pool(pool_mut.clone());
         win.adapter = Some(adapter_value.clone());
-        win.buffer = Some(way_pri_buffer);
-
-        let (slint_event_sender, slint_event_receiver) =
-            calloop::channel::channel::<Box<dyn FnOnce() + Send>>();
 
         ADAPTERS.with_borrow_mut(|v| v.push(adapter_value.clone()));
         SET_SLINT_PLATFORM.call_once(|| {
             trace!("Slint platform set");
+            let (slint_event_sender, slint_event_receiver) =
+                calloop::channel::channel::<Box<dyn FnOnce() + Send>>();
+            SLINT_EVENTS.with_borrow_mut(|events| *events = Some(slint_event_receiver));
             if let Err(err) =
                 slint::platform::set_platform(Box::new(SpellLayerShell::new(slint_event_sender)))
             {
@@ -290,41 +378,20 @@ impl SpellWin {
             }
         });
         win.adapter = Some(adapter_value);
-        let target_output: Option<&WlOutput> = output_info.as_ref().map(|(a, _, _)| a);
-        let layer = layer_shell.create_layer_surface(
-            &qh,
-            surface,
-            window_conf.layer_type,
-            Some(layer_name.clone()),
-            target_output,
-        );
-
-        win.layer = Some(layer);
-        win.set_config_internal();
+        win.create_layer(surface);
 
         if let Err(err) = event_queue.roundtrip(&mut win) {
             warn!("Received roundtrip error: {}", err);
         }
-        let surface: &WlSurface = win.layer.as_ref().unwrap().wl_surface();
-
-        // This needs to occur after layer creation so as to ensure that layer
-        // used in window is not null during use to scale. Details in issue 34.
-        let fractional_scale = win.states.fractional_scale_state.get_scale(surface, &qh);
-        let viewport = win
-            .states
-            .viewporter_state
-            .get_viewport(surface, &qh, fractional_scale);
-        win.viewport = Some(viewport);
-
-        win.layer.as_ref().unwrap().commit();
-        win.set_event_sources(handle, slint_event_receiver);
+        win.set_event_sources(handle);
+        win.watch_config_file();
 
         info!("Win: {} layer created successfully.", layer_name);
 
         WaylandSource::new(conn.clone(), event_queue)
             .insert(win.loop_handle.clone())
-            .unwrap();
-        win
+            .map_err(|err| SpellError::EventLoop(err.error))?;
+        Ok(win)
     }
 
     /// Returns a handle of [`WinHandle`] to invoke wayland specific features.
@@ -339,11 +406,18 @@ impl SpellWin {
     /// # Panics
     ///
     /// This function needs to be called "before" initialising your slint window to avoid
-    /// panicing of this function.
+    /// panicing of this function. It also panics if the compositor lacks a global
+    /// required by the widget, use [`SpellWin::try_invoke_spell`] to handle that case.
     pub fn invoke_spell(name: &str, window_conf: WindowConf) -> Self {
+        SpellWin::try_invoke_spell(name, window_conf).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    /// Same as [`SpellWin::invoke_spell`], but returns an error instead of panicking
+    /// when the widget can't be created on the compositor.
+    pub fn try_invoke_spell(name: &str, window_conf: WindowConf) -> Result<Self, SpellError> {
         let handle = set_up_tracing(name);
-        let conn = Connection::connect_to_env().unwrap();
-        SpellWin::create_window(&conn, window_conf.clone(), name.to_string(), handle)
+        let conn = Connection::connect_to_env().map_err(SpellError::Connect)?;
+        SpellWin::create_window(&conn, window_conf, name.to_string(), handle)
     }
 
     /// Hides the layer (aka the widget) if it is visible in screen.
@@ -351,6 +425,7 @@ impl SpellWin {
         if !self.is_hidden.replace(true) {
             info!("Win: Hiding window");
             self.layer.as_ref().unwrap().wl_surface().attach(None, 0, 0);
+            self.layer.as_ref().unwrap().commit();
         }
     }
 
@@ -358,7 +433,11 @@ impl SpellWin {
     pub fn show_again(&self) {
         if self.is_hidden.replace(false) {
             info!("Win: Showing window again");
+            // Frame callbacks are not sent for a surface without a buffer.
+            self.adapter.as_ref().unwrap().frame_pending.set(false);
             self.set_config_internal();
+            // The unmapped surface is mapped again only after a new configure.
+            self.is_configured.set(false);
             self.first_configure.set(true);
             self.layer.as_ref().unwrap().commit();
         }
@@ -377,8 +456,10 @@ impl SpellWin {
     /// This function adds specific rectangular regions of your complete layer to receive
     /// input events from pointer and/or touch. The coordinates are in surface local
     /// format from top left corener. By default, The whole layer is considered for input
-    /// events. Adding existing areas again as input region has no effect. This function
-    /// combined with transparent base widgets can be used to mimic resizable widgets.
+    /// events. Adding existing areas again as input region has no effect. To resize the
+    /// widget itself, use [`SpellWin::set_size`]. Input regions are kept on resizing,
+    /// except for the parts outside the new size. Area gained by growing the widget
+    /// receives input.
     pub fn add_input_region(&self, x: i32, y: i32, width: i32, height: i32) {
         info!(
             "Win: input region added: [x: {}, y: {}, width: {}, height: {}]",
@@ -474,6 +555,148 @@ impl SpellWin {
         self.layer.as_ref().unwrap().commit();
     }
 
+    /// Resizes the widget to the given width and height. Buffers are reallocated
+    /// and the slint window is resized with them, so that the widget actually
+    /// grows or shrinks instead of faking it with input regions. The input region
+    /// is reset to cover the whole widget and the opaque region is clipped to
+    /// the new size. `Full` and `Percentage` dimensions are evaluated against the
+    /// monitor the widget is on.
+    pub fn set_size(&mut self, width: Dimension, height: Dimension) {
+        self.config.width = width;
+        self.config.height = height;
+        self.reconfigure();
+    }
+
+    /// Sets the edges of the monitor to which the widget is anchored. Multiple
+    /// edges can be combined (like `Anchor::TOP | Anchor::LEFT`), an empty [`Anchor`]
+    /// centers the widget. The size of the widget is re-evaluated for the new anchors.
+    pub fn set_anchor(&mut self, anchor: Anchor) {
+        info!("Win: anchor set to {:?}", anchor);
+        self.config.anchor = [Some(anchor), None, None, None];
+        self.reconfigure();
+    }
+
+    /// Sets the margins of the widget from the edges of the monitor, view
+    /// [`WindowConfBuilder::margins`](crate::layer_properties::WindowConfBuilder::margins)
+    /// for details.
+    pub fn set_margins(&mut self, top: i32, right: i32, bottom: i32, left: i32) {
+        info!(
+            "Win: margins set to [top: {}, right: {}, bottom: {}, left: {}]",
+            top, right, bottom, left
+        );
+        self.config.margin = (top, right, bottom, left);
+        self.reconfigure();
+    }
+
+    /// Moves the widget to the given [`Layer`].
+    pub fn set_layer(&mut self, layer: Layer) {
+        info!("Win: layer set to {:?}", layer);
+        self.config.layer_type = layer;
+        self.reconfigure();
+    }
+
+    /// Moves the widget to another monitor. Layer surfaces can't change their
+    /// monitor, so the surface of the widget is re-created on the new monitor. The
+    /// slint component (and hence its state) is kept as it is, but open popups are
+    /// closed. A hidden widget is shown on the new monitor. If the monitor isn't
+    /// found, the compositor chooses the monitor.
+    pub fn move_to_output<M: Into<MonitorSelector>>(&mut self, monitor: M) {
+        let selector: MonitorSelector = monitor.into();
+        info!("Win: moving to monitor {:?}", selector);
+        self.output = selector
+            .resolve(&outputs_with_info(&self.states.output_state))
+            .and_then(|(output, info)| {
+                let (width, height) = info.logical_size?;
+                Some((output.clone(), width, height))
+            });
+        if self.output.is_none() && !matches!(selector, MonitorSelector::Focused) {
+            warn!("Monitor {:?} not found, using default monitor", selector);
+        }
+        self.config.monitor = Some(selector);
+
+        self.popup_manager.close_all();
+        // Viewport needs to be destroyed before its surface.
+        drop(self.viewport.take());
+        drop(self.layer.take());
+        let (width, height) = self.evaluate_size(&self.config.width, &self.config.height);
+        self.config.evaluated_width = width;
+        self.config.evaluated_height = height;
+        self.resize_surface(width, height);
+        self.is_configured.set(false);
+        self.first_configure.set(true);
+        let surface = self.states.compositor_state.create_surface(&self.queue);
+        self.is_hidden.set(false);
+        self.create_layer(surface);
+    }
+
+    /// Applies a new [`WindowConf`] to the running widget. Size, anchors, margins,
+    /// layer, keyboard interactivity, exclusive zone and monitor of the widget are
+    /// updated, the widget is moved to the new monitor if it has changed. It is
+    /// called on changes of the file the configuration is loaded from (see
+    /// [`WindowConf::from_file`]).
+    pub fn apply_window_conf(&mut self, window_conf: WindowConf) {
+        info!("Win: applying new window configuration");
+        self.config.width = window_conf.width;
+        self.config.height = window_conf.height;
+        self.config.anchor = window_conf.anchor;
+        self.config.margin = window_conf.margin;
+        self.config.layer_type = window_conf.layer_type;
+        self.config
+            .board_interactivity
+            .set(window_conf.board_interactivity.get());
+        self.config.exclusive_zone = window_conf.exclusive_zone;
+        self.config.natural_scroll = window_conf.natural_scroll;
+        self.natural_scroll = window_conf.natural_scroll;
+        // Monitors of widgets created per output are decided by their outputs.
+        if !self.config.all_outputs && self.config.monitor != window_conf.monitor {
+            self.move_to_output(window_conf.monitor.unwrap_or(MonitorSelector::Focused));
+        } else {
+            self.reconfigure();
+        }
+    }
+
+    /// Sets the callback called with the multi-finger gestures made on the widget,
+    /// either on a touchscreen or on a touchpad while the pointer is over the widget.
+    /// Single finger touches are sent to slint as pointer events, so they work
+    /// with `TouchArea` like a mouse.
+    pub fn on_gesture<F: FnMut(Gesture) + 'static>(&mut self, callback: F) {
+        self.gesture_callback = Some(Box::new(callback));
+    }
+
+    /// Makes the widget a drop target for the given MIME types, listed in the order
+    /// of preference. The callback is called with the [`DropEvent`]s of drags made
+    /// over the widget, and with the data in the first accepted type on drop. Drags
+    /// which offer none of the types are rejected but still reported.
+    pub fn on_drop<F: FnMut(DropEvent) + 'static>(&mut self, mime_types: Vec<String>, callback: F) {
+        self.dnd.accepted_types = mime_types;
+        self.dnd.callback = Some(Box::new(callback));
+    }
+
+    /// Starts a drag from the widget offering the given data, like an application
+    /// dragged from a launcher into a terminal. It needs to be called while a pointer
+    /// button is held on the widget, which is usually from the `moved` callback of
+    /// a `TouchArea` with `pressed` set. Data is sent to the receiving client when
+    /// it asks for it.
+    pub fn start_drag(&mut self, data: MimeData) -> Result<(), Box<dyn std::error::Error>> {
+        let (Some(manager), Some(device)) =
+            (&self.states.data_device_manager, &self.states.data_device)
+        else {
+            return Err("Drag and drop is not supported by the compositor".into());
+        };
+        let Some((serial, surface)) = self.states.pointer_state.last_press.clone() else {
+            return Err("Drags can only be started while a pointer button is held".into());
+        };
+        let source = manager.create_drag_and_drop_source(
+            &self.queue,
+            data.mime_types(),
+            DndAction::Copy | DndAction::Move,
+        );
+        source.start_drag(device, &surface, None, serial);
+        info!("Win: drag started from {}", self.layer_name);
+        self.dnd.source = Some((source, data));
+        Ok(())
+    }
+
     /// Opens a popup given the [`PopupConf`]. It returns the ID of the popup if
     /// created successfully. The method fails if the concerned compositor fails
     /// to create a popup instance or doesn't support the protocol.
@@ -517,6 +740,33 @@ impl SpellWin {
     }
 }
 
+impl Drop for SpellWin {
+    fn drop(&mut self) {
+        info!("Win: {} layer is being removed.", self.layer_name);
+        if self.ipc_handler.take().is_some() {
+            let socket_path = format!("/tmp/{}_ipc.sock", self.layer_name);
+            if let Err(err) = std::fs::remove_file(&socket_path) {
+                warn!("Win: couldn't remove IPC socket {}: {}", socket_path, err);
+            }
+        }
+        ADAPTERS.with_borrow_mut(|adapters| {
+            adapters.retain(|adapter| {
+                !self
+                    .adapter
+                    .as_ref()
+                    .is_some_and(|own| Rc::ptr_eq(own, adapter))
+                    && !self.popup_manager.owns_adapter(adapter)
+            })
+        });
+        self.popup_manager.close_all();
+        // Dropping the layer destroys the layer surface.
+        drop(self.layer.take());
+        if let Err(err) = self.conn.flush() {
+            warn!("Win: couldn't flush the connection on removal: {}", err);
+        }
+    }
+}
+
 delegate_compositor!(SpellWin);
 delegate_xdg_shell!(SpellWin);
 delegate_xdg_popup!(SpellWin);
@@ -530,19 +780,53 @@ delegate_touch!(SpellWin);
 delegate_layer!(SpellWin);
 delegate_fractional_scale!(SpellWin);
 delegate_viewporter!(SpellWin);
+delegate_text_input!(SpellWin);
+delegate_pointer_gestures!(SpellWin);
+delegate_data_device!(SpellWin);
+delegate_primary_selection!(SpellWin);
 
 impl SpellAssociatedNew for SpellWin {
     fn on_call(&mut self) -> Result<(), Box<dyn std::error::Error>> {
         let event_loop = self.event_loop.clone();
         event_loop
             .borrow_mut()
-            .dispatch(std::time::Duration::from_millis(1), self)?;
+            .dispatch(std::time::Duration::ZERO, self)?;
+        // Buffers can only be attached after the first configure, which in turn
+        // calls the converter.
+        if self.is_configured.get() {
+            let qh = self.queue.clone();
+            self.converter(&qh);
+            self.popup_manager.redraw_popups(&qh);
+            self.update_text_input();
+            self.update_clipboard();
+        }
+        self.states.pointer_state.animate_cursor();
+        self.conn.flush()?;
         Ok(())
     }
 
+    fn event_fd(&self) -> BorrowedFd<'_> {
+        // SAFETY: The event loop is owned by the widget and lives as long as it.
+        unsafe { BorrowedFd::borrow_raw(self.event_loop.borrow().as_raw_fd()) }
+    }
+
     fn get_span(&self) -> tracing::span::Span {
         self.span.clone()
     }
+
+    fn layer_name(&self) -> Option<&str> {
+        Some(&self.layer_name)
+    }
+
+    fn next_timeout(&self) -> Option<std::time::Duration> {
+        [
+            self.key_repeat.next_timeout(),
+            self.states.pointer_state.cursor_timeout(),
+        ]
+        .into_iter()
+        .flatten()
+        .min()
+    }
 }
 
 /// This is a wrapper around calloop's [loop_handle](https://docs.rs/calloop/latest/calloop/struct.LoopHandle.html)
@@ -552,58 +836,111 @@ impl SpellAssociatedNew for SpellWin {
 pub struct WinHandle(pub LoopHandle<'static, SpellWin>);
 
 impl WinHandle {
+    /// Queues a callback to run on the widget in its next dispatch and wakes
+    /// the event loop so that the dispatch happens right away.
+    fn insert_idle<F: FnOnce(&mut SpellWin) + 'static>(&self, callback: F) {
+        self.0.insert_idle(callback);
+        crate::wake_event_loop();
+    }
+
     /// Internally calls [`crate::wayland_adapter::SpellWin::hide`]
     pub fn hide(&self) {
-        self.0.insert_idle(|win| win.hide());
+        self.insert_idle(|win| win.hide());
     }
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::show_again`]
     pub fn show_again(&self) {
-        self.0.insert_idle(|win| win.show_again());
+        self.insert_idle(|win| win.show_again());
     }
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::toggle`]
     pub fn toggle(&self) {
-        self.0.insert_idle(|win| win.toggle());
+        self.insert_idle(|win| win.toggle());
     }
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::grab_focus`]
     pub fn grab_focus(&self) {
-        self.0.insert_idle(|win| win.grab_focus());
+        self.insert_idle(|win| win.grab_focus());
+    }
+
+    /// Internally calls [`crate::wayland_adapter::SpellWin::on_gesture`]
+    pub fn on_gesture<F: FnMut(Gesture) + 'static>(&self, callback: F) {
+        self.insert_idle(move |win| win.on_gesture(callback));
+    }
+
+    /// Internally calls [`crate::wayland_adapter::SpellWin::on_drop`]
+    pub fn on_drop<F: FnMut(DropEvent) + 'static>(&self, mime_types: Vec<String>, callback: F) {
+        self.insert_idle(move |win| win.on_drop(mime_types, callback));
+    }
+
+    /// Internally calls [`crate::wayland_adapter::SpellWin::start_drag`]
+    pub fn start_drag(&self, data: MimeData) {
+        self.insert_idle(move |win| {
+            if let Err(err) = win.start_drag(data) {
+                warn!("Win: couldn't start the drag: {}", err);
+            }
+        });
     }
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::remove_focus`]
     pub fn remove_focus(&self) {
-        self.0.insert_idle(|win| win.remove_focus());
+        self.insert_idle(|win| win.remove_focus());
     }
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::add_input_region`]
     pub fn add_input_region(&self, x: i32, y: i32, width: i32, height: i32) {
-        self.0
-            .insert_idle(move |win| win.add_input_region(x, y, width, height));
+        self.insert_idle(move |win| win.add_input_region(x, y, width, height));
     }
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::subtract_input_region`]
     pub fn subtract_input_region(&self, x: i32, y: i32, width: i32, height: i32) {
-        self.0
-            .insert_idle(move |win| win.subtract_input_region(x, y, width, height));
+        self.insert_idle(move |win| win.subtract_input_region(x, y, width, height));
     }
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::add_opaque_region`]
     pub fn add_opaque_region(&self, x: i32, y: i32, width: i32, height: i32) {
-        self.0
-            .insert_idle(move |win| win.add_opaque_region(x, y, width, height));
+        self.insert_idle(move |win| win.add_opaque_region(x, y, width, height));
     }
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::subtract_opaque_region`]
     pub fn subtract_opaque_region(&self, x: i32, y: i32, width: i32, height: i32) {
-        self.0
-            .insert_idle(move |win| win.subtract_opaque_region(x, y, width, height));
+        self.insert_idle(move |win| win.subtract_opaque_region(x, y, width, height));
     }
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::set_exclusive_zone`]
     pub fn set_exclusive_zone(&self, val: i32) {
-        self.0.insert_idle(move |win| win.set_exclusive_zone(val));
+        self.insert_idle(move |win| win.set_exclusive_zone(val));
+    }
+
+    /// Internally calls [`crate::wayland_adapter::SpellWin::set_size`]
+    pub fn set_size(&self, width: Dimension, height: Dimension) {
+        self.insert_idle(move |win| win.set_size(width, height));
+    }
+
+    /// Internally calls [`crate::wayland_adapter::SpellWin::apply_window_conf`]
+    pub fn apply_window_conf(&self, window_conf: WindowConf) {
+        self.insert_idle(move |win| win.apply_window_conf(window_conf));
+    }
+
+    /// Internally calls [`crate::wayland_adapter::SpellWin::set_anchor`]
+    pub fn set_anchor(&self, anchor: Anchor) {
+        self.insert_idle(move |win| win.set_anchor(anchor));
+    }
+
+    /// Internally calls [`crate::wayland_adapter::SpellWin::set_margins`]
+    pub fn set_margins(&self, top: i32, right: i32, bottom: i32, left: i32) {
+        self.insert_idle(move |win| win.set_margins(top, right, bottom, left));
+    }
+
+    /// Internally calls [`crate::wayland_adapter::SpellWin::set_layer`]
+    pub fn set_layer(&self, layer: Layer) {
+        self.insert_idle(move |win| win.set_layer(layer));
+    }
+
+    /// Internally calls [`crate::wayland_adapter::SpellWin::move_to_output`]
+    pub fn move_to_output<M: Into<MonitorSelector>>(&self, monitor: M) {
+        let monitor = monitor.into();
+        self.insert_idle(move |win| win.move_to_output(monitor));
     }
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::open_popup`]. Since,
@@ -615,7 +952,7 @@ impl WinHandle {
         popup_conf: PopupConf,
         callback: Box<dyn FnOnce(u32)>,
     ) -> Result<u32, Box<dyn std::error::Error>> {
-        self.0.insert_idle(|win| {
+        self.insert_idle(|win| {
             if let Ok(id) = win.open_popup::<T>(popup_conf) {
                 callback(id);
             }
@@ -625,7 +962,7 @@ impl WinHandle {
 
     /// Internally calls [`crate::wayland_adapter::SpellWin::close_popup`].
     pub fn close_popup(&self, id: u32) {
-        self.0.insert_idle(move |win| {
+        self.insert_idle(move |win| {
             win.close_popup(id);
         });
     }
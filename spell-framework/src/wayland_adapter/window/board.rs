@@ -0,0 +1,513 @@
+use crate::{
+    SpellAssociatedNew,
+    configure::WindowConf,
+    wayland_adapter::{SpellWin, common::get_keysym},
+};
+use rustix::fs::{MemfdFlags, memfd_create};
+use smithay_client_toolkit::{
+    reexports::{
+        calloop::LoopHandle,
+        client::{Connection, Dispatch, QueueHandle, protocol::wl_seat::WlSeat},
+    },
+    seat::keyboard::Keysym,
+};
+use std::{
+    fs::File,
+    io::Write,
+    ops::{Deref, DerefMut},
+    os::fd::{AsFd, BorrowedFd},
+    sync::{
+        Mutex,
+        atomic::{AtomicU32, Ordering},
+    },
+    time::Instant,
+};
+use tracing::{info, trace, warn};
+use wayland_protocols_misc::{
+    zwp_input_method_v2::client::{
+        zwp_input_method_manager_v2::ZwpInputMethodManagerV2,
+        zwp_input_method_v2::{self, ZwpInputMethodV2},
+    },
+    zwp_virtual_keyboard_v1::client::{
+        zwp_virtual_keyboard_manager_v1::ZwpVirtualKeyboardManagerV1,
+        zwp_virtual_keyboard_v1::ZwpVirtualKeyboardV1,
+    },
+};
+
+/// Keysyms placed in the keymap from the start. Keysyms of other keys are added
+/// to the keymap as they are typed.
+const INITIAL_KEYSYMS: [Keysym; 12] = [
+    Keysym::BackSpace,
+    Keysym::Return,
+    Keysym::Tab,
+    Keysym::Escape,
+    Keysym::Delete,
+    Keysym::space,
+    Keysym::Left,
+    Keysym::Right,
+    Keysym::Up,
+    Keysym::Down,
+    Keysym::Home,
+    Keysym::End,
+];
+
+/// Modifiers held down by the on-screen keyboard, they apply to the keys sent
+/// after them.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct BoardModifiers {
+    /// Shift key.
+    pub shift: bool,
+    /// Control key.
+    pub control: bool,
+    /// Alt key.
+    pub alt: bool,
+    /// Super (or Windows) key.
+    pub logo: bool,
+    /// Caps lock, which stays locked till it is set again.
+    pub caps_lock: bool,
+}
+
+impl BoardModifiers {
+    /// Returns the depressed and locked modifier masks, as per the real modifiers
+    /// of xkb.
+    fn masks(&self) -> (u32, u32) {
+        let mut depressed = 0;
+        if self.shift {
+            depressed |= 1;
+        }
+        if self.control {
+            depressed |= 1 << 2;
+        }
+        if self.alt {
+            depressed |= 1 << 3;
+        }
+        if self.logo {
+            depressed |= 1 << 6;
+        }
+        let locked = if self.caps_lock { 1 << 1 } else { 0 };
+        (depressed, locked)
+    }
+}
+
+/// User data of the input method, storing its activation state till it is applied
+/// on `done` event.
+#[derive(Debug, Default)]
+pub(crate) struct InputMethodData {
+    pending_active: Mutex<bool>,
+    /// Number of `done` events received, needed for committing text.
+    serial: AtomicU32,
+}
+
+/// Wayland side of [`SpellBoard`], stored by the widget which acts as the keyboard.
+#[derive(Debug)]
+pub(super) struct Board {
+    virtual_keyboard: ZwpVirtualKeyboardV1,
+    input_method: Option<ZwpInputMethodV2>,
+    /// Keysyms of the uploaded keymap, the evdev code of a keysym is its index + 1.
+    keysyms: Vec<Keysym>,
+    modifiers: BoardModifiers,
+    /// Whether some text input of the focused application is active.
+    is_active: bool,
+    /// Shows and hides the keyboard as text inputs are activated and deactivated.
+    auto_toggle: bool,
+    start: Instant,
+}
+
+impl Board {
+    fn new(
+        virtual_keyboard: ZwpVirtualKeyboardV1,
+        input_method: Option<ZwpInputMethodV2>,
+    ) -> Result<Self, Box<dyn std::error::Error>> {
+        let board = Board {
+            virtual_keyboard,
+            input_method,
+            keysyms: INITIAL_KEYSYMS.to_vec(),
+            modifiers: BoardModifiers::default(),
+            is_active: false,
+            auto_toggle: true,
+            start: Instant::now(),
+        };
+        board.upload_keymap()?;
+        Ok(board)
+    }
+
+    /// Uploads a keymap in which every keysym has a key of its own, so that any
+    /// text can be typed independent of the layout of the user.
+    fn upload_keymap(&self) -> Result<(), Box<dyn std::error::Error>> {
+        let mut keymap = String::from("xkb_keymap {\nxkb_keycodes \"spell\" {\nminimum = 8;\n");
+        keymap.push_str(&format!("maximum = {};\n", self.keysyms.len() + 9));
+        for index in 0..self.keysyms.len() {
+            keymap.push_str(&format!("<K{}> = {};\n", index + 1, index + 9));
+        }
+        keymap.push_str("};\nxkb_types \"spell\" { include \"complete\" };\n");
+        keymap.push_str("xkb_compatibility \"spell\" { include \"complete\" };\n");
+        keymap.push_str("xkb_symbols \"spell\" {\n");
+        for (index, keysym) in self.keysyms.iter().enumerate() {
+            keymap.push_str(&format!(
+                "key <K{}> {{[ {:#x} ]}};\n",
+                index + 1,
+                keysym.raw()
+            ));
+        }
+        keymap.push_str("};\n};\n");
+        // The keymap is shared through an anonymous file, so it never shows up on
+        // disk.
+        let mut file = File::from(memfd_create("spell-board-keymap", MemfdFlags::CLOEXEC)?);
+        file.write_all(keymap.as_bytes())?;
+        file.write_all(&[0])?;
+        // 1 is the format of xkb keymaps.
+        self.virtual_keyboard
+            .keymap(1, file.as_fd(), keymap.len() as u32 + 1);
+        Ok(())
+    }
+
+    /// Returns the evdev code of the keysym, adding it to the keymap if needed.
+    fn keycode(&mut self, keysym: Keysym) -> u32 {
+        let index = match self.keysyms.iter().position(|known| *known == keysym) {
+            Some(index) => index,
+            None => {
+                self.keysyms.push(keysym);
+                if let Err(err) = self.upload_keymap() {
+                    warn!("Couldn't upload the keymap: {}", err);
+                }
+                self.keysyms.len() - 1
+            }
+        };
+        index as u32 + 1
+    }
+
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    fn key(&mut self, keysym: Keysym, pressed: bool) {
+        let keycode = self.keycode(keysym);
+        let time = self.time();
+        self.virtual_keyboard
+            .key(time, keycode, if pressed { 1 } else { 0 });
+    }
+
+    fn set_modifiers(&mut self, modifiers: BoardModifiers) {
+        self.modifiers = modifiers;
+        let (depressed, locked) = modifiers.masks();
+        self.virtual_keyboard.modifiers(depressed, 0, locked, 0);
+    }
+
+    /// Types the text into the focused application. The text is committed through
+    /// the input method when a text input is active, otherwise it is typed key by key.
+    fn type_text(&mut self, text: &str) {
+        if let (Some(input_method), true) = (&self.input_method, self.is_active) {
+            let serial = input_method
+                .data::<InputMethodData>()
+                .map_or(0, |data| data.serial.load(Ordering::Relaxed));
+            input_method.commit_string(text.to_string());
+            input_method.commit(serial);
+            return;
+        }
+        for ch in text.chars() {
+            let Some(keysym) = get_keysym(ch.encode_utf8(&mut [0; 4])) else {
+                continue;
+            };
+            self.key(keysym, true);
+            self.key(keysym, false);
+        }
+    }
+}
+
+impl Drop for Board {
+    fn drop(&mut self) {
+        self.virtual_keyboard.destroy();
+        if let Some(input_method) = &self.input_method {
+            input_method.destroy();
+        }
+    }
+}
+
+/// SpellBoard is an on-screen keyboard. It is a layer like [`SpellWin`], whose slint
+/// UI sends keys, modifiers and text to the focused application through
+/// `zwp_virtual_keyboard_v1`. If the compositor supports `zwp_input_method_v2`, the
+/// keyboard is shown only while a text input of the focused application is active,
+/// this can be turned off with [`SpellBoard::set_auto_toggle`]. The input method is
+/// not available if some other input method (like an IME) is running.
+///
+/// The layer should not take the keyboard focus, so its [`WindowConf`] needs
+/// [`BoardType::None`](crate::layer_properties::BoardType::None) as keyboard
+/// interactivity. Keys are sent from the callbacks of the UI with the
+/// [`BoardHandle`] of the keyboard. Apart from that, it can be used everywhere a
+/// [`SpellWin`] is used.
+///
+/// ```rust,ignore
+/// let board = SpellBoard::invoke_spell("keyboard", window_conf);
+/// let handle = board.get_handler();
+/// ui.on_key_pressed(move |key| handle.type_text(key.to_string()));
+/// ```
+pub struct SpellBoard {
+    win: SpellWin,
+}
+
+impl std::fmt::Debug for SpellBoard {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("SpellBoard")
+            .field("win", &self.win)
+            .finish()
+    }
+}
+
+impl SpellBoard {
+    /// This function is called to create an instance of the keyboard, see
+    /// [`SpellWin::invoke_spell`] for details.
+    ///
+    /// # Panics
+    ///
+    /// This function panics if the compositor doesn't support virtual keyboards or
+    /// has no seat.
+    pub fn invoke_spell(name: &str, window_conf: WindowConf) -> Self {
+        let mut win = SpellWin::invoke_spell(name, window_conf);
+        win.create_board()
+            .unwrap_or_else(|err| panic!("Couldn't create the keyboard: {err}"));
+        SpellBoard { win }
+    }
+
+    /// Returns a handle of [`BoardHandle`] to send keys from the UI.
+    pub fn get_handler(&self) -> BoardHandle {
+        info!("Win: Board handle provided.");
+        BoardHandle(self.win.get_handler().0)
+    }
+
+    /// Sets whether the keyboard is shown and hidden as text inputs of the focused
+    /// application are activated and deactivated. It is set by default.
+    pub fn set_auto_toggle(&mut self, auto_toggle: bool) {
+        self.win.set_board_auto_toggle(auto_toggle);
+    }
+
+    /// Returns the underlying [`SpellWin`], which still acts as the keyboard.
+    pub fn into_inner(self) -> SpellWin {
+        self.win
+    }
+}
+
+impl Deref for SpellBoard {
+    type Target = SpellWin;
+    fn deref(&self) -> &Self::Target {
+        &self.win
+    }
+}
+
+impl DerefMut for SpellBoard {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        &mut self.win
+    }
+}
+
+impl SpellAssociatedNew for SpellBoard {
+    fn on_call(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        self.win.on_call()
+    }
+
+    fn event_fd(&self) -> BorrowedFd<'_> {
+        self.win.event_fd()
+    }
+
+    fn get_span(&self) -> tracing::span::Span {
+        self.win.get_span()
+    }
+
+    fn layer_name(&self) -> Option<&str> {
+        self.win.layer_name()
+    }
+
+    fn next_timeout(&self) -> Option<std::time::Duration> {
+        self.win.next_timeout()
+    }
+}
+
+impl SpellWin {
+    /// Binds the virtual keyboard, along with the input method if available, on
+    /// the first seat.
+    pub(super) fn create_board(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        let seat: WlSeat = self
+            .states
+            .seat_state
+            .seats()
+            .next()
+            .ok_or("no seat is available")?;
+        let qh = self.queue.clone();
+        let manager: ZwpVirtualKeyboardManagerV1 =
+            self.states.registry_state.bind_one(&qh, 1..=1, ())?;
+        let virtual_keyboard = manager.create_virtual_keyboard(&seat, &qh, ());
+        let input_method = match self
+            .states
+            .registry_state
+            .bind_one::<ZwpInputMethodManagerV2, _, _>(&qh, 1..=1, ())
+        {
+            Ok(manager) => Some(manager.get_input_method(&seat, &qh, InputMethodData::default())),
+            Err(err) => {
+                info!(
+                    "Input method is not available, keyboard won't toggle: {}",
+                    err
+                );
+                None
+            }
+        };
+        let auto_toggle = input_method.is_some();
+        self.board = Some(Board::new(virtual_keyboard, input_method)?);
+        // Shown once a text input is active.
+        if auto_toggle {
+            self.hide();
+        }
+        Ok(())
+    }
+
+    fn set_board_auto_toggle(&mut self, auto_toggle: bool) {
+        if let Some(board) = self.board.as_mut() {
+            board.auto_toggle = auto_toggle;
+        }
+    }
+
+    fn with_board<F: FnOnce(&mut Board)>(&mut self, callback: F) {
+        match self.board.as_mut() {
+            Some(board) => callback(board),
+            None => warn!("Win: {} is not a keyboard", self.layer_name),
+        }
+    }
+
+    /// Called on activation or deactivation of a text input in the focused application.
+    fn board_activated(&mut self, is_active: bool) {
+        let Some(board) = self.board.as_mut() else {
+            return;
+        };
+        if board.is_active == is_active {
+            return;
+        }
+        trace!("Text input active: {}", is_active);
+        board.is_active = is_active;
+        if board.auto_toggle {
+            if is_active {
+                self.show_again();
+            } else {
+                self.hide();
+            }
+        }
+    }
+}
+
+/// This is a wrapper around calloop's [loop_handle](https://docs.rs/calloop/latest/calloop/struct.LoopHandle.html)
+/// for sending keys from a [`SpellBoard`]. It can be accessed from
+/// [`crate::wayland_adapter::SpellBoard::get_handler`]. Keys are given as they are
+/// in slint, either a character or one of slint's special keys, like
+/// [`Key::Backspace`](slint::platform::Key::Backspace).
+#[derive(Clone, Debug)]
+pub struct BoardHandle(pub LoopHandle<'static, SpellWin>);
+
+impl BoardHandle {
+    fn insert_idle<F: FnOnce(&mut Board) + 'static>(&self, callback: F) {
+        self.0.insert_idle(|win| win.with_board(callback));
+        crate::wake_event_loop();
+    }
+
+    /// Presses the key, it stays pressed till it is released.
+    pub fn press_key<S: AsRef<str>>(&self, key: S) {
+        let Some(keysym) = get_keysym(key.as_ref()) else {
+            warn!("Not a single key: {}", key.as_ref());
+            return;
+        };
+        self.insert_idle(move |board| board.key(keysym, true));
+    }
+
+    /// Releases a pressed key.
+    pub fn release_key<S: AsRef<str>>(&self, key: S) {
+        let Some(keysym) = get_keysym(key.as_ref()) else {
+            warn!("Not a single key: {}", key.as_ref());
+            return;
+        };
+        self.insert_idle(move |board| board.key(keysym, false));
+    }
+
+    /// Types the text into the focused application.
+    pub fn type_text<S: Into<String>>(&self, text: S) {
+        let text = text.into();
+        self.insert_idle(move |board| board.type_text(&text));
+    }
+
+    /// Sets the modifiers held down by the keyboard.
+    pub fn set_modifiers(&self, modifiers: BoardModifiers) {
+        self.insert_idle(move |board| board.set_modifiers(modifiers));
+    }
+
+    /// Returns the modifiers currently held down by the keyboard through the callback.
+    pub fn modifiers<F: FnOnce(BoardModifiers) + 'static>(&self, callback: F) {
+        self.insert_idle(move |board| callback(board.modifiers));
+    }
+}
+
+impl Dispatch<ZwpVirtualKeyboardManagerV1, ()> for SpellWin {
+    fn event(
+        _: &mut Self,
+        _: &ZwpVirtualKeyboardManagerV1,
+        _: <ZwpVirtualKeyboardManagerV1 as smithay_client_toolkit::reexports::client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        unreachable!("ZwpVirtualKeyboardManagerV1 has no events")
+    }
+}
+
+impl Dispatch<ZwpVirtualKeyboardV1, ()> for SpellWin {
+    fn event(
+        _: &mut Self,
+        _: &ZwpVirtualKeyboardV1,
+        _: <ZwpVirtualKeyboardV1 as smithay_client_toolkit::reexports::client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        unreachable!("ZwpVirtualKeyboardV1 has no events")
+    }
+}
+
+impl Dispatch<ZwpInputMethodManagerV2, ()> for SpellWin {
+    fn event(
+        _: &mut Self,
+        _: &ZwpInputMethodManagerV2,
+        _: <ZwpInputMethodManagerV2 as smithay_client_toolkit::reexports::client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        unreachable!("ZwpInputMethodManagerV2 has no events")
+    }
+}
+
+impl Dispatch<ZwpInputMethodV2, InputMethodData> for SpellWin {
+    fn event(
+        state: &mut Self,
+        _: &ZwpInputMethodV2,
+        event: zwp_input_method_v2::Event,
+        data: &InputMethodData,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwp_input_method_v2::Event::Activate => *data.pending_active.lock().unwrap() = true,
+            zwp_input_method_v2::Event::Deactivate => *data.pending_active.lock().unwrap() = false,
+            zwp_input_method_v2::Event::Done => {
+                data.serial.fetch_add(1, Ordering::Relaxed);
+                let is_active = *data.pending_active.lock().unwrap();
+                state.board_activated(is_active);
+            }
+            zwp_input_method_v2::Event::Unavailable => {
+                warn!("Input method is used by another program, keyboard won't toggle");
+                if let Some(board) = state.board.as_mut() {
+                    if let Some(input_method) = board.input_method.take() {
+                        input_method.destroy();
+                    }
+                    board.auto_toggle = false;
+                }
+                state.show_again();
+            }
+            // Surrounding text and content type are of no use for the keyboard.
+            _ => {}
+        }
+    }
+}
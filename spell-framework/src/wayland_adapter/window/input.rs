@@ -14,10 +14,6 @@ use smithay_client_toolkit::{
 };
 use tracing::{info, trace, warn};
 
-// Slint doesn't hve very specific
-// APIs for touch support (I think). I am talking with them on what
-// can be done so that things like multi-touch support, gestures etc
-// can be made possible. For now I am going to place empty value in here.
 impl TouchHandler for SpellWin {
     fn up(
         &mut self,
@@ -26,9 +22,11 @@ impl TouchHandler for SpellWin {
         _touch: &smithay_client_toolkit::reexports::client::protocol::wl_touch::WlTouch,
         _serial: u32,
         _time: u32,
-        _id: i32,
+        id: i32,
     ) {
-        info!("Up event from touch");
+        trace!("Up event from touch");
+        let output = self.touch.up(id);
+        self.handle_touch(output);
     }
     fn down(
         &mut self,
@@ -37,11 +35,13 @@ impl TouchHandler for SpellWin {
         _touch: &smithay_client_toolkit::reexports::client::protocol::wl_touch::WlTouch,
         _serial: u32,
         _time: u32,
-        _surface: smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
-        _id: i32,
+        surface: smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
+        id: i32,
         position: (f64, f64),
     ) {
-        info!("Down event produced with posaition: {position:?}");
+        trace!("Down event produced with position: {position:?}");
+        let output = self.touch.down(id, surface, position);
+        self.handle_touch(output);
     }
 
     fn motion(
@@ -50,19 +50,11 @@ impl TouchHandler for SpellWin {
         _qh: &QueueHandle<Self>,
         _touch: &smithay_client_toolkit::reexports::client::protocol::wl_touch::WlTouch,
         _time: u32,
-        _id: i32,
+        id: i32,
         position: (f64, f64),
     ) {
-        self.adapter
-            .as_ref()
-            .unwrap()
-            .try_dispatch_event(WindowEvent::PointerMoved {
-                position: slint::LogicalPosition {
-                    x: position.0 as f32,
-                    y: position.1 as f32,
-                },
-            })
-            .unwrap_or_else(|err| warn!("Touch move event failed with error: {:?}", err));
+        let output = self.touch.motion(id, position);
+        self.handle_touch(output);
     }
 
     fn shape(
@@ -74,7 +66,7 @@ impl TouchHandler for SpellWin {
         major: f64,
         minor: f64,
     ) {
-        info!("Shape data released. Major: {major}, Minor: {minor}");
+        trace!("Shape data released. Major: {major}, Minor: {minor}");
     }
     fn orientation(
         &mut self,
@@ -84,7 +76,7 @@ impl TouchHandler for SpellWin {
         _id: i32,
         orientation: f64,
     ) {
-        info!("Orientation data released: {orientation}.")
+        trace!("Orientation data released: {orientation}.")
     }
     fn cancel(
         &mut self,
@@ -93,6 +85,8 @@ impl TouchHandler for SpellWin {
         _touch: &smithay_client_toolkit::reexports::client::protocol::wl_touch::WlTouch,
     ) {
         info!("Active touch sequence cancelled");
+        let output = self.touch.cancel();
+        self.handle_touch(output);
     }
 }
 
@@ -135,10 +129,13 @@ impl PointerHandler for SpellWin {
                                 err
                             )
                         });
-                    self.states.pointer_state.last_cursor_enter_serial = Some(serial);
+                    self.states
+                        .pointer_state
+                        .enter(serial, adapter.scale_factor.get());
                 }
                 Leave { .. } => {
                     trace!("Pointer left: {:?}", event.position);
+                    self.states.pointer_state.leave();
 
                     adapter
                         .try_dispatch_event(WindowEvent::PointerExited)
@@ -160,8 +157,10 @@ impl PointerHandler for SpellWin {
                             warn!("Pointer move event failed with error: {:?}", err)
                         });
                 }
-                Press { button, .. } => {
+                Press { button, serial, .. } => {
                     trace!("Press {:?} @ {:?}", button, event.position);
+                    self.states.pointer_state.last_press = Some((serial, event.surface.clone()));
+                    self.clipboard.serial = Some(serial);
 
                     adapter
                         .try_dispatch_event(WindowEvent::PointerPressed {
@@ -177,6 +176,7 @@ impl PointerHandler for SpellWin {
                 }
                 Release { button, .. } => {
                     trace!("Release {:?} @ {:?}", button, event.position);
+                    self.states.pointer_state.last_press = None;
 
                     adapter
                         .try_dispatch_event(WindowEvent::PointerReleased {
@@ -237,11 +237,13 @@ impl KeyboardHandler for SpellWin {
         _qh: &QueueHandle<Self>,
         _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
         _surface: &smithay_client_toolkit::reexports::client::protocol::wl_surface::WlSurface,
-        _serial: u32,
+        serial: u32,
         _raw: &[u32],
         _keysyms: &[smithay_client_toolkit::seat::keyboard::Keysym],
     ) {
         info!("Keyboard focus entered");
+        self.clipboard.has_focus = true;
+        self.clipboard.serial = Some(serial);
     }
 
     fn leave(
@@ -253,6 +255,8 @@ impl KeyboardHandler for SpellWin {
         _serial: u32,
     ) {
         info!("Keyboard focus left");
+        self.clipboard.has_focus = false;
+        self.key_repeat.stop(&self.loop_handle);
     }
 
     fn press_key(
@@ -260,23 +264,31 @@ impl KeyboardHandler for SpellWin {
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
         _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
-        _serial: u32,
+        serial: u32,
         event: smithay_client_toolkit::seat::keyboard::KeyEvent,
     ) {
         trace!("Key pressed");
-        let string_val: SharedString = get_string(event);
-        // if string_val == <slint::platform::Key as Into<SharedString>>::into(Key::Backspace) {
-        //     self.loop_handle.enable(&self.backspace).unwrap();
-        //     self.adapter
-        //         .try_dispatch_event(WindowEvent::KeyPressed { text: string_val })
-        //         .unwrap();
-        // } else {
+        self.clipboard.serial = Some(serial);
+        let string_val: SharedString = get_string(event.clone());
         self.adapter
             .as_ref()
             .unwrap()
-            .try_dispatch_event(WindowEvent::KeyPressed { text: string_val })
+            .try_dispatch_event(WindowEvent::KeyPressed {
+                text: string_val.clone(),
+            })
             .unwrap_or_else(|err| warn!("Key press event failed with error: {:?}", err));
-        // }
+        self.key_repeat.start(
+            &self.loop_handle,
+            &event,
+            string_val,
+            |win: &mut SpellWin, text| {
+                win.adapter
+                    .as_ref()
+                    .unwrap()
+                    .try_dispatch_event(WindowEvent::KeyPressRepeated { text })
+                    .unwrap_or_else(|err| warn!("Key repeat event failed with error: {:?}", err));
+            },
+        );
     }
 
     fn release_key(
@@ -285,14 +297,10 @@ impl KeyboardHandler for SpellWin {
         _qh: &QueueHandle<Self>,
         _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
         _serial: u32,
-        /*mut*/ event: smithay_client_toolkit::seat::keyboard::KeyEvent,
+        event: smithay_client_toolkit::seat::keyboard::KeyEvent,
     ) {
         trace!("Key released");
-        // if let Err(err) = self.loop_handle.disable(&self.backspace) {
-        //     warn!("{}", err);
-        // }
-        // let key_sym = Keysym::new(event.raw_code);
-        // event.keysym = key_sym;
+        self.key_repeat.stop_key(&self.loop_handle, event.raw_code);
         let string_val: SharedString = get_string(event);
         self.adapter
             .as_ref()
@@ -313,17 +321,19 @@ impl KeyboardHandler for SpellWin {
         _layout: u32,
     ) {
     }
-    // TODO This method needs to be implemented after the looping mecha is changed to calloop.
     fn update_repeat_info(
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
         _keyboard: &smithay_client_toolkit::reexports::client::protocol::wl_keyboard::WlKeyboard,
-        _info: smithay_client_toolkit::seat::keyboard::RepeatInfo,
+        info: smithay_client_toolkit::seat::keyboard::RepeatInfo,
     ) {
         trace!("Key repeat info updated");
+        self.key_repeat.update_info(info);
     }
 
+    // Keys are repeated by `KeyRepeat` as the keyboard is not created with sctk's
+    // own repeat source.
     fn repeat_key(
         &mut self,
         _conn: &Connection,
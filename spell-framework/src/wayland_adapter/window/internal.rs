@@ -1,31 +1,41 @@
 use std::{
     cell::RefCell,
-    collections::HashMap,
     fs,
-    io::{BufRead, BufReader},
+    io::{self, BufRead, BufReader},
+    path::PathBuf,
     rc::Rc,
     time::Duration,
 };
 
 use crate::{
-    configure::{HomeHandle, PopupConf, PopupCore, WindowConf},
-    wayland_adapter::window::SpellWin,
+    configure::{Dimension, HomeHandle, PopupConf, PopupCore, WindowConf},
+    slint_adapter::SpellSkiaWinAdapter,
+    wayland_adapter::{
+        clipboard::{self, ReadCallback},
+        common::{damage_surface, outputs_with_info},
+        data_device::read_pipe,
+        gestures::TouchOutput,
+        text_input::TextInput,
+        window::SpellWin,
+    },
 };
-use slint::platform::WindowAdapter;
+use inotify::{Inotify, WatchMask};
+use slint::platform::Clipboard;
 use smithay_client_toolkit::{
+    data_device_manager::ReadPipe,
+    output::OutputInfo,
     reexports::{
-        calloop::{
-            self,
-            timer::{TimeoutAction, Timer},
-        },
+        calloop::{Interest, Mode, PostAction, generic::Generic},
         client::{
             EventQueue, QueueHandle,
-            protocol::{wl_output, wl_region::WlRegion},
+            protocol::{
+                wl_data_source::WlDataSource, wl_output, wl_region::WlRegion, wl_surface::WlSurface,
+            },
         },
     },
     shell::{
         WaylandSurface,
-        wlr_layer::LayerSurface,
+        wlr_layer::{Anchor, LayerSurface},
         xdg::{XdgPositioner, popup::Popup},
     },
     shm::slot::SlotPool,
@@ -34,6 +44,123 @@ use tracing::{info, warn};
 use tracing_subscriber::EnvFilter;
 
 impl SpellWin {
+    /// Returns the adapter of the layer or popup with the given surface.
+    pub(super) fn adapter_for_surface(
+        &self,
+        surface: &WlSurface,
+    ) -> Option<Rc<SpellSkiaWinAdapter>> {
+        if let Some(adapter) = self.popup_manager.return_adapter(surface) {
+            Some(adapter.clone())
+        } else if self
+            .layer
+            .as_ref()
+            .is_some_and(|layer| layer.wl_surface() == surface)
+        {
+            self.adapter.clone()
+        } else {
+            None
+        }
+    }
+
+    /// Dispatches the pointer events of touch points to the adapters of their
+    /// surfaces and the gestures to the gesture callback.
+    pub(super) fn handle_touch(&mut self, output: TouchOutput) {
+        for (surface, event) in output.pointer {
+            if let Some(adapter) = self.adapter_for_surface(&surface) {
+                adapter
+                    .try_dispatch_event(event)
+                    .unwrap_or_else(|err| warn!("Touch event failed with error: {:?}", err));
+            }
+        }
+        if let Some(callback) = self.gesture_callback.as_mut() {
+            for gesture in output.gestures {
+                callback(gesture);
+            }
+        }
+    }
+
+    /// Destroys the source of a drag or of the clipboard once it is no longer in
+    /// use, like when the drag is dropped or another client sets the selection.
+    pub(super) fn release_source(&mut self, source: &WlDataSource) {
+        if self
+            .dnd
+            .source
+            .as_ref()
+            .is_some_and(|(drag_source, _)| drag_source.inner() == source)
+        {
+            self.dnd.source = None;
+        }
+        if self
+            .clipboard
+            .source
+            .as_ref()
+            .is_some_and(|(copy_source, _)| copy_source.inner() == source)
+        {
+            self.clipboard.source = None;
+        }
+        source.destroy();
+    }
+
+    /// Offers the data set on [`SpellClipboard`](crate::wayland_adapter::SpellClipboard)
+    /// as the selection. It can only be done by the widget with keyboard focus.
+    pub(super) fn update_clipboard(&mut self) {
+        let Some(serial) = self.clipboard.serial.filter(|_| self.clipboard.has_focus) else {
+            return;
+        };
+        if let (Some(manager), Some(device)) =
+            (&self.states.data_device_manager, &self.states.data_device)
+            && let Some(data) = clipboard::take_pending(Clipboard::DefaultClipboard)
+        {
+            info!("Win: setting the clipboard");
+            let source = manager.create_copy_paste_source(&self.queue, data.mime_types());
+            source.set_selection(device, serial);
+            self.clipboard.source = Some((source, data));
+        }
+        if let (Some(manager), Some(device)) = (
+            &self.states.primary_selection_manager,
+            &self.states.primary_selection_device,
+        ) && let Some(data) = clipboard::take_pending(Clipboard::SelectionClipboard)
+        {
+            info!("Win: setting the primary selection");
+            let source = manager.create_selection_source(&self.queue, data.mime_types());
+            source.set_selection(device, serial);
+            self.clipboard.primary_source = Some((source, data));
+        }
+    }
+
+    /// Returns a function reading the given selection offer in a MIME type, which
+    /// is shared with the other widgets through the clipboard.
+    pub(super) fn selection_receiver<E: std::fmt::Debug>(
+        &self,
+        receive: impl Fn(String) -> Result<ReadPipe, E> + 'static,
+    ) -> Rc<dyn Fn(String, ReadCallback)> {
+        let loop_handle = self.loop_handle.clone();
+        Rc::new(move |mime_type, callback| match receive(mime_type) {
+            Ok(pipe) => {
+                read_pipe(&loop_handle, pipe, move |_: &mut SpellWin, result| {
+                    callback(result)
+                });
+                // The request is sent when the widget flushes its connection.
+                crate::wake_event_loop();
+            }
+            Err(err) => callback(Err(io::Error::other(format!("{:?}", err)))),
+        })
+    }
+
+    /// Sends the input method requests made by slint since the last call to the
+    /// compositor.
+    pub(super) fn update_text_input(&mut self) {
+        let Some(surface) = self.states.text_input.as_ref().and_then(TextInput::focus) else {
+            return;
+        };
+        let Some(adapter) = self.adapter_for_surface(&surface) else {
+            return;
+        };
+        if let Some(text_input) = self.states.text_input.as_mut() {
+            text_input.update(&adapter, false);
+        }
+    }
+
     pub(super) fn set_config_internal(&self) {
         set_config(
             &self.config,
@@ -43,61 +170,268 @@ impl SpellWin {
         );
     }
 
+    /// Creates the layer surface of the widget on its output, along with the
+    /// viewport used for scaling.
+    pub(super) fn create_layer(&mut self, surface: WlSurface) {
+        let target_output: Option<&wl_output::WlOutput> =
+            self.output.as_ref().map(|(output, _, _)| output);
+        let layer = self.layer_shell.create_layer_surface(
+            &self.queue,
+            surface,
+            self.config.layer_type,
+            Some(self.layer_name.clone()),
+            target_output,
+        );
+        self.layer = Some(layer);
+        self.set_config_internal();
+        let surface: &WlSurface = self.layer.as_ref().unwrap().wl_surface();
+
+        // This needs to occur after layer creation so as to ensure that layer
+        // used in window is not null during use to scale. Details in issue 34.
+        if let (Some(fractional_scale_state), Some(viewporter_state)) = (
+            self.states.fractional_scale_state.as_ref(),
+            self.states.viewporter_state.as_ref(),
+        ) {
+            let fractional_scale = fractional_scale_state.get_scale(surface, &self.queue);
+            let viewport = viewporter_state.get_viewport(surface, &self.queue, fractional_scale);
+            self.viewport = Some(viewport);
+        } else {
+            info!("Win: fractional scaling is unavailable, using integer buffer scale");
+        }
+
+        self.layer.as_ref().unwrap().commit();
+    }
+
+    /// Re-evaluates the size of the widget and sends its current configuration
+    /// to the compositor.
+    pub(super) fn reconfigure(&mut self) {
+        let (width, height) = self.evaluate_size(&self.config.width, &self.config.height);
+        if width != self.config.evaluated_width || height != self.config.evaluated_height {
+            info!("Win: resizing to width: {}, height: {}", width, height);
+            self.config.evaluated_width = width;
+            self.config.evaluated_height = height;
+            self.resize_surface(width, height);
+            // The buffer of the new size is attached right away.
+            if !self.is_hidden.get() {
+                self.first_configure.set(true);
+            }
+        }
+        self.set_config_internal();
+        self.layer.as_ref().unwrap().commit();
+    }
+
+    /// Evaluates the given dimensions to logical pixels using the size of the
+    /// output the layer is created on. `Full` and `Percentage` are relative to the
+    /// output's size on the same axis, less the margins on that axis.
+    pub(super) fn evaluate_size(&self, width: &Dimension, height: &Dimension) -> (u32, u32) {
+        let preferred_size = self
+            .adapter
+            .as_ref()
+            .and_then(|adapter| adapter.preferred_size.get());
+        // Size of the output is unknown till the surface enters the output chosen by
+        // the compositor, the widget is resized then.
+        let output_size = || {
+            self.output
+                .as_ref()
+                .map_or((0, 0), |(_, width, height)| (*width, *height))
+        };
+        let (top, right, bottom, left) = self.config.margin;
+        let evaluated_width = match width {
+            Dimension::Pixel(x) => *x,
+            Dimension::Full => available_size(output_size().0, left + right),
+            Dimension::Percentage(y) => available_size(output_size().0, left + right) * y / 100,
+            Dimension::Auto { min, max } => {
+                Dimension::auto_size(preferred_size.map(|size| size.width), *min, *max)
+            }
+        };
+        let evaluated_height = match height {
+            Dimension::Pixel(x) => *x,
+            Dimension::Full => available_size(output_size().1, top + bottom),
+            Dimension::Percentage(y) => available_size(output_size().1, top + bottom) * y / 100,
+            Dimension::Auto { min, max } => {
+                Dimension::auto_size(preferred_size.map(|size| size.height), *min, *max)
+            }
+        };
+        (evaluated_width.max(1), evaluated_height.max(1))
+    }
+
+    /// Adopts the size sent by the compositor in configure for the axes which are
+    /// sized by it (see [`requested_size`]). This size leaves out the margins and
+    /// the exclusive zones of other layers.
+    pub(super) fn fit_to_configure(&mut self, new_size: (u32, u32)) {
+        let (fills_width, fills_height) = fills_axes(&self.config);
+        let width = if fills_width && new_size.0 != 0 {
+            new_size.0
+        } else {
+            self.config.evaluated_width
+        };
+        let height = if fills_height && new_size.1 != 0 {
+            new_size.1
+        } else {
+            self.config.evaluated_height
+        };
+        if width == self.config.evaluated_width && height == self.config.evaluated_height {
+            return;
+        }
+        info!(
+            "Win: compositor configured width: {}, height: {}",
+            width, height
+        );
+        self.config.evaluated_width = width;
+        self.config.evaluated_height = height;
+        self.resize_surface(width, height);
+        self.first_configure.set(true);
+    }
+
+    /// Reallocates the buffers, resizes the slint window, viewport and regions of
+    /// the widget to the given logical size.
+    pub(super) fn resize_surface(&mut self, new_width: u32, new_height: u32) {
+        let adapter = self.adapter.clone().unwrap();
+        let old_width = adapter.size_original.get().width;
+        let old_height = adapter.size_original.get().height;
+        // Only the area lost or gained by the resize is changed, so that the regions
+        // set by the user inside the widget are kept.
+        if new_width < old_width {
+            let (x, y, width, height) = (
+                new_width as i32,
+                0,
+                (old_width - new_width) as i32,
+                old_height as i32,
+            );
+            self.input_region.subtract(x, y, width, height);
+            self.opaque_region.subtract(x, y, width, height);
+        }
+        if new_height < old_height {
+            let (x, y, width, height) = (
+                0,
+                new_height as i32,
+                old_width as i32,
+                (old_height - new_height) as i32,
+            );
+            self.input_region.subtract(x, y, width, height);
+            self.opaque_region.subtract(x, y, width, height);
+        }
+        // Gained area receives input, like the whole widget does by default.
+        if new_width > old_width {
+            self.input_region.add(
+                old_width as i32,
+                0,
+                (new_width - old_width) as i32,
+                new_height as i32,
+            );
+        }
+        if new_height > old_height {
+            self.input_region.add(
+                0,
+                old_height as i32,
+                new_width as i32,
+                (new_height - old_height) as i32,
+            );
+        }
+
+        let (physical_width, physical_height) = adapter.resize(new_width, new_height);
+        if let Some(viewport) = self.viewport.as_ref() {
+            viewport.set_source(0., 0., physical_width.into(), physical_height.into());
+            viewport.set_destination(new_width as i32, new_height as i32);
+        }
+        // Frame callbacks of the old buffer are of no use.
+        adapter.frame_pending.set(false);
+    }
+
+    /// Re-evaluates the size of the widget after its output changed its mode or
+    /// scale. Only `Full` and `Percentage` dimensions depend on it.
+    pub(super) fn output_updated(&mut self, output: &wl_output::WlOutput) {
+        let Some(logical_size) = self
+            .states
+            .output_state
+            .info(output)
+            .and_then(|info| info.logical_size)
+        else {
+            return;
+        };
+        let Some((current, width, height)) = self.output.as_mut() else {
+            return;
+        };
+        if current != output || (*width, *height) == logical_size {
+            return;
+        }
+        info!(
+            "Win: output resized to width: {}, height: {}",
+            logical_size.0, logical_size.1
+        );
+        (*width, *height) = logical_size;
+        let depends_on_output =
+            |dimension: &Dimension| matches!(dimension, Dimension::Full | Dimension::Percentage(_));
+        if depends_on_output(&self.config.width) || depends_on_output(&self.config.height) {
+            self.set_size(self.config.width.clone(), self.config.height.clone());
+        }
+    }
+
+    /// Resizes the widget if it is sized by [`Dimension::Auto`] and the preferred
+    /// size of its slint component has changed. Returns true if it is resized.
+    fn fit_to_content(&mut self) -> bool {
+        let is_auto = |dimension: &Dimension| matches!(dimension, Dimension::Auto { .. });
+        if !(is_auto(&self.config.width) || is_auto(&self.config.height))
+            || !self
+                .adapter
+                .as_ref()
+                .unwrap()
+                .preferred_size_changed
+                .replace(false)
+        {
+            return false;
+        }
+        let (width, height) = self.evaluate_size(&self.config.width, &self.config.height);
+        if width == self.config.evaluated_width && height == self.config.evaluated_height {
+            return false;
+        }
+        self.set_size(self.config.width.clone(), self.config.height.clone());
+        true
+    }
+
     pub(super) fn converter(&mut self, qh: &QueueHandle<Self>) {
         slint::platform::update_timers_and_animations();
-        let width: u32 = self.adapter.as_ref().unwrap().size.get().width;
-        let height: u32 = self.adapter.as_ref().unwrap().size.get().height;
-        let window_adapter = self.adapter.clone();
-
-        if !self.is_hidden.get() {
-            // FIXME: Rendering should take place between the sources, here it
-            // should just be setting the buffers.
-            let redraw_val: bool = window_adapter.unwrap().draw_if_needed();
-            self.states
-                .pointer_state
-                .update_cursor(self.adapter.as_ref().unwrap().current_cursor.get(), qh);
-
-            let buffer = &self.buffer;
-            if self.first_configure.get() || redraw_val {
-                // if self.first_configure {
-                self.first_configure.set(false);
-                self.layer.as_ref().unwrap().wl_surface().damage_buffer(
-                    0,
-                    0,
-                    width as i32,
-                    height as i32,
-                );
-                // } else {
-                //     for (position, size) in self.damaged_part.as_ref().unwrap().iter() {
-                //         // println!(
-                //         //     "{}, {}, {}, {}",
-                //         //     position.x, position.y, size.width as i32, size.height as i32,
-                //         // );
-                //         // if size.width != width && size.height != height {
-                //         self.layer.wl_surface().damage_buffer(
-                //             position.x,
-                //             position.y,
-                //             size.width as i32,
-                //             size.height as i32,
-                //         );
-                //         //}
-                //     }
-                // }
-                // Request our next frame
-                self.layer.as_ref().unwrap().wl_surface().attach(
-                    Some(buffer.as_ref().unwrap().wl_buffer()),
-                    0,
-                    0,
-                );
-            }
+        let window_adapter = self.adapter.clone().unwrap();
+
+        // Hidden surfaces don't have a buffer attached, so nothing is drawn.
+        if self.is_hidden.get() {
+            return;
+        }
+        self.states
+            .pointer_state
+            .update_cursor(window_adapter.current_cursor.get(), qh);
+        // The frame of the new size is drawn on the configure following the resize.
+        if self.fit_to_content() {
+            return;
+        }
+        // A new frame is only drawn after the compositor has presented the last one,
+        // except after a configure which needs to be answered right away.
+        if !self.first_configure.get() && window_adapter.frame_pending.get() {
+            return;
+        }
 
+        // FIXME: Rendering should take place between the sources, here it
+        // should just be setting the buffers.
+        let redraw_val: bool = window_adapter.draw_if_needed();
+        if self.first_configure.get() || redraw_val {
+            // Only the parts changed by slint are damaged, except for the first
+            // frame after a configure.
+            damage_surface(
+                self.layer.as_ref().unwrap().wl_surface(),
+                &window_adapter,
+                self.first_configure.replace(false),
+            );
+            window_adapter.attach_buffer(self.layer.as_ref().unwrap().wl_surface());
+            // Frames are only requested after a draw, so an unchanged UI
+            // doesn't keep the compositor (and us) busy. Slint requests a redraw
+            // for every step of a running animation, which in turn are drawn on
+            // the frame callbacks.
             self.layer
                 .as_ref()
                 .unwrap()
                 .wl_surface()
                 .frame(qh, self.layer.as_ref().unwrap().wl_surface().clone());
-            self.layer.as_ref().unwrap().commit();
-        } else {
+            window_adapter.frame_pending.set(true);
             self.layer.as_ref().unwrap().commit();
         }
     }
@@ -105,42 +439,34 @@ impl SpellWin {
     /// Fetches the available monitors from the Wayland registry.
     ///
     /// This function fetches the available monitors from the Wayland registry
-    /// and returns a map of the available monitors where the key is the name
-    /// of the monitor and the value is [`wl_output::WlOutput`] with its assosiated
-    /// logical size(width, height). Dimentions are later used in size determination.
+    /// and returns them along with their [`OutputInfo`], which is used to resolve
+    /// the [`MonitorSelector`](crate::layer_properties::MonitorSelector) of the widget and to determine its size.
     /// It uses an already registered event queue & spell window.
     ///
     /// # Errors
     ///
-    /// Returns `None` if the registry queue could not be initialized.
+    /// Returns `None` if the roundtrip with the compositor fails.
     pub(super) fn get_available_monitors(
         event_queue: &mut EventQueue<SpellWin>,
         win: &mut SpellWin,
-    ) -> Option<HashMap<String, (wl_output::WlOutput, i32, i32)>> {
+    ) -> Option<Vec<(wl_output::WlOutput, OutputInfo)>> {
         // roundtrip to get all available monitors from Wayland
         event_queue.roundtrip(win).ok()?;
+        Some(outputs_with_info(&win.states.output_state))
+    }
 
-        Some(
-            win.states
-                .output_state
-                .outputs()
-                .filter_map(|output| {
-                    let info = win.states.output_state.info(&output)?;
-                    Some((
-                        info.name?,
-                        (output, info.logical_size?.0, info.logical_size?.1),
-                    ))
-                })
-                .collect(),
-        )
+    /// Sets the output of a widget created on the output chosen by the compositor
+    /// (see [`MonitorSelector::Focused`](crate::layer_properties::MonitorSelector::Focused)) once its surface enters it.
+    pub(super) fn output_entered(&mut self, output: &wl_output::WlOutput) {
+        if self.output.is_some() {
+            return;
+        }
+        info!("Win: layer is shown on the output chosen by the compositor");
+        self.output = Some((output.clone(), 0, 0));
+        self.output_updated(output);
     }
 
-    pub(super) fn set_event_sources(
-        &self,
-        handle: HomeHandle,
-        slint_event_receiver: calloop::channel::Channel<Box<dyn FnOnce() + Send>>,
-    ) {
-        let event_loop = self.event_loop.as_ref().borrow();
+    pub(super) fn set_event_sources(&self, handle: HomeHandle) {
         // let backspace_event = event_loop
         //     .handle()
         //     .insert_source(
@@ -162,88 +488,138 @@ impl SpellWin {
         let logging_dir = runtime_dir + "/spell/";
         let socket_cli_dir = logging_dir.clone() + "/spell_cli";
 
-        // This is currently redundent source as it is not working in any way
-        event_loop
-            .handle()
-            .insert_source(
-                Timer::from_duration(Duration::from_secs(2)),
-                move |_, _, _| {
-                    let file = fs::File::open(&socket_cli_dir)
-                        .unwrap_or_else(|_| fs::File::create_new(&socket_cli_dir).unwrap());
-                    let buf = BufReader::new(file);
-                    let file_contents: Vec<String> = buf
-                        .lines()
-                        .map(|l| l.expect("Could not parse line"))
-                        .collect();
-                    if !file_contents.is_empty() {
-                        match file_contents[0].as_str() {
-                            "slint_log" => {
-                                handle
-                                    .modify(|layer| {
-                                        *layer.filter_mut() = EnvFilter::new(
-                                            "spell_framework::slint_adapter=info,warn",
-                                        );
-                                    })
-                                    .unwrap_or_else(|error| {
-                                        warn!("Error when setting slint_log: {}", error);
-                                    });
-                            }
-                            "debug" => {
-                                handle
-                                    .modify(|layer| {
-                                        *layer.filter_mut() =
-                                            EnvFilter::new("spell_framework=info,warn"); //*layer;
-                                    })
-                                    .unwrap_or_else(|error| {
-                                        warn!("Error when setting slint_log: {}", error);
-                                    });
-                            }
-                            "dump" => {
-                                handle
-                                    .modify(|layer| {
-                                        *layer.filter_mut() =
-                                            EnvFilter::new("spell_framework=trace,info"); //*layer;
-                                    })
-                                    .unwrap_or_else(|error| {
-                                        warn!("Error when setting slint_log: {}", error);
-                                    });
-                            }
-                            "dev" => {
-                                handle
-                                    .modify(|layer| {
-                                        *layer.filter_mut() =
-                                            EnvFilter::new("spell_framework=trace,warn"); //*layer;
-                                    })
-                                    .unwrap_or_else(|error| {
-                                        warn!("Error when setting slint_log: {}", error);
-                                    });
-                            }
-                            val => {
-                                warn!("Something else came: {}", val);
-                            }
+        // This is currently redundent source as it is not working in any way.
+        // It is a slint timer so that the shared event loop knows when to wake up
+        // for it.
+        self.log_timer.start(
+            slint::TimerMode::Repeated,
+            Duration::from_secs(2),
+            move || {
+                let file = fs::File::open(&socket_cli_dir)
+                    .unwrap_or_else(|_| fs::File::create_new(&socket_cli_dir).unwrap());
+                let buf = BufReader::new(file);
+                let file_contents: Vec<String> = buf
+                    .lines()
+                    .map(|l| l.expect("Could not parse line"))
+                    .collect();
+                if !file_contents.is_empty() {
+                    match file_contents[0].as_str() {
+                        "slint_log" => {
+                            handle
+                                .modify(|layer| {
+                                    *layer.filter_mut() =
+                                        EnvFilter::new("spell_framework::slint_adapter=info,warn");
+                                })
+                                .unwrap_or_else(|error| {
+                                    warn!("Error when setting slint_log: {}", error);
+                                });
+                        }
+                        "debug" => {
+                            handle
+                                .modify(|layer| {
+                                    *layer.filter_mut() =
+                                        EnvFilter::new("spell_framework=info,warn"); //*layer;
+                                })
+                                .unwrap_or_else(|error| {
+                                    warn!("Error when setting slint_log: {}", error);
+                                });
+                        }
+                        "dump" => {
+                            handle
+                                .modify(|layer| {
+                                    *layer.filter_mut() =
+                                        EnvFilter::new("spell_framework=trace,info"); //*layer;
+                                })
+                                .unwrap_or_else(|error| {
+                                    warn!("Error when setting slint_log: {}", error);
+                                });
+                        }
+                        "dev" => {
+                            handle
+                                .modify(|layer| {
+                                    *layer.filter_mut() =
+                                        EnvFilter::new("spell_framework=trace,warn"); //*layer;
+                                })
+                                .unwrap_or_else(|error| {
+                                    warn!("Error when setting slint_log: {}", error);
+                                });
+                        }
+                        val => {
+                            warn!("Something else came: {}", val);
                         }
                     }
-                    TimeoutAction::ToDuration(Duration::from_secs(2))
-                },
-            )
-            .unwrap();
-
-        event_loop
-            .handle()
-            .insert_source(slint_event_receiver, |event, _, data| {
-                if let calloop::channel::Event::Msg(callback) = event {
-                    callback();
-                    data.adapter.as_ref().unwrap().request_redraw();
                 }
-            })
-            .unwrap();
+            },
+        );
+    }
+
+    /// Watches the file the configuration is loaded from (if it is) and applies
+    /// its changes to the widget. The directory of the file is watched, as editors
+    /// often replace the file instead of writing into it.
+    pub(super) fn watch_config_file(&self) {
+        let Some((path, section)) = self.config.config_file.clone() else {
+            return;
+        };
+        let Some(file_name) = path.file_name().map(|name| name.to_os_string()) else {
+            warn!("Win: config path {} is not a file", path.display());
+            return;
+        };
+        let directory = match path.parent() {
+            Some(parent) if !parent.as_os_str().is_empty() => parent.to_path_buf(),
+            _ => PathBuf::from("."),
+        };
+        let inotify = match Inotify::init() {
+            Ok(inotify) => inotify,
+            Err(err) => {
+                warn!("Win: couldn't watch the config file: {}", err);
+                return;
+            }
+        };
+        if let Err(err) = inotify.watches().add(
+            &directory,
+            WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::CREATE,
+        ) {
+            warn!("Win: couldn't watch {}: {}", directory.display(), err);
+            return;
+        }
+        let result = self.loop_handle.insert_source(
+            Generic::new(inotify, Interest::READ, Mode::Level),
+            move |_, inotify, win| {
+                let mut buffer = [0; 1024];
+                // SAFETY: The inotify instance is neither dropped nor replaced here.
+                let events = match unsafe { inotify.get_mut() }.read_events(&mut buffer) {
+                    Ok(events) => events,
+                    Err(err) => {
+                        warn!("Win: couldn't read the config file events: {}", err);
+                        return Ok(PostAction::Continue);
+                    }
+                };
+                let changed = events
+                    .into_iter()
+                    .any(|event| event.name == Some(file_name.as_os_str()));
+                if changed {
+                    info!("Win: config file {} changed", path.display());
+                    match WindowConf::from_file(&path, &section) {
+                        Ok(window_conf) => win.apply_window_conf(window_conf),
+                        Err(err) => warn!("Win: couldn't reload the config file: {}", err),
+                    }
+                }
+                Ok(PostAction::Continue)
+            },
+        );
+        if let Err(err) = result {
+            warn!("Win: couldn't watch the config file: {}", err);
+        }
     }
 
     pub(super) fn create_popup_core(&mut self, popup_conf: PopupConf) -> Option<PopupCore> {
+        let Some(xdg_shell) = self.xdg_shell.as_ref() else {
+            warn!("couldn't create a popup, xdg shell is not available");
+            return None;
+        };
         let popup_surface = self.states.compositor_state.create_surface(&self.queue);
         // popup_surface.commit();
-        let position =
-            XdgPositioner::new(&self.xdg_shell).expect("Failed to created XdgPositioner");
+        let position = XdgPositioner::new(xdg_shell).expect("Failed to created XdgPositioner");
         position.set_size(popup_conf.width as i32, popup_conf.height as i32);
         position.set_parent_size(
             self.config.evaluated_width as i32,
@@ -263,10 +639,10 @@ impl SpellWin {
             &position,
             &self.queue,
             popup_surface,
-            &self.xdg_shell,
+            xdg_shell,
         ) {
             let pool = SlotPool::new(
-                (popup_conf.width * popup_conf.height * 4) as usize,
+                (popup_conf.width * popup_conf.height * 4 * 2) as usize,
                 &self.states.shm,
             )
             .expect("Unable to create slot pool for popup");
@@ -277,8 +653,8 @@ impl SpellWin {
             Some(self.popup_manager.create_popup_core(
                 popup,
                 popup_conf,
-                &self.states.fractional_scale_state,
-                &self.states.viewporter_state,
+                self.states.fractional_scale_state.as_ref(),
+                self.states.viewporter_state.as_ref(),
                 &self.queue,
             ))
         } else {
@@ -288,13 +664,54 @@ impl SpellWin {
     }
 }
 
+/// Size of the output on an axis left after the margins of that axis.
+fn available_size(output_size: i32, margins: i32) -> u32 {
+    (output_size - margins).max(1) as u32
+}
+
+/// Returns if the width and height of the widget are sized by the compositor. It
+/// is the case for `Full` dimensions of axes anchored to both of their edges.
+fn fills_axes(window_conf: &WindowConf) -> (bool, bool) {
+    let anchor = window_conf
+        .anchor
+        .into_iter()
+        .flatten()
+        .fold(Anchor::empty(), |combined, anchor| combined | anchor);
+    (
+        matches!(window_conf.width, Dimension::Full)
+            && anchor.contains(Anchor::LEFT | Anchor::RIGHT),
+        matches!(window_conf.height, Dimension::Full)
+            && anchor.contains(Anchor::TOP | Anchor::BOTTOM),
+    )
+}
+
+/// Returns the size to be requested from the compositor. Axes which are filled
+/// are requested as 0, so that the compositor sizes them to the area left by
+/// margins and exclusive zones of other layers.
+fn requested_size(window_conf: &WindowConf) -> (u32, u32) {
+    let (fills_width, fills_height) = fills_axes(window_conf);
+    (
+        if fills_width {
+            0
+        } else {
+            window_conf.evaluated_width
+        },
+        if fills_height {
+            0
+        } else {
+            window_conf.evaluated_height
+        },
+    )
+}
+
 fn set_config(
     window_conf: &WindowConf,
     layer: &LayerSurface,
     input_region: Option<&WlRegion>,
     opaque_region: Option<&WlRegion>,
 ) {
-    layer.set_size(window_conf.evaluated_width, window_conf.evaluated_height);
+    let (width, height) = requested_size(window_conf);
+    layer.set_size(width, height);
     layer.set_margin(
         window_conf.margin.0,
         window_conf.margin.1,
@@ -313,14 +730,15 @@ fn set_config(
 }
 
 fn set_anchor(window_conf: &WindowConf, layer: &LayerSurface) {
-    let mut anchors = window_conf.anchor.into_iter().flatten();
-    if let Some(mut combined) = anchors.next() {
-        for a in anchors {
-            combined.insert(a);
-        }
-        layer.set_anchor(combined);
-    }
-    if let Some(val) = window_conf.exclusive_zone {
-        layer.set_exclusive_zone(val);
-    }
+    // An empty anchor centers the widget, it is set too so that anchors can be
+    // removed at runtime.
+    let combined = window_conf
+        .anchor
+        .into_iter()
+        .flatten()
+        .fold(Anchor::empty(), |combined, anchor| combined | anchor);
+    layer.set_anchor(combined);
+    // Zone of 0 is the default, it is set so that a removed exclusive zone is
+    // reset on reconfiguration.
+    layer.set_exclusive_zone(window_conf.exclusive_zone.unwrap_or(0));
 }
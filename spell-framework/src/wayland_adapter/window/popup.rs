@@ -1,14 +1,11 @@
 use slint::platform::WindowAdapter;
 use smithay_client_toolkit::{
     reexports::{
-        client::{
-            QueueHandle,
-            protocol::{wl_shm, wl_surface::WlSurface},
-        },
+        client::{QueueHandle, protocol::wl_surface::WlSurface},
         protocols::xdg::shell::client::xdg_surface::XdgSurface,
     },
     shell::xdg::popup::Popup,
-    shm::slot::{Buffer, SlotPool},
+    shm::slot::SlotPool,
 };
 use std::{
     cell::{Cell, RefCell},
@@ -23,6 +20,7 @@ use crate::{
     slint_adapter::{ADAPTERS, SpellSkiaWinAdapter},
     wayland_adapter::{
         SpellWin,
+        common::damage_surface,
         fractional_scaling::{
             FractionalScaleHandler, FractionalScaleState, delegate_fractional_scale,
         },
@@ -62,25 +60,12 @@ impl PopupManager {
         &mut self,
         popup: Popup,
         popup_conf: PopupConf,
-        _fractional_scale_state: &FractionalScaleState,
-        _viewporter_state: &ViewporterState,
+        _fractional_scale_state: Option<&FractionalScaleState>,
+        _viewporter_state: Option<&ViewporterState>,
         _qh: &QueueHandle<SpellWin>,
     ) -> PopupCore {
         // let fractional_scale = fractional_scale_state.get_scale(popup.wl_surface(), qh);
         // let viewport = viewporter_state.get_viewport(popup.wl_surface(), qh, fractional_scale);
-        let stride = popup_conf.width as i32 * 4;
-        let (buffer, _) = self
-            .pool
-            .as_ref()
-            .unwrap()
-            .borrow_mut()
-            .create_buffer(
-                popup_conf.width as i32,
-                popup_conf.height as i32,
-                stride,
-                wl_shm::Format::Argb8888,
-            )
-            .expect("failed to create buffer for popup");
         // viewport.set_destination(popup_conf.width as i32, popup_conf.height as i32);
         // popup.wl_surface().attach(Some(buffer.wl_buffer()), 0, 0);
         // popup
@@ -104,7 +89,6 @@ impl PopupManager {
             pool: self.pool.as_ref().unwrap().clone(),
             popup,
             popup_conf,
-            buffer,
             // viewport,
         }
     }
@@ -153,6 +137,20 @@ impl PopupManager {
             );
         };
     }
+
+    /// Closes every popup, used when the parent widget is removed.
+    pub(super) fn close_all(&mut self) {
+        for (id, popup) in self.popups.drain() {
+            popup.inner().xdg_popup().destroy();
+            info!("Removed Popup with id: {}", id);
+        }
+    }
+
+    pub(super) fn owns_adapter(&self, adapter: &Rc<SpellSkiaWinAdapter>) -> bool {
+        self.popups
+            .values()
+            .any(|popup| Rc::ptr_eq(popup.adapter(), adapter))
+    }
 }
 
 /// This struct holds the backend information for creating and managing a XDG
@@ -164,7 +162,6 @@ impl PopupManager {
 pub struct SpellXDGPopup {
     adapter: Rc<SpellSkiaWinAdapter>,
     popup: Popup,
-    buffer: Buffer,
     first_configure: Cell<bool>,
     // viewport: Viewport,
 }
@@ -177,7 +174,6 @@ impl SpellXDGPopup {
     pub fn new(popup_settings: PopupCore) -> Self {
         let adapter_value: Rc<SpellSkiaWinAdapter> = SpellSkiaWinAdapter::new(
             popup_settings.pool,
-            RefCell::new(popup_settings.buffer.slot()),
             popup_settings.popup_conf.width,
             popup_settings.popup_conf.height,
         );
@@ -185,7 +181,6 @@ impl SpellXDGPopup {
         SpellXDGPopup {
             adapter: adapter_value,
             popup: popup_settings.popup,
-            buffer: popup_settings.buffer,
             first_configure: Cell::new(true),
             // viewport: popup_settings.viewport,
         }
@@ -213,38 +208,24 @@ impl SpellXDGPopup {
 
     /// Method necessary for a [`PopupSlint`] implementation.
     pub fn converter_popup<'a>(&self, wl_surface: &'a WlSurface, qh: &'a QueueHandle<SpellWin>) {
+        // Nothing can be attached to the popup before its first configure.
+        if self.first_configure.get() {
+            return;
+        }
         slint::platform::update_timers_and_animations();
-        let width: u32 = self.adapter.as_ref().size.get().width;
-        let height: u32 = self.adapter.as_ref().size.get().height;
         let window_adapter = self.adapter.clone();
+        // Waits for the compositor to present the last frame.
+        if window_adapter.frame_pending.get() {
+            return;
+        }
 
         let redraw_val: bool = window_adapter.draw_if_needed();
-        let buffer = &self.buffer;
-        if self.first_configure.get() || redraw_val {
-            // if self.first_configure {
-            // self.first_configure.set(false);
-            wl_surface.damage_buffer(0, 0, width as i32, height as i32);
-            // } else {
-            //     for (position, size) in self.damaged_part.as_ref().unwrap().iter() {
-            //         // println!(
-            //         //     "{}, {}, {}, {}",
-            //         //     position.x, position.y, size.width as i32, size.height as i32,
-            //         // );
-            //         // if size.width != width && size.height != height {
-            //         self.layer.wl_surface().damage_buffer(
-            //             position.x,
-            //             position.y,
-            //             size.width as i32,
-            //             size.height as i32,
-            //         );
-            //         //}
-            //     }
-            // }
+        if redraw_val {
+            damage_surface(wl_surface, &self.adapter, false);
             // Request our next frame
-            wl_surface.attach(Some(buffer.wl_buffer()), 0, 0);
+            window_adapter.attach_buffer(wl_surface);
             wl_surface.frame(qh, wl_surface.clone());
-            wl_surface.commit();
-        } else {
+            window_adapter.frame_pending.set(true);
             wl_surface.commit();
         }
     }
@@ -272,10 +253,9 @@ impl FractionalScaleHandler for SpellXDGPopup {
             self.adapter.size.get().height as i32,
         );
         // FIXME: Make use of this for proper scaling implementation.
-        let (buffer, _width, _height, scale_factor) = self.adapter.changed_scale_factor(scale);
+        let (_width, _height, scale_factor) = self.adapter.changed_scale_factor(scale);
         // self.width = width;
         // self.height = height;
-        self.buffer = buffer;
         self.adapter
             .try_dispatch_event(slint::platform::WindowEvent::ScaleFactorChanged { scale_factor })
             .unwrap();
@@ -1,14 +1,40 @@
-use crate::wayland_adapter::{fractional_scaling::FractionalScaleHandler, window::SpellWin};
-use slint::platform::WindowAdapter;
+use crate::wayland_adapter::{
+    clipboard,
+    data_device::{DropEvent, read_pipe, write_pipe},
+    fractional_scaling::FractionalScaleHandler,
+    gestures::{Gesture, PointerGestureHandler},
+    text_input::{TextInputChanges, TextInputHandler},
+    window::SpellWin,
+};
+use slint::platform::{Clipboard, WindowAdapter, WindowEvent};
 use smithay_client_toolkit::{
     compositor::CompositorHandler,
+    data_device_manager::{
+        WritePipe,
+        data_device::{DataDeviceData, DataDeviceHandler},
+        data_offer::{DataOfferHandler, DragOffer},
+        data_source::DataSourceHandler,
+    },
     output::{OutputHandler, OutputState},
+    primary_selection::{
+        device::{PrimarySelectionDeviceData, PrimarySelectionDeviceHandler},
+        selection::PrimarySelectionSourceHandler,
+    },
     reexports::{
         client::{
             Connection, Dispatch, QueueHandle,
-            protocol::{wl_output, wl_seat, wl_surface},
+            protocol::{
+                wl_data_device::WlDataDevice, wl_data_device_manager::DndAction,
+                wl_data_source::WlDataSource, wl_output, wl_seat, wl_surface,
+            },
+        },
+        protocols::{
+            wp::primary_selection::zv1::client::{
+                zwp_primary_selection_device_v1::ZwpPrimarySelectionDeviceV1,
+                zwp_primary_selection_source_v1::ZwpPrimarySelectionSourceV1,
+            },
+            xdg::shell::client::xdg_surface::XdgSurface,
         },
-        protocols::xdg::shell::client::xdg_surface::XdgSurface,
     },
     registry::{ProvidesRegistryState, RegistryState},
     registry_handlers,
@@ -58,6 +84,16 @@ impl SeatHandler for SpellWin {
         seat: wl_seat::WlSeat,
         capability: Capability,
     ) {
+        if self.states.data_device.is_none()
+            && let Some(manager) = &self.states.data_device_manager
+        {
+            self.states.data_device = Some(manager.get_data_device(qh, &seat));
+        }
+        if self.states.primary_selection_device.is_none()
+            && let Some(manager) = &self.states.primary_selection_manager
+        {
+            self.states.primary_selection_device = Some(manager.get_selection_device(qh, &seat));
+        }
         if capability == Capability::Keyboard && self.states.keyboard_state.is_none() {
             info!("Setting keyboard capability");
             let keyboard = self
@@ -66,6 +102,9 @@ impl SeatHandler for SpellWin {
                 .get_keyboard(qh, &seat, None)
                 .expect("Failed to create keyboard");
             self.states.keyboard_state = Some(keyboard);
+            if let Some(text_input_state) = &self.states.text_input_state {
+                self.states.text_input = Some(text_input_state.get_text_input(&seat, qh));
+            }
         }
         if capability == Capability::Touch && self.states.touch_state.is_none() {
             info!("Setting touch Capability");
@@ -83,6 +122,10 @@ impl SeatHandler for SpellWin {
                 .seat_state
                 .get_pointer(qh, &seat)
                 .expect("Failed to create pointer");
+            if let Some(pointer_gestures_state) = &self.states.pointer_gestures_state {
+                self.states.pointer_gestures =
+                    Some(pointer_gestures_state.get_gestures(&pointer, qh));
+            }
             let pointer_data = PointerData::new(seat);
             self.states.pointer_state.pointer = Some(pointer);
             self.states.pointer_state.pointer_data = Some(pointer_data);
@@ -99,10 +142,12 @@ impl SeatHandler for SpellWin {
         if capability == Capability::Keyboard && self.states.keyboard_state.is_some() {
             info!("Unsetting keyboard capability");
             self.states.keyboard_state.take().unwrap().release();
+            self.states.text_input = None;
         }
 
         if capability == Capability::Pointer && self.states.pointer_state.pointer.is_some() {
             info!("Unsetting pointer capability");
+            self.states.pointer_gestures = None;
             self.states.pointer_state.pointer.take().unwrap().release();
         }
         if capability == Capability::Touch && self.states.touch_state.is_some() {
@@ -176,9 +221,10 @@ impl OutputHandler for SpellWin {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _output: wl_output::WlOutput,
+        output: wl_output::WlOutput,
     ) {
         trace!("Existing output is updated");
+        self.output_updated(&output);
     }
 
     fn output_destroyed(
@@ -195,11 +241,27 @@ impl CompositorHandler for SpellWin {
     fn scale_factor_changed(
         &mut self,
         _: &Connection,
-        _: &QueueHandle<Self>,
-        _: &wl_surface::WlSurface,
-        _: i32,
+        qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        new_factor: i32,
     ) {
-        info!("Scale factor changed, compositor msg");
+        info!("Scale factor changed, compositor msg: {}", new_factor);
+        // Preferred scale of fractional scale is used whenever it is available.
+        if self.viewport.is_some() || self.layer.as_ref().unwrap().wl_surface() != surface {
+            return;
+        }
+        let adapter = self.adapter.clone().unwrap();
+        let (_width, _height, scale_factor) =
+            adapter.changed_scale_factor(new_factor.max(1) as u32 * 120);
+        surface.set_buffer_scale(new_factor.max(1));
+        adapter
+            .try_dispatch_event(slint::platform::WindowEvent::ScaleFactorChanged { scale_factor })
+            .unwrap_or_else(|err| warn!("Scale factor event failed with error: {:?}", err));
+        // Buffer of the new scale has to be attached along with it.
+        self.first_configure.set(true);
+        if self.is_configured.get() {
+            self.converter(qh);
+        }
     }
 
     fn transform_changed(
@@ -216,9 +278,15 @@ impl CompositorHandler for SpellWin {
         &mut self,
         _conn: &Connection,
         qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
+        surface: &wl_surface::WlSurface,
         _time: u32,
     ) {
+        // The last frame of the surface is presented, a new one can be drawn.
+        if self.layer.as_ref().unwrap().wl_surface() == surface {
+            self.adapter.as_ref().unwrap().frame_pending.set(false);
+        } else if let Some(adapter) = self.popup_manager.return_adapter(surface) {
+            adapter.frame_pending.set(false);
+        }
         self.converter(qh);
         self.popup_manager.redraw_popups(qh);
     }
@@ -227,10 +295,13 @@ impl CompositorHandler for SpellWin {
         &mut self,
         _conn: &Connection,
         _qh: &QueueHandle<Self>,
-        _surface: &wl_surface::WlSurface,
-        _output: &wl_output::WlOutput,
+        surface: &wl_surface::WlSurface,
+        output: &wl_output::WlOutput,
     ) {
         trace!("Surface entered");
+        if self.layer.as_ref().unwrap().wl_surface() == surface {
+            self.output_entered(output);
+        }
     }
 
     fn surface_leave(
@@ -261,27 +332,23 @@ impl FractionalScaleHandler for SpellWin {
             self.adapter.as_ref().unwrap().size.get().width as i32,
             self.adapter.as_ref().unwrap().size.get().height as i32,
         );
-        let (buffer, width, height, scale_factor) =
+        // Evaluated size stays in logical pixels, only the buffers are scaled.
+        let (_width, _height, scale_factor) =
             self.adapter.as_ref().unwrap().changed_scale_factor(scale);
-        self.config.evaluated_width = width;
-        self.config.evaluated_height = height;
-        self.buffer = Some(buffer);
         self.adapter
             .as_ref()
             .unwrap()
             .try_dispatch_event(slint::platform::WindowEvent::ScaleFactorChanged { scale_factor })
             .unwrap();
-        self.viewport.as_ref().unwrap().set_source(
-            0.,
-            0.,
-            self.adapter.as_ref().unwrap().size.get().width.into(),
-            self.adapter.as_ref().unwrap().size.get().height.into(),
-        );
-
-        self.viewport
-            .as_ref()
-            .unwrap()
-            .set_destination(width_old as i32, height_old as i32);
+        if let Some(viewport) = self.viewport.as_ref() {
+            viewport.set_source(
+                0.,
+                0.,
+                self.adapter.as_ref().unwrap().size.get().width.into(),
+                self.adapter.as_ref().unwrap().size.get().height.into(),
+            );
+            viewport.set_destination(width_old as i32, height_old as i32);
+        }
         self.adapter.as_ref().unwrap().request_redraw();
         self.layer.as_ref().unwrap().commit();
     }
@@ -297,9 +364,11 @@ impl LayerShellHandler for SpellWin {
         _conn: &Connection,
         qh: &QueueHandle<Self>,
         _layer: &LayerSurface,
-        _configure: LayerSurfaceConfigure,
+        configure: LayerSurfaceConfigure,
         _serial: u32,
     ) {
+        self.is_configured.set(true);
+        self.fit_to_configure(configure.new_size);
         self.converter(qh);
     }
 }
@@ -322,3 +391,350 @@ impl Dispatch<XdgSurface, ()> for SpellWin {
         }
     }
 }
+
+impl TextInputHandler for SpellWin {
+    fn text_input_enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+    ) {
+        trace!("Text input focus entered");
+        let Some(adapter) = self.adapter_for_surface(surface) else {
+            return;
+        };
+        if let Some(text_input) = self.states.text_input.as_mut() {
+            text_input.update(&adapter, true);
+        }
+    }
+
+    fn text_input_leave(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _surface: &wl_surface::WlSurface,
+    ) {
+        trace!("Text input focus left");
+        if let Some(text_input) = self.states.text_input.as_mut() {
+            text_input.disable();
+        }
+    }
+
+    fn text_input_done(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        surface: &wl_surface::WlSurface,
+        changes: TextInputChanges,
+    ) {
+        if let Some(adapter) = self.adapter_for_surface(surface) {
+            changes.apply(&adapter);
+        }
+    }
+}
+
+impl PointerGestureHandler for SpellWin {
+    fn pointer_gesture(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, gesture: Gesture) {
+        trace!("Pointer gesture: {:?}", gesture);
+        if let Some(callback) = self.gesture_callback.as_mut() {
+            callback(gesture);
+        }
+    }
+}
+
+impl SpellWin {
+    /// Sends the position of a drag over the widget to slint as pointer motion, so
+    /// that drop areas can be highlighted through `has-hover`.
+    fn drag_motion(&mut self, surface: &wl_surface::WlSurface, x: f64, y: f64) {
+        if let Some(adapter) = self.adapter_for_surface(surface) {
+            adapter
+                .try_dispatch_event(WindowEvent::PointerMoved {
+                    position: slint::LogicalPosition {
+                        x: x as f32,
+                        y: y as f32,
+                    },
+                })
+                .unwrap_or_else(|err| warn!("Drag motion event failed with error: {:?}", err));
+        }
+    }
+}
+
+fn drag_offer(data_device: &WlDataDevice) -> Option<DragOffer> {
+    data_device
+        .data::<DataDeviceData>()
+        .and_then(|data| data.drag_offer())
+}
+
+impl DataDeviceHandler for SpellWin {
+    fn enter(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        data_device: &WlDataDevice,
+        x: f64,
+        y: f64,
+        surface: &wl_surface::WlSurface,
+    ) {
+        let Some(offer) = drag_offer(data_device) else {
+            return;
+        };
+        let mime_types = offer.with_mime_types(|mime_types| mime_types.to_vec());
+        trace!("Drag entered with MIME types: {:?}", mime_types);
+        let accepted = self.dnd.negotiate(&mime_types);
+        offer.accept_mime_type(offer.serial, accepted.clone());
+        if accepted.is_some() {
+            offer.set_actions(DndAction::Copy | DndAction::Move, DndAction::Copy);
+        } else {
+            offer.set_actions(DndAction::empty(), DndAction::empty());
+        }
+        self.dnd.accepted = accepted.clone();
+        self.dnd.dropped = false;
+        self.drag_motion(surface, x, y);
+        self.dnd.emit(DropEvent::Enter {
+            mime_types,
+            accepted,
+            x,
+            y,
+        });
+    }
+
+    fn leave(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, data_device: &WlDataDevice) {
+        trace!("Drag left");
+        self.dnd.accepted = None;
+        if std::mem::take(&mut self.dnd.dropped) {
+            return;
+        }
+        if let Some(offer) = drag_offer(data_device)
+            && let Some(adapter) = self.adapter_for_surface(&offer.surface)
+        {
+            adapter
+                .try_dispatch_event(WindowEvent::PointerExited)
+                .unwrap_or_else(|err| warn!("Drag leave event failed with error: {:?}", err));
+        }
+        self.dnd.emit(DropEvent::Leave);
+    }
+
+    fn motion(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        data_device: &WlDataDevice,
+        x: f64,
+        y: f64,
+    ) {
+        let Some(offer) = drag_offer(data_device) else {
+            return;
+        };
+        self.drag_motion(&offer.surface, x, y);
+        self.dnd.emit(DropEvent::Motion { x, y });
+    }
+
+    fn selection(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        data_device: &WlDataDevice,
+    ) {
+        let offer = data_device
+            .data::<DataDeviceData>()
+            .and_then(|data| data.selection_offer());
+        trace!("Clipboard selection changed");
+        match offer {
+            Some(offer) => {
+                let mime_types = offer.with_mime_types(|mime_types| mime_types.to_vec());
+                let receive = self.selection_receiver(move |mime_type| offer.receive(mime_type));
+                clipboard::set_selection(Clipboard::DefaultClipboard, mime_types, Some(receive));
+            }
+            None => clipboard::set_selection(Clipboard::DefaultClipboard, Vec::new(), None),
+        }
+    }
+
+    fn drop_performed(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        data_device: &WlDataDevice,
+    ) {
+        let Some(offer) = drag_offer(data_device) else {
+            return;
+        };
+        self.dnd.dropped = true;
+        let Some(mime_type) = self.dnd.accepted.take() else {
+            offer.destroy();
+            return;
+        };
+        info!("Win: data dropped as {}", mime_type);
+        let pipe = match offer.receive(mime_type.clone()) {
+            Ok(pipe) => pipe,
+            Err(err) => {
+                warn!("Win: couldn't receive the dropped data: {:?}", err);
+                offer.destroy();
+                return;
+            }
+        };
+        let (x, y) = (offer.x, offer.y);
+        read_pipe(&self.loop_handle, pipe, move |win: &mut SpellWin, data| {
+            match data {
+                Ok(data) => win.dnd.emit(DropEvent::Drop {
+                    mime_type,
+                    data,
+                    x,
+                    y,
+                }),
+                Err(err) => warn!("Win: couldn't read the dropped data: {}", err),
+            }
+            offer.finish();
+            offer.destroy();
+        });
+    }
+}
+
+impl DataOfferHandler for SpellWin {
+    fn source_actions(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        offer: &mut DragOffer,
+        actions: DndAction,
+    ) {
+        trace!("Drag source actions: {:?}", actions);
+        if self.dnd.accepted.is_some() {
+            offer.set_actions(DndAction::Copy | DndAction::Move, DndAction::Copy);
+        }
+    }
+
+    fn selected_action(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _offer: &mut DragOffer,
+        actions: DndAction,
+    ) {
+        trace!("Drag selected action: {:?}", actions);
+    }
+}
+
+impl DataSourceHandler for SpellWin {
+    fn accept_mime(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+        mime: Option<String>,
+    ) {
+        trace!("Drag target accepted MIME type: {:?}", mime);
+    }
+
+    fn send_request(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &WlDataSource,
+        mime: String,
+        fd: WritePipe,
+    ) {
+        let drag_data = self
+            .dnd
+            .source
+            .as_ref()
+            .filter(|(drag_source, _)| drag_source.inner() == source)
+            .map(|(_, data)| data);
+        let copy_data = self
+            .clipboard
+            .source
+            .as_ref()
+            .filter(|(copy_source, _)| copy_source.inner() == source)
+            .map(|(_, data)| data);
+        let data = drag_data.or(copy_data).and_then(|data| data.get(&mime));
+        match data {
+            Some(data) => write_pipe(&self.loop_handle, fd, data),
+            None => warn!("Win: data requested in unoffered MIME type {}", mime),
+        }
+    }
+
+    fn cancelled(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, source: &WlDataSource) {
+        trace!("Data source cancelled");
+        self.release_source(source);
+    }
+
+    fn dnd_dropped(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, _source: &WlDataSource) {
+        trace!("Drag dropped on target");
+    }
+
+    fn dnd_finished(&mut self, _conn: &Connection, _qh: &QueueHandle<Self>, source: &WlDataSource) {
+        info!("Win: drag finished");
+        self.release_source(source);
+    }
+
+    fn action(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        _source: &WlDataSource,
+        action: DndAction,
+    ) {
+        trace!("Drag action: {:?}", action);
+    }
+}
+
+impl PrimarySelectionDeviceHandler for SpellWin {
+    fn selection(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        primary_selection_device: &ZwpPrimarySelectionDeviceV1,
+    ) {
+        let offer = primary_selection_device
+            .data::<PrimarySelectionDeviceData>()
+            .and_then(|data| data.selection_offer());
+        trace!("Primary selection changed");
+        match offer {
+            Some(offer) => {
+                let mime_types = offer.with_mime_types(|mime_types| mime_types.to_vec());
+                let receive = self.selection_receiver(move |mime_type| offer.receive(mime_type));
+                clipboard::set_selection(Clipboard::SelectionClipboard, mime_types, Some(receive));
+            }
+            None => clipboard::set_selection(Clipboard::SelectionClipboard, Vec::new(), None),
+        }
+    }
+}
+
+impl PrimarySelectionSourceHandler for SpellWin {
+    fn send_request(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &ZwpPrimarySelectionSourceV1,
+        mime: String,
+        fd: WritePipe,
+    ) {
+        let data = self
+            .clipboard
+            .primary_source
+            .as_ref()
+            .filter(|(primary_source, _)| primary_source.inner() == source)
+            .and_then(|(_, data)| data.get(&mime));
+        match data {
+            Some(data) => write_pipe(&self.loop_handle, fd, data),
+            None => warn!("Win: data requested in unoffered MIME type {}", mime),
+        }
+    }
+
+    fn cancelled(
+        &mut self,
+        _conn: &Connection,
+        _qh: &QueueHandle<Self>,
+        source: &ZwpPrimarySelectionSourceV1,
+    ) {
+        trace!("Primary selection source cancelled");
+        if self
+            .clipboard
+            .primary_source
+            .as_ref()
+            .is_some_and(|(primary_source, _)| primary_source.inner() == source)
+        {
+            self.clipboard.primary_source = None;
+        }
+        source.destroy();
+    }
+}